@@ -21,10 +21,7 @@ fn main() {
             })
             .collect()
     } else {
-        vec![
-            PathBuf::new().join("cpp/fec.cpp"),
-            PathBuf::new().join("cpp/nal.cpp"),
-        ]
+        vec![PathBuf::new().join("cpp/nal.cpp")]
     };
 
     let mut builder = &mut cc::Build::new();
@@ -41,11 +38,6 @@ fn main() {
     }
     builder.compile("bindings");
 
-    cc::Build::new()
-        .cpp(false)
-        .files(&["cpp/reedsolomon/rs.c"])
-        .compile("bindings_rs_c");
-
     if platform_name == "android" {
         println!("cargo:rustc-link-lib=log");
         println!("cargo:rustc-link-lib=EGL");
@@ -12,14 +12,9 @@ struct EyeInput {
 struct VideoFrame {
     unsigned int packetCounter;
     unsigned long long trackingFrameIndex;
-    // FEC decoder needs some value for identify video frame number to detect new frame.
-    // trackingFrameIndex becomes sometimes same value as previous video frame (in case of low
-    // tracking rate).
     unsigned long long videoFrameIndex;
     unsigned long long sentTime;
     unsigned int frameByteSize;
-    unsigned int fecIndex;
-    unsigned short fecPercentage;
 };
 
 struct OnCreateResult {
@@ -54,16 +49,19 @@ extern "C" void prepareLobbyRoom(int viewWidth,
                                  int swapchainLength);
 extern "C" void destroyRenderers();
 extern "C" void setStreamConfig(StreamConfigInput config);
+extern "C" void setFoveationConfig(float centerSizeX,
+                                   float centerSizeY,
+                                   float centerShiftX,
+                                   float centerShiftY,
+                                   float edgeRatioX,
+                                   float edgeRatioY);
 extern "C" void streamStartNative(const int *swapchainTextures[2], int swapchainLength);
 extern "C" void updateLobbyHudTexture(const unsigned char *data);
 extern "C" void renderLobbyNative(const EyeInput eyeInputs[2], const int swapchainIndices[2]);
 extern "C" void renderStreamNative(const int swapchainIndices[2], void *streamHardwareBuffer);
 
 // nal.h
-extern "C" void initializeNalParser(int codec, bool enableFec);
-extern "C" bool processNalPacket(VideoFrame header,
-                                 const unsigned char *payload,
-                                 int payloadSize,
-                                 bool &outHadFecFailure);
+extern "C" void initializeNalParser(int codec);
+extern "C" bool processNalPacket(VideoFrame header, const unsigned char *payload, int payloadSize);
 extern "C" void (*createDecoder)(const char *csd_0, int length);
 extern "C" void (*pushNal)(const char *buffer, int length, unsigned long long frameIndex);
\ No newline at end of file
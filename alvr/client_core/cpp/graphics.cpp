@@ -133,6 +133,9 @@ class GraphicsContext {
     std::unique_ptr<ovrRenderer> lobbyRenderer;
 
     StreamConfigInput streamConfig{};
+    // Foveation parameters received while streaming, applied by the render thread
+    std::mutex streamConfigMutex;
+    bool foveationConfigChanged = false;
     std::unique_ptr<Texture> streamTexture;
     std::vector<GLuint> streamSwapchainTextures[2];
     std::unique_ptr<ovrRenderer> streamRenderer;
@@ -761,7 +764,41 @@ void destroyRenderers() {
     }
 }
 
-void setStreamConfig(StreamConfigInput config) { g_ctx.streamConfig = config; }
+void setStreamConfig(StreamConfigInput config) {
+    std::lock_guard<std::mutex> lock(g_ctx.streamConfigMutex);
+
+    g_ctx.streamConfig = config;
+    g_ctx.foveationConfigChanged = false;
+}
+
+void setFoveationConfig(float centerSizeX,
+                        float centerSizeY,
+                        float centerShiftX,
+                        float centerShiftY,
+                        float edgeRatioX,
+                        float edgeRatioY) {
+    std::lock_guard<std::mutex> lock(g_ctx.streamConfigMutex);
+
+    g_ctx.streamConfig.foveationCenterSizeX = centerSizeX;
+    g_ctx.streamConfig.foveationCenterSizeY = centerSizeY;
+    g_ctx.streamConfig.foveationCenterShiftX = centerShiftX;
+    g_ctx.streamConfig.foveationCenterShiftY = centerShiftY;
+    g_ctx.streamConfig.foveationEdgeRatioX = edgeRatioX;
+    g_ctx.streamConfig.foveationEdgeRatioY = edgeRatioY;
+    g_ctx.foveationConfigChanged = true;
+}
+
+FFRData streamFFRData() {
+    return {g_ctx.streamConfig.enableFoveation,
+            g_ctx.streamConfig.viewWidth,
+            g_ctx.streamConfig.viewHeight,
+            g_ctx.streamConfig.foveationCenterSizeX,
+            g_ctx.streamConfig.foveationCenterSizeY,
+            g_ctx.streamConfig.foveationCenterShiftX,
+            g_ctx.streamConfig.foveationCenterShiftY,
+            g_ctx.streamConfig.foveationEdgeRatioX,
+            g_ctx.streamConfig.foveationEdgeRatioY};
+}
 
 void streamStartNative(const int *swapchainTextures[2], int swapchainLength) {
     if (g_ctx.streamRenderer) {
@@ -777,6 +814,8 @@ void streamStartNative(const int *swapchainTextures[2], int swapchainLength) {
         }
     }
 
+    std::lock_guard<std::mutex> lock(g_ctx.streamConfigMutex);
+
     g_ctx.streamRenderer = std::make_unique<ovrRenderer>();
     ovrRenderer_Create(g_ctx.streamRenderer.get(),
                        g_ctx.streamConfig.viewWidth,
@@ -784,15 +823,8 @@ void streamStartNative(const int *swapchainTextures[2], int swapchainLength) {
                        g_ctx.streamTexture.get(),
                        g_ctx.hudTexture->GetGLTexture(),
                        g_ctx.streamSwapchainTextures,
-                       {g_ctx.streamConfig.enableFoveation,
-                        g_ctx.streamConfig.viewWidth,
-                        g_ctx.streamConfig.viewHeight,
-                        g_ctx.streamConfig.foveationCenterSizeX,
-                        g_ctx.streamConfig.foveationCenterSizeY,
-                        g_ctx.streamConfig.foveationCenterShiftX,
-                        g_ctx.streamConfig.foveationCenterShiftY,
-                        g_ctx.streamConfig.foveationEdgeRatioX,
-                        g_ctx.streamConfig.foveationEdgeRatioY});
+                       streamFFRData());
+    g_ctx.foveationConfigChanged = false;
 }
 
 void updateLobbyHudTexture(const unsigned char *data) {
@@ -835,6 +867,16 @@ void renderStreamNative(const int swapchainIndices[2], void *streamHardwareBuffe
     GL(glBindTexture(GL_TEXTURE_EXTERNAL_OES, g_ctx.streamTexture->GetGLTexture()));
     GL(glEGLImageTargetTexture2DOES(GL_TEXTURE_EXTERNAL_OES, (GLeglImageOES)image));
 
+    {
+        std::lock_guard<std::mutex> lock(g_ctx.streamConfigMutex);
+
+        // The size of the decoded frames does not change, only the shader is rebuilt
+        if (g_ctx.foveationConfigChanged && g_ctx.streamRenderer->enableFFR) {
+            g_ctx.streamRenderer->ffr->Initialize(streamFFRData());
+        }
+        g_ctx.foveationConfigChanged = false;
+    }
+
     EyeInput eyeInputs[2] = {};
     ovrRenderer_RenderFrame(g_ctx.streamRenderer.get(), eyeInputs, swapchainIndices, false);
 
@@ -3,7 +3,7 @@
 ////////////////////////////////////////////////////////////////////
 
 #include "bindings.h"
-#include "fec.h"
+#include <cstddef>
 
 static const std::byte NAL_TYPE_SPS = static_cast<const std::byte>(7);
 static const std::byte H265_NAL_TYPE_VPS = static_cast<const std::byte>(32);
@@ -17,16 +17,10 @@ void (*createDecoder)(const char *csd_0, int length);
 void (*pushNal)(const char *buffer, int length, unsigned long long frameIndex);
 
 namespace {
-bool m_enableFEC;
 int m_codec = 1;
-FECQueue m_queue;
 } // namespace
 
-void initializeNalParser(int codec, bool enableFec) {
-    m_enableFEC = enableFec;
-    m_codec = codec;
-    m_queue = FECQueue();
-}
+void initializeNalParser(int codec) { m_codec = codec; }
 
 int findVPSSPS(const std::byte *frameBuffer, int frameByteSize) {
     int zeroes = 0;
@@ -53,52 +47,32 @@ int findVPSSPS(const std::byte *frameBuffer, int frameByteSize) {
     return -1;
 }
 
-bool processNalPacket(VideoFrame header,
-                      const unsigned char *payload,
-                      int payloadSize,
-                      bool &outHadFecFailure) {
-    if (m_enableFEC) {
-        m_queue.addVideoPacket(header, payload, payloadSize, outHadFecFailure);
-    }
-
-    if (m_queue.reconstruct() || !m_enableFEC) {
-        const std::byte *frameBuffer;
-        int frameByteSize;
-        if (m_enableFEC) {
-            // Reconstructed
-            frameBuffer = m_queue.getFrameBuffer();
-            frameByteSize = m_queue.getFrameByteSize();
-        } else {
-            frameBuffer = (const std::byte *)payload;
-            frameByteSize = payloadSize;
-        }
-
-        std::byte NALType;
-        if (m_codec == ALVR_CODEC_H264)
-            NALType = frameBuffer[4] & std::byte(0x1F);
-        else
-            NALType = (frameBuffer[4] >> 1) & std::byte(0x3F);
+// The payload contains the whole frame, it has been reassembled by the stream socket
+bool processNalPacket(VideoFrame header, const unsigned char *payload, int payloadSize) {
+    const std::byte *frameBuffer = (const std::byte *)payload;
+    int frameByteSize = payloadSize;
 
-        if ((m_codec == ALVR_CODEC_H264 && NALType == NAL_TYPE_SPS) ||
-            (m_codec == ALVR_CODEC_H265 && NALType == H265_NAL_TYPE_VPS)) {
-            // This frame contains (VPS + )SPS + PPS + IDR on NVENC H.264 (H.265) stream.
-            // (VPS + )SPS + PPS has short size (8bytes + 28bytes in some environment), so we can
-            // assume SPS + PPS is contained in first fragment.
+    std::byte NALType;
+    if (m_codec == ALVR_CODEC_H264)
+        NALType = frameBuffer[4] & std::byte(0x1F);
+    else
+        NALType = (frameBuffer[4] >> 1) & std::byte(0x3F);
 
-            int end = findVPSSPS(frameBuffer, frameByteSize);
-            if (end == -1) {
-                // Invalid frame.
-                return false;
-            }
-            createDecoder((const char *)&frameBuffer[0], end);
-            pushNal(
-                (const char *)&frameBuffer[end], frameByteSize - end, header.trackingFrameIndex);
+    if ((m_codec == ALVR_CODEC_H264 && NALType == NAL_TYPE_SPS) ||
+        (m_codec == ALVR_CODEC_H265 && NALType == H265_NAL_TYPE_VPS)) {
+        // This frame contains (VPS + )SPS + PPS + IDR on NVENC H.264 (H.265) stream.
+        // (VPS + )SPS + PPS has short size (8bytes + 28bytes in some environment), so we can
+        // assume SPS + PPS is contained in first fragment.
 
-            m_queue.clearFecFailure();
-        } else {
-            pushNal((const char *)&frameBuffer[0], frameByteSize, header.trackingFrameIndex);
+        int end = findVPSSPS(frameBuffer, frameByteSize);
+        if (end == -1) {
+            // Invalid frame.
+            return false;
         }
-        return true;
+        createDecoder((const char *)&frameBuffer[0], end);
+        pushNal((const char *)&frameBuffer[end], frameByteSize - end, header.trackingFrameIndex);
+    } else {
+        pushNal((const char *)&frameBuffer[0], frameByteSize, header.trackingFrameIndex);
     }
-    return false;
+    return true;
 }
@@ -10,21 +10,22 @@ use crate::{
     IS_STREAMING, STATISTICS_MANAGER, STATISTICS_SENDER, TRACKING_SENDER, USE_OPENGL,
 };
 use alvr_audio::{AudioDevice, AudioDeviceType};
-use alvr_common::{prelude::*, ALVR_NAME, ALVR_VERSION};
+use alvr_common::{once_cell::sync::Lazy, prelude::*, ALVR_NAME, ALVR_VERSION};
 use alvr_session::{
     AudioDeviceId, CodecType, MediacodecDataType, OculusFovetionLevel, SessionDesc,
 };
 use alvr_sockets::{
     spawn_cancelable, ClientConfigPacket, ClientConnectionResult, ClientControlPacket,
-    ClientHandshakePacket, Haptics, HeadsetInfoPacket, PeerType, ProtoControlSocket,
-    ServerControlPacket, ServerHandshakePacket, StreamSocketBuilder, VideoFrameHeaderPacket, AUDIO,
-    HAPTICS, STATISTICS, TRACKING, VIDEO,
+    ClientHandshakePacket, FecConfig, Haptics, HeadsetInfoPacket, PeerType, ProbePacketHeader,
+    ProtoControlSocket, ServerControlPacket, ServerHandshakePacket, StreamSocketBuilder,
+    VideoFrameHeaderPacket, AUDIO, HAPTICS, PROBE, STATISTICS, TRACKING, VIDEO,
 };
 use futures::future::BoxFuture;
 use glyph_brush_layout::{
     ab_glyph::{Font, FontRef, ScaleFont},
     FontId, GlyphPositioner, HorizontalAlign, Layout, SectionGeometry, SectionText, VerticalAlign,
 };
+use rand::Rng;
 use serde_json as json;
 use settings_schema::Switch;
 use std::{future, sync::Arc, time::Duration};
@@ -43,7 +44,7 @@ use alvr_audio as audio;
 
 const INITIAL_MESSAGE: &str = "Searching for server...\n(open ALVR on your PC)";
 const NETWORK_UNREACHABLE_MESSAGE: &str = "Cannot connect to the internet";
-const CLIENT_UNTRUSTED_MESSAGE: &str = "On the PC, click \"Trust\"\nnext to the client entry";
+const CLIENT_UNTRUSTED_MESSAGE: &str = "On the PC, pair the client\nwith the code:";
 const INCOMPATIBLE_VERSIONS_MESSAGE: &str = concat!(
     "Server and client have\n",
     "incompatible types.\n",
@@ -59,6 +60,11 @@ const RETRY_CONNECT_MIN_INTERVAL: Duration = Duration::from_secs(1);
 const NETWORK_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
 const CLEANUP_PAUSE: Duration = Duration::from_millis(500);
 
+// Shown to the user and submitted on the dashboard to trust this client. It stays the same until
+// the app is restarted.
+static PAIRING_CODE: Lazy<String> =
+    Lazy::new(|| format!("{:06}", rand::thread_rng().gen_range(0..1_000_000)));
+
 const LOADING_TEXTURE_WIDTH: usize = 1280;
 const LOADING_TEXTURE_HEIGHT: usize = 720;
 const FONT_SIZE: f32 = 50_f32;
@@ -119,15 +125,18 @@ async fn connection_pipeline(
     decoder_guard: Arc<Mutex<()>>,
 ) -> StrResult {
     let device_name = platform::device_name();
-    let hostname = Config::load().hostname;
+    let Config {
+        hostname, identity, ..
+    } = Config::load();
+    let identity = identity.ok_or_else(enone!())?;
 
     let handshake_packet = ClientHandshakePacket {
         alvr_name: ALVR_NAME.into(),
         version: ALVR_VERSION.clone(),
         device_name,
         hostname,
-        reserved1: "".into(),
-        reserved2: "".into(),
+        reserved1: identity.fingerprint()?,
+        reserved2: PAIRING_CODE.clone(),
     };
 
     let (mut proto_socket, server_ip) = tokio::select! {
@@ -136,11 +145,14 @@ async fn connection_pipeline(
                 ConnectionError::ServerMessage(message) => {
                     info!("Server response: {message:?}");
                     let message_str = match message {
-                        ServerHandshakePacket::ClientUntrusted => CLIENT_UNTRUSTED_MESSAGE,
-                        ServerHandshakePacket::IncompatibleVersions =>
-                            INCOMPATIBLE_VERSIONS_MESSAGE,
+                        ServerHandshakePacket::ClientUntrusted => {
+                            format!("{CLIENT_UNTRUSTED_MESSAGE}\n{}", *PAIRING_CODE)
+                        }
+                        ServerHandshakePacket::IncompatibleVersions => {
+                            INCOMPATIBLE_VERSIONS_MESSAGE.into()
+                        }
                     };
-                    set_loading_message(message_str);
+                    set_loading_message(&message_str);
                     return Ok(());
                 }
                 ConnectionError::NetworkUnreachable => {
@@ -161,7 +173,9 @@ async fn connection_pipeline(
         },
         pair = async {
             loop {
-                if let Ok(pair) = ProtoControlSocket::connect_to(PeerType::Server).await {
+                if let Ok(pair) =
+                    ProtoControlSocket::connect_to(PeerType::Server(identity.clone())).await
+                {
                     break pair;
                 }
 
@@ -191,6 +205,7 @@ async fn connection_pipeline(
         .await
         .map_err(err!())?;
 
+    let stream_key = proto_socket.stream_key();
     let (control_sender, mut control_receiver) = proto_socket.split();
     let control_sender = Arc::new(Mutex::new(control_sender));
 
@@ -248,6 +263,8 @@ async fn connection_pipeline(
         res = stream_socket_builder.accept_from_server(
             server_ip,
             settings.connection.stream_port,
+            stream_key,
+            settings.connection.max_packet_size as _,
         ) => res?,
         _ = time::sleep(Duration::from_secs(5)) => {
             return fmt_e!("Timeout while setting up streams");
@@ -350,7 +367,7 @@ async fn connection_pipeline(
     // };
 
     let tracking_send_loop = {
-        let mut socket_sender = stream_socket.request_stream(TRACKING).await?;
+        let mut socket_sender = stream_socket.request_stream(TRACKING, None).await?;
         async move {
             let (data_sender, mut data_receiver) = tmpsc::unbounded_channel();
             *TRACKING_SENDER.lock() = Some(data_sender);
@@ -375,7 +392,7 @@ async fn connection_pipeline(
     };
 
     let statistics_send_loop = {
-        let mut socket_sender = stream_socket.request_stream(STATISTICS).await?;
+        let mut socket_sender = stream_socket.request_stream(STATISTICS, None).await?;
         async move {
             let (data_sender, mut data_receiver) = tmpsc::unbounded_channel();
             *STATISTICS_SENDER.lock() = Some(data_sender);
@@ -422,7 +439,6 @@ async fn connection_pipeline(
             .subscribe_to_stream::<VideoFrameHeaderPacket>(VIDEO)
             .await?;
         let codec = settings.video.codec;
-        let enable_fec = settings.connection.enable_fec;
         async move {
             let _decoder_guard = decoder_guard.lock().await;
 
@@ -445,9 +461,7 @@ async fn connection_pipeline(
 
             let _stream_guard = StreamCloseGuard;
 
-            unsafe {
-                crate::initializeNalParser(matches!(codec, CodecType::HEVC) as _, enable_fec)
-            };
+            unsafe { crate::initializeNalParser(matches!(codec, CodecType::HEVC) as _) };
 
             IS_STREAMING.set(true);
 
@@ -466,30 +480,29 @@ async fn connection_pipeline(
                     videoFrameIndex: packet.header.video_frame_index,
                     sentTime: packet.header.sent_time,
                     frameByteSize: packet.header.frame_byte_size,
-                    fecIndex: packet.header.fec_index,
-                    fecPercentage: packet.header.fec_percentage,
                 };
 
                 if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
                     stats.report_video_packet_received(Duration::from_nanos(
                         packet.header.tracking_frame_index,
                     ));
+                    stats.report_video_stream(receiver.statistics());
+                }
+
+                // Some packets could not be recovered with FEC, at least one frame is missing
+                if packet.had_packet_loss {
+                    if let Some(sender) = &*CONTROL_CHANNEL_SENDER.lock() {
+                        sender.send(ClientControlPacket::VideoErrorReport).ok();
+                    }
                 }
 
-                let mut fec_failure = false;
                 unsafe {
                     crate::processNalPacket(
                         header,
                         packet.buffer.as_ptr(),
                         packet.buffer.len() as _,
-                        &mut fec_failure,
                     )
                 };
-                if fec_failure {
-                    if let Some(sender) = &*CONTROL_CHANNEL_SENDER.lock() {
-                        sender.send(ClientControlPacket::VideoErrorReport).ok();
-                    }
-                }
             }
         }
     };
@@ -532,7 +545,8 @@ async fn connection_pipeline(
         let device = AudioDevice::new(None, &AudioDeviceId::Default, AudioDeviceType::Input)
             .map_err(err!())?;
 
-        let microphone_sender = stream_socket.request_stream(AUDIO).await?;
+        let fec = settings.connection.enable_fec.then(FecConfig::default);
+        let microphone_sender = stream_socket.request_stream(AUDIO, fec).await?;
         Box::pin(audio::record_audio_loop(
             device,
             1,
@@ -543,6 +557,18 @@ async fn connection_pipeline(
         Box::pin(future::pending())
     };
 
+    // The server measures the bandwidth with the probe reports
+    let probe_receive_loop = {
+        let receiver = stream_socket
+            .subscribe_to_stream::<ProbePacketHeader>(PROBE)
+            .await?;
+        alvr_sockets::probe_receive_loop(receiver, |report| {
+            if let Some(sender) = &*CONTROL_CHANNEL_SENDER.lock() {
+                sender.send(ClientControlPacket::ProbeReport(report)).ok();
+            }
+        })
+    };
+
     let keepalive_sender_loop = {
         let control_sender = Arc::clone(&control_sender);
         async move {
@@ -574,11 +600,27 @@ async fn connection_pipeline(
     let control_receive_loop = async move {
         loop {
             match control_receiver.recv().await {
+                Ok(ServerControlPacket::Ping(timestamp)) => {
+                    if let Some(sender) = &*CONTROL_CHANNEL_SENDER.lock() {
+                        sender.send(ClientControlPacket::Pong(timestamp)).ok();
+                    }
+                }
                 Ok(ServerControlPacket::Restarting) => {
                     info!("{SERVER_RESTART_MESSAGE}");
                     set_loading_message(SERVER_RESTART_MESSAGE);
                     break Ok(());
                 }
+                #[cfg(target_os = "android")]
+                Ok(ServerControlPacket::FoveationConfig(config)) => unsafe {
+                    crate::setFoveationConfig(
+                        config.center_size_x,
+                        config.center_size_y,
+                        config.center_shift_x,
+                        config.center_shift_y,
+                        config.edge_ratio_x,
+                        config.edge_ratio_y,
+                    )
+                },
                 Ok(_) => (),
                 Err(e) => {
                     info!("{SERVER_DISCONNECTED_MESSAGE} Cause: {e}");
@@ -589,6 +631,11 @@ async fn connection_pipeline(
         }
     };
 
+    let send_loop = {
+        let stream_socket = Arc::clone(&stream_socket);
+        async move { stream_socket.send_loop().await }
+    };
+
     let receive_loop = async move { stream_socket.receive_loop().await };
 
     // Run many tasks concurrently. Threading is managed by the runtime, for best performance.
@@ -603,12 +650,14 @@ async fn connection_pipeline(
 
             Ok(())
         },
+        res = spawn_cancelable(send_loop) => res,
         res = spawn_cancelable(game_audio_loop) => res,
         res = spawn_cancelable(microphone_loop) => res,
         res = spawn_cancelable(tracking_send_loop) => res,
         res = spawn_cancelable(statistics_send_loop) => res,
         res = spawn_cancelable(video_receive_loop) => res,
         res = spawn_cancelable(haptics_receive_loop) => res,
+        res = spawn_cancelable(probe_receive_loop) => res,
         res = spawn_cancelable(control_send_loop) => res,
 
         // keep these loops on the current task
@@ -1,4 +1,4 @@
-use alvr_sockets::ClientStatistics;
+use alvr_sockets::{ClientStatistics, StreamReceiverStatistics};
 use std::{
     collections::VecDeque,
     time::{Duration, Instant},
@@ -15,6 +15,7 @@ pub struct StatisticsManager {
     max_history_size: usize,
     prev_vsync: Instant,
     last_average_total_pipeline_latency: Duration,
+    video_stream_statistics: StreamReceiverStatistics,
 }
 
 impl StatisticsManager {
@@ -24,6 +25,7 @@ impl StatisticsManager {
             history_buffer: VecDeque::new(),
             prev_vsync: Instant::now(),
             last_average_total_pipeline_latency: Duration::ZERO,
+            video_stream_statistics: StreamReceiverStatistics::default(),
         }
     }
 
@@ -59,6 +61,10 @@ impl StatisticsManager {
         }
     }
 
+    pub fn report_video_stream(&mut self, statistics: StreamReceiverStatistics) {
+        self.video_stream_statistics = statistics;
+    }
+
     pub fn report_frame_decoded(&mut self, target_timestamp: Duration) {
         if let Some(frame) = self
             .history_buffer
@@ -120,7 +126,12 @@ impl StatisticsManager {
         self.history_buffer
             .iter()
             .find(|frame| frame.intervals.target_timestamp == target_timestamp)
-            .map(|frame| frame.intervals.clone())
+            .map(|frame| ClientStatistics {
+                video_packets_reordered: self.video_stream_statistics.packets_reordered,
+                video_packets_late: self.video_stream_statistics.packets_late,
+                video_packets_duplicated: self.video_stream_statistics.packets_duplicated,
+                ..frame.intervals.clone()
+            })
     }
 
     // latency used for prediction
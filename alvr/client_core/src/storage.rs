@@ -1,4 +1,5 @@
 use alvr_common::prelude::*;
+use alvr_sockets::ClientIdentity;
 use app_dirs2::{AppDataType, AppInfo};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
@@ -19,10 +20,21 @@ fn config_path() -> PathBuf {
     .join("session.json")
 }
 
+// The client cannot connect without an identity, but it can still start
+fn generate_identity() -> Option<ClientIdentity> {
+    ClientIdentity::generate()
+        .map_err(|e| error!("Error generating the client certificate: {e}"))
+        .ok()
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Config {
     pub protocol_id: u64,
     pub hostname: String,
+    // Certificate used to authenticate with the server. Its fingerprint is pinned when trusted.
+    // Configs stored by older versions do not have it, it is generated on load
+    #[serde(default)]
+    pub identity: Option<ClientIdentity>,
 }
 
 impl Default for Config {
@@ -38,6 +50,7 @@ impl Default for Config {
                 rng.gen_range(0..10),
                 rng.gen_range(0..10),
             ),
+            identity: generate_identity(),
         }
     }
 }
@@ -47,7 +60,12 @@ impl Config {
         if let Ok(config_string) = fs::read_to_string(config_path()) {
             // Failure happens if the Config signature changed between versions.
             // todo: recover data from mismatched Config signature. low priority
-            if let Ok(config) = serde_json::from_str(&config_string) {
+            if let Ok(mut config) = serde_json::from_str::<Config>(&config_string) {
+                if config.identity.is_none() {
+                    config.identity = generate_identity();
+                    config.store();
+                }
+
                 return config;
             } else {
                 info!("Error parsing ALVR config. Using default");
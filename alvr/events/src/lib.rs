@@ -1,6 +1,15 @@
-use alvr_common::prelude::*;
+use alvr_common::{once_cell::sync::Lazy, parking_lot::Mutex};
 use alvr_session::SessionDesc;
 use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    mem,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::broadcast;
+
+// Capacity of the channel used by each subscriber. If a subscriber is slower, it loses events.
+const EVENTS_CHANNEL_CAPACITY: usize = 256;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub enum EventSeverity {
@@ -18,6 +27,12 @@ pub struct Statistics {
     pub video_packets_per_sec: usize,
     pub video_mbytes_total: usize,
     pub video_mbits_per_sec: f32,
+    // Dropped because the send queue was full
+    pub video_packets_dropped: usize,
+    // Reported by the client
+    pub video_packets_reordered: usize,
+    pub video_packets_late: usize,
+    pub video_packets_duplicated: usize,
     pub total_latency_ms: f32,
     pub network_latency_ms: f32,
     pub encode_latency_ms: f32,
@@ -47,6 +62,40 @@ pub struct GraphStatistics {
     pub server_fps: f32,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyPercentiles {
+    pub p50_ms: f32,
+    pub p95_ms: f32,
+    pub p99_ms: f32,
+    // mean absolute difference between consecutive frames
+    pub jitter_ms: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LatencyStatistics {
+    pub frames_count: usize,
+    pub total_pipeline: LatencyPercentiles,
+    pub game_time: LatencyPercentiles,
+    pub server_compositor: LatencyPercentiles,
+    pub encoder: LatencyPercentiles,
+    pub network: LatencyPercentiles,
+    pub decoder: LatencyPercentiles,
+    pub client_compositor: LatencyPercentiles,
+    pub vsync_queue: LatencyPercentiles,
+}
+
+// Measured by the server by probing the network. The values are None until they are measured
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkStatistics {
+    pub rtt_ms: Option<f32>,
+    pub rtt_variation_ms: Option<f32>,
+    pub bandwidth_mbits_per_sec: Option<f32>,
+    pub loss_percentage: f32,
+}
+
 // This struct is temporary, until we switch to the new event system
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct LogEvent {
@@ -67,8 +116,7 @@ pub struct ButtonEvent {
     pub value: ButtonValue,
 }
 
-// Event is serialized as #{ "id": "..." [, "data": ...] }#
-// Pound signs are used to identify start and finish of json
+// Serialized as { "id": "..." [, "data": ...] }
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(tag = "id", content = "data")]
 pub enum EventType {
@@ -83,17 +131,124 @@ pub enum EventType {
     UpdateDownloadError,
     Statistics(Statistics),
     GraphStatistics(GraphStatistics),
+    LatencyStatistics(LatencyStatistics),
+    NetworkStatistics(NetworkStatistics),
     Button(ButtonEvent),
     ServerQuitting,
     Log(LogEvent),
 }
 
+// Serialized as { "sequence": ..., "timestamp": ..., "id": "..." [, "data": ...] }
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Event {
-    pub timestamp: String,
+    // Incremented for each event, starting from 0 when the process starts. Subscribers can use it
+    // to find replayed events they have already received.
+    pub sequence: u64,
+    // Milliseconds since the UNIX epoch
+    pub timestamp: u64,
+    #[serde(flatten)]
     pub event_type: EventType,
 }
 
+struct EventsState {
+    next_sequence: u64,
+    // Latest event of each type, sent again to new subscribers. Statistics are sent for every frame,
+    // keeping only the latest one preserves the older events that carry the state. Log events are
+    // not included, they are stored in the session log.
+    replay_buffer: VecDeque<Event>,
+    sender: broadcast::Sender<Event>,
+}
+
+static EVENTS_STATE: Lazy<Mutex<EventsState>> = Lazy::new(|| {
+    Mutex::new(EventsState {
+        next_sequence: 0,
+        replay_buffer: VecDeque::new(),
+        sender: broadcast::channel(EVENTS_CHANNEL_CAPACITY).0,
+    })
+});
+
 pub fn send_event(event_type: EventType) {
-    info!("#{}#", serde_json::to_string(&event_type).unwrap());
+    let mut state = EVENTS_STATE.lock();
+
+    let event = Event {
+        sequence: state.next_sequence,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or_default(),
+        event_type,
+    };
+    state.next_sequence += 1;
+
+    if !matches!(event.event_type, EventType::Log(_)) {
+        let event_id = mem::discriminant(&event.event_type);
+        state
+            .replay_buffer
+            .retain(|replayed| mem::discriminant(&replayed.event_type) != event_id);
+        state.replay_buffer.push_back(event.clone());
+    }
+
+    // There could be no subscribers
+    state.sender.send(event).ok();
+}
+
+// Sequence number of the next event that will be sent
+pub fn next_event_sequence() -> u64 {
+    EVENTS_STATE.lock().next_sequence
+}
+
+// Returns the latest events (oldest first) and a receiver for the events sent afterwards. No event
+// is lost or duplicated between the two.
+pub fn subscribe_events() -> (Vec<Event>, broadcast::Receiver<Event>) {
+    let state = EVENTS_STATE.lock();
+
+    (
+        state.replay_buffer.iter().cloned().collect(),
+        state.sender.subscribe(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latest_event_of_each_type_is_replayed() {
+        send_event(EventType::ClientConnected);
+        send_event(EventType::Session(Box::default()));
+        // Sent at a high rate
+        for bytes_count in 0..100 {
+            send_event(EventType::UpdateDownloadedBytesCount(bytes_count));
+        }
+        send_event(EventType::Log(LogEvent {
+            timestamp: "".into(),
+            severity: EventSeverity::Info,
+            content: "".into(),
+        }));
+        send_event(EventType::ClientDisconnected);
+        send_event(EventType::ClientConnected);
+
+        let (replay, mut receiver) = subscribe_events();
+
+        let replay_ids = replay
+            .iter()
+            .map(|event| serde_json::to_value(event).unwrap()["id"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            replay_ids,
+            [
+                "Session",
+                "UpdateDownloadedBytesCount",
+                "ClientDisconnected",
+                "ClientConnected"
+            ]
+        );
+        assert!(replay
+            .windows(2)
+            .all(|events| events[0].sequence < events[1].sequence));
+
+        send_event(EventType::ServerQuitting);
+        let event = receiver.try_recv().unwrap();
+        assert_eq!(event.sequence, replay.last().unwrap().sequence + 1);
+    }
 }
@@ -190,11 +190,21 @@ impl Layout {
         self.config_dir.join("session.json")
     }
 
-    pub fn session_log(&self) -> PathBuf {
+    // rhai script run at startup, used to register event callbacks
+    pub fn automation_script(&self) -> PathBuf {
+        self.config_dir.join("automation.rhai")
+    }
+
+    pub fn web_server_token(&self) -> PathBuf {
+        self.config_dir.join("web_server_token.txt")
+    }
+
+    // Log files of the current and previous server sessions
+    pub fn session_logs_dir(&self) -> PathBuf {
         if cfg!(windows) {
-            self.log_dir.join("session_log.txt")
+            self.log_dir.join("session_logs")
         } else {
-            self.log_dir.join("alvr_session_log.txt")
+            self.log_dir.join("alvr_session_logs")
         }
     }
 
@@ -202,6 +212,10 @@ impl Layout {
         self.log_dir.join("crash_log.txt")
     }
 
+    pub fn recordings_dir(&self) -> PathBuf {
+        self.log_dir.join("recordings")
+    }
+
     pub fn openvr_driver_lib_dir(&self) -> PathBuf {
         let platform = if cfg!(windows) {
             "win64"
@@ -12,116 +12,26 @@ ClientConnection::ClientConnection() {
 
 	m_Statistics = std::make_shared<Statistics>();
 
-	reed_solomon_init();
-	
 	videoPacketCounter = 0;
-	m_fecPercentage = INITIAL_FEC_PERCENTAGE;
-}
-
-void ClientConnection::FECSend(uint8_t *buf, int len, uint64_t targetTimestampNs, uint64_t videoFrameIndex) {
-	int shardPackets = CalculateFECShardPackets(len, m_fecPercentage);
-
-	int blockSize = shardPackets * ALVR_MAX_VIDEO_BUFFER_SIZE;
-
-	int dataShards = (len + blockSize - 1) / blockSize;
-	int totalParityShards = CalculateParityShards(dataShards, m_fecPercentage);
-	int totalShards = dataShards + totalParityShards;
-
-	assert(totalShards <= DATA_SHARDS_MAX);
-
-	reed_solomon *rs = reed_solomon_new(dataShards, totalParityShards);
-
-	std::vector<uint8_t *> shards(totalShards);
-
-	for (int i = 0; i < dataShards; i++) {
-		shards[i] = buf + i * blockSize;
-	}
-	if (len % blockSize != 0) {
-		// Padding
-		shards[dataShards - 1] = new uint8_t[blockSize];
-		memset(shards[dataShards - 1], 0, blockSize);
-		memcpy(shards[dataShards - 1], buf + (dataShards - 1) * blockSize, len % blockSize);
-	}
-	for (int i = 0; i < totalParityShards; i++) {
-		shards[dataShards + i] = new uint8_t[blockSize];
-	}
-
-	int ret = reed_solomon_encode(rs, &shards[0], totalShards, blockSize);
-	assert(ret == 0);
-
-	reed_solomon_release(rs);
-
-	uint8_t packetBuffer[2000];
-	VideoFrame *header = (VideoFrame *)packetBuffer;
-	uint8_t *payload = packetBuffer + sizeof(VideoFrame);
-	int dataRemain = len;
-
-	header->trackingFrameIndex = targetTimestampNs;
-	header->videoFrameIndex = videoFrameIndex;
-	header->sentTime = GetTimestampUs();
-	header->frameByteSize = len;
-	header->fecIndex = 0;
-	header->fecPercentage = (uint16_t)m_fecPercentage;
-	for (int i = 0; i < dataShards; i++) {
-		for (int j = 0; j < shardPackets; j++) {
-			int copyLength = std::min(ALVR_MAX_VIDEO_BUFFER_SIZE, dataRemain);
-			if (copyLength <= 0) {
-				break;
-			}
-			memcpy(payload, shards[i] + j * ALVR_MAX_VIDEO_BUFFER_SIZE, copyLength);
-			dataRemain -= ALVR_MAX_VIDEO_BUFFER_SIZE;
-
-			header->packetCounter = videoPacketCounter;
-			videoPacketCounter++;
-			VideoSend(*header, (unsigned char *)packetBuffer + sizeof(VideoFrame), copyLength);
-			m_Statistics->CountPacket(sizeof(VideoFrame) + copyLength);
-			header->fecIndex++;
-		}
-	}
-	header->fecIndex = dataShards * shardPackets;
-	for (int i = 0; i < totalParityShards; i++) {
-		for (int j = 0; j < shardPackets; j++) {
-			int copyLength = ALVR_MAX_VIDEO_BUFFER_SIZE;
-			memcpy(payload, shards[dataShards + i] + j * ALVR_MAX_VIDEO_BUFFER_SIZE, copyLength);
-
-			header->packetCounter = videoPacketCounter;
-			videoPacketCounter++;
-			
-			VideoSend(*header, (unsigned char *)packetBuffer + sizeof(VideoFrame), copyLength);
-			m_Statistics->CountPacket(sizeof(VideoFrame) + copyLength);
-			header->fecIndex++;
-		}
-	}
-
-	if (len % blockSize != 0) {
-		delete[] shards[dataShards - 1];
-	}
-	for (int i = 0; i < totalParityShards; i++) {
-		delete[] shards[dataShards + i];
-	}
 }
 
 void ClientConnection::SendVideo(uint8_t *buf, int len, uint64_t targetTimestampNs) {
-	// Report before the frame is packetized
+	// Report before the frame is sent
 	ReportEncoded(targetTimestampNs);
 
-	if (Settings::Instance().m_enableFec) {
-		FECSend(buf, len, targetTimestampNs, mVideoFrameIndex);
-	} else {
-		VideoFrame header = {};
-		header.packetCounter = this->videoPacketCounter;
-		header.trackingFrameIndex = targetTimestampNs;
-		header.videoFrameIndex = mVideoFrameIndex;
-		header.sentTime = GetTimestampUs();
-		header.frameByteSize = len;
+	// The whole frame is sent at once, the stream socket splits it into packets
+	VideoFrame header = {};
+	header.packetCounter = this->videoPacketCounter;
+	header.trackingFrameIndex = targetTimestampNs;
+	header.videoFrameIndex = mVideoFrameIndex;
+	header.sentTime = GetTimestampUs();
+	header.frameByteSize = len;
 
-		VideoSend(header, buf, len);
+	VideoSend(header, buf, len);
 
-		m_Statistics->CountPacket(sizeof(VideoFrame) + len);
-
-		this->videoPacketCounter++;
-	}
+	m_Statistics->CountPacket(sizeof(VideoFrame) + len);
 
+	this->videoPacketCounter++;
 	mVideoFrameIndex++;
 }
 
@@ -129,16 +39,8 @@ void ClientConnection::ReportNetworkLatency(uint64_t latencyUs) {
 	m_Statistics->NetworkSend(latencyUs);
 }
 
-void ClientConnection::OnFecFailure() {
-	Debug("Listener::OnFecFailure()\n");
-	if (GetTimestampUs() - m_lastFecFailure < CONTINUOUS_FEC_FAILURE) {
-		if (m_fecPercentage < MAX_FEC_PERCENTAGE) {
-			m_fecPercentage += 5;
-		}
-	}
-	m_lastFecFailure = GetTimestampUs();
-
-	ReportFecFailure(m_fecPercentage);
+void ClientConnection::ReportNetworkEstimate(uint64_t bandwidthBps, float lossRate) {
+	m_Statistics->NetworkEstimate(bandwidthBps, lossRate);
 }
 
 std::shared_ptr<Statistics> ClientConnection::GetStatistics() {
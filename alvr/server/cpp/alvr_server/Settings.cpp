@@ -97,6 +97,7 @@ void Settings::Load()
 		m_adaptiveBitrateUpRate = (int)config.get("bitrate_up_rate").get<int64_t>();
 		m_adaptiveBitrateDownRate = (int)config.get("bitrate_down_rate").get<int64_t>();
 		m_adaptiveBitrateLightLoadThreshold = config.get("bitrate_light_load_threshold").get<double>();
+		m_adaptiveBitrateUseNetworkEstimate = config.get("bitrate_use_network_estimate").get<bool>();
 		m_use10bitEncoder = config.get("use_10bit_encoder").get<bool>();
 		m_force_sw_encoding = config.get("force_sw_encoding").get<bool>();
 		m_swThreadCount = (int32_t)config.get("sw_thread_count").get<int64_t>();
@@ -158,8 +159,6 @@ void Settings::Load()
 		m_gamma = (float)config.get("gamma").get<double>();
 		m_sharpening = (float)config.get("sharpening").get<double>();
 
-		m_enableFec = config.get("enable_fec").get<bool>();
-
 		m_enableLinuxVulkanAsync = config.get("linux_async_reprojection").get<bool>();
 		
 		Debug("Config JSON: %hs\n", json.c_str());
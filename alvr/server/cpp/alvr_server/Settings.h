@@ -73,6 +73,7 @@ public:
 	uint64_t m_adaptiveBitrateUpRate;
 	uint64_t m_adaptiveBitrateDownRate;
 	float m_adaptiveBitrateLightLoadThreshold;
+	bool m_adaptiveBitrateUseNetworkEstimate;
 	bool m_use10bitEncoder;
 	bool m_force_sw_encoding;
 	uint32_t m_swThreadCount;
@@ -124,8 +125,6 @@ public:
 	bool m_enableViveTrackerProxy = false;
 
 	bool m_useHeadsetTrackingSystem = false;
-	
-	bool m_enableFec;
 
 	bool m_enableLinuxVulkanAsync;
 };
@@ -11,6 +11,10 @@
 
 #define BITS_IN_MBIT 1000000
 #define US_IN_S 1000000
+// Part of the measured bandwidth that can be used by the video stream
+#define NETWORK_BANDWIDTH_USAGE 0.8
+// Loss rate of the probe packets above which the bitrate is decreased
+#define NETWORK_LOSS_RATE_THRESHOLD 0.02
 
 class Statistics {
 public:
@@ -51,26 +55,43 @@ public:
 			m_sendLatency = latencyUs * 0.1 + m_sendLatency * 0.9;
 		}
 	}
+	// bandwidthBps is 0 if it has not been measured yet
+	void NetworkEstimate(uint64_t bandwidthBps, float lossRate) {
+		m_networkBandwidth = bandwidthBps / BITS_IN_MBIT;
+		m_networkLossRate = lossRate;
+	}
 	uint64_t GetBitrate() {
 		return m_bitrate;
 	}
 
 	bool CheckBitrateUpdated() {
 		if (m_enableAdaptiveBitrate) {
+			uint64_t maximum = m_adaptiveBitrateMaximum;
+			bool lossy = false;
+			if (m_adaptiveBitrateUseNetworkEstimate) {
+				if (m_networkBandwidth != 0) {
+					maximum = std::min(maximum, std::max((uint64_t)(m_networkBandwidth * NETWORK_BANDWIDTH_USAGE), (uint64_t)5));
+				}
+				lossy = m_networkLossRate > NETWORK_LOSS_RATE_THRESHOLD;
+			}
+
 			uint64_t latencyUs = m_sendLatency; // using video stream transport latency
-			if (latencyUs != 0) { // check valid latency
-				if (latencyUs > m_adaptiveBitrateTarget + m_adaptiveBitrateThreshold) {
+			if (latencyUs != 0 || lossy) { // check valid latency
+				if (lossy || latencyUs > m_adaptiveBitrateTarget + m_adaptiveBitrateThreshold) {
 					if (m_bitrate <= 5 + m_adaptiveBitrateDownRate)
 						m_bitrate = 5; // minimum bitrate 5mbps
 					else
 						m_bitrate -= m_adaptiveBitrateDownRate;
 				} else if (latencyUs < m_adaptiveBitrateTarget - m_adaptiveBitrateThreshold) {
-					if (m_bitrate >= m_adaptiveBitrateMaximum - m_adaptiveBitrateUpRate)
-						m_bitrate = m_adaptiveBitrateMaximum; // maximum bitrate
+					if (m_bitrate + m_adaptiveBitrateUpRate >= maximum)
+						m_bitrate = maximum; // maximum bitrate
 					else if (m_bitrateSent > m_bitrate * m_adaptiveBitrateLightLoadThreshold * (m_framesPrevious == 0 ? m_refreshRate : m_framesPrevious) / m_refreshRate)
 						m_bitrate += m_adaptiveBitrateUpRate; // increase bitrate if sent mbps is higher than set bitrate threshold (set bitrate * load threshold * valid framerate)
 				}
 			}
+			if (m_adaptiveBitrateUseNetworkEstimate && m_bitrate > maximum) {
+				m_bitrate = maximum; // the measured bandwidth dropped
+			}
 			if (m_bitrateUpdated != m_bitrate) { // bitrate changed
 				m_bitrateUpdated = m_bitrate;
 				return true;
@@ -138,6 +159,11 @@ public:
 	uint64_t m_adaptiveBitrateTargetMaximum = Settings::Instance().m_adaptiveBitrateTargetMaximum;
 	int32_t m_adaptiveBitrateTargetOffset = Settings::Instance().m_adaptiveBitrateTargetOffset;
 	uint64_t m_adaptiveBitrateThreshold = Settings::Instance().m_adaptiveBitrateThreshold;
+	bool m_adaptiveBitrateUseNetworkEstimate = Settings::Instance().m_adaptiveBitrateUseNetworkEstimate;
+
+	// Measured by probing the network, 0 if unknown (mbit/s)
+	uint64_t m_networkBandwidth = 0;
+	float m_networkLossRate = 0;
 	
 	float m_adaptiveBitrateLightLoadThreshold = Settings::Instance().m_adaptiveBitrateLightLoadThreshold;
 
@@ -173,7 +173,6 @@ unsigned long long (*PathStringToHash)(const char *path);
 void (*ReportPresent)(unsigned long long timestamp_ns);
 void (*ReportComposed)(unsigned long long timestamp_ns);
 void (*ReportEncoded)(unsigned long long timestamp_ns);
-void (*ReportFecFailure)(int percentage);
 
 void *CppEntryPoint(const char *interface_name, int *return_code) {
     // Initialize path constants
@@ -240,6 +239,12 @@ void ReportNetworkLatency(unsigned long long latencyUs) {
     }
 }
 
+void ReportNetworkEstimate(unsigned long long bandwidthBps, float lossRate) {
+    if (g_driver_provider.hmd && g_driver_provider.hmd->m_Listener) {
+        g_driver_provider.hmd->m_Listener->ReportNetworkEstimate(bandwidthBps, lossRate);
+    }
+}
+
 unsigned long long GetGameFrameIntervalNs() {
     vr::Compositor_FrameTiming timings[2];
     timings[0].m_nSize = sizeof(vr::Compositor_FrameTiming);
@@ -270,7 +275,6 @@ unsigned long long GetGameFrameIntervalNs() {
 }
 void VideoErrorReportReceive() {
     if (g_driver_provider.hmd && g_driver_provider.hmd->m_Listener) {
-        g_driver_provider.hmd->m_Listener->OnFecFailure();
         g_driver_provider.hmd->m_encoder->OnPacketLoss();
     }
 }
@@ -319,14 +323,54 @@ void SetButton(unsigned long long path, AlvrButtonValue value) {
 
 void SetBitrateParameters(unsigned long long bitrate_mbs,
                           bool adaptive_bitrate_enabled,
-                          unsigned long long bitrate_max) {
+                          unsigned long long bitrate_max,
+                          bool use_network_estimate) {
     if (g_driver_provider.hmd && g_driver_provider.hmd->m_Listener) {
         if (adaptive_bitrate_enabled) {
             g_driver_provider.hmd->m_Listener->m_Statistics->m_enableAdaptiveBitrate = true;
             g_driver_provider.hmd->m_Listener->m_Statistics->m_adaptiveBitrateMaximum = bitrate_max;
+            g_driver_provider.hmd->m_Listener->m_Statistics->m_adaptiveBitrateUseNetworkEstimate =
+                use_network_estimate;
         } else {
             g_driver_provider.hmd->m_Listener->m_Statistics->m_enableAdaptiveBitrate = false;
             g_driver_provider.hmd->m_Listener->m_Statistics->m_bitrate = bitrate_mbs;
         }
     }
+}
+
+void SetHapticsParameters(float intensity,
+                          float amplitude_curve,
+                          float min_duration,
+                          float low_duration_amplitude_multiplier,
+                          float low_duration_range) {
+    Settings::Instance().m_hapticsIntensity = intensity;
+    Settings::Instance().m_hapticsAmplitudeCurve = amplitude_curve;
+    Settings::Instance().m_hapticsMinDuration = min_duration;
+    Settings::Instance().m_hapticsLowDurationAmplitudeMultiplier =
+        low_duration_amplitude_multiplier;
+    Settings::Instance().m_hapticsLowDurationRange = low_duration_range;
+}
+
+// The new values are picked up by the render passes on the next frame
+void SetFoveationParameters(float center_size_x,
+                            float center_size_y,
+                            float center_shift_x,
+                            float center_shift_y,
+                            float edge_ratio_x,
+                            float edge_ratio_y) {
+    Settings::Instance().m_foveationCenterSizeX = center_size_x;
+    Settings::Instance().m_foveationCenterSizeY = center_size_y;
+    Settings::Instance().m_foveationCenterShiftX = center_shift_x;
+    Settings::Instance().m_foveationCenterShiftY = center_shift_y;
+    Settings::Instance().m_foveationEdgeRatioX = edge_ratio_x;
+    Settings::Instance().m_foveationEdgeRatioY = edge_ratio_y;
+}
+
+void SetColorCorrectionParameters(
+    float brightness, float contrast, float saturation, float gamma, float sharpening) {
+    Settings::Instance().m_brightness = brightness;
+    Settings::Instance().m_contrast = contrast;
+    Settings::Instance().m_saturation = saturation;
+    Settings::Instance().m_gamma = gamma;
+    Settings::Instance().m_sharpening = sharpening;
 }
\ No newline at end of file
@@ -37,14 +37,9 @@ struct ClientStats {
 struct VideoFrame {
     unsigned int packetCounter;
     unsigned long long trackingFrameIndex;
-    // FEC decoder needs some value for identify video frame number to detect new frame.
-    // trackingFrameIndex becomes sometimes same value as previous video frame (in case of low
-    // tracking rate).
     unsigned long long videoFrameIndex;
     unsigned long long sentTime;
     unsigned int frameByteSize;
-    unsigned int fecIndex;
-    unsigned short fecPercentage;
     // char frameBuffer[];
 };
 enum OpenvrPropertyType {
@@ -121,7 +116,6 @@ extern "C" unsigned long long (*PathStringToHash)(const char *path);
 extern "C" void (*ReportPresent)(unsigned long long timestamp_ns);
 extern "C" void (*ReportComposed)(unsigned long long timestamp_ns);
 extern "C" void (*ReportEncoded)(unsigned long long timestamp_ns);
-extern "C" void (*ReportFecFailure)(int percentage);
 
 extern "C" void *CppEntryPoint(const char *pInterfaceName, int *pReturnCode);
 extern "C" void InitializeStreaming();
@@ -135,6 +129,7 @@ extern "C" void SetTracking(unsigned long long targetTimestampNs,
                             OculusHand leftHand,
                             OculusHand rightHand);
 extern "C" void ReportNetworkLatency(unsigned long long latencyUs);
+extern "C" void ReportNetworkEstimate(unsigned long long bandwidthBps, float lossRate);
 extern "C" unsigned long long GetGameFrameIntervalNs();
 extern "C" void VideoErrorReportReceive();
 extern "C" void ShutdownSteamvr();
@@ -147,4 +142,18 @@ extern "C" void SetButton(unsigned long long path, AlvrButtonValue value);
 
 extern "C" void SetBitrateParameters(unsigned long long bitrate_mbs,
                                      bool adaptive_bitrate_enabled,
-                                     unsigned long long bitrate_max);
\ No newline at end of file
+                                     unsigned long long bitrate_max,
+                                     bool use_network_estimate);
+extern "C" void SetHapticsParameters(float intensity,
+                                     float amplitude_curve,
+                                     float min_duration,
+                                     float low_duration_amplitude_multiplier,
+                                     float low_duration_range);
+extern "C" void SetFoveationParameters(float center_size_x,
+                                       float center_size_y,
+                                       float center_shift_x,
+                                       float center_shift_y,
+                                       float edge_ratio_x,
+                                       float edge_ratio_y);
+extern "C" void SetColorCorrectionParameters(
+    float brightness, float contrast, float saturation, float gamma, float sharpening);
\ No newline at end of file
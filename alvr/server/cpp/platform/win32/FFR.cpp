@@ -66,6 +66,8 @@ namespace {
 			eyeWidthRatioAligned, eyeHeightRatioAligned,
 			centerSizeXAligned, centerSizeYAligned, centerShiftXAligned, centerShiftYAligned, edgeRatioX, edgeRatioY };
 	}
+
+	FoveationVars gFoveationVars;
 }
 
 
@@ -75,11 +77,14 @@ void FFR::GetOptimizedResolution(uint32_t* width, uint32_t* height) {
 	*height = fovVars.optimizedEyeHeight;
 }
 
-FFR::FFR(ID3D11Device* device) : mDevice(device) {}
+FFR::FFR(ID3D11Device* device) : mDevice(device) {
+	mDevice->GetImmediateContext(&mContext);
+}
 
 void FFR::Initialize(ID3D11Texture2D* compositionTexture) {
 	auto fovVars = CalculateFoveationVars();
-	ComPtr<ID3D11Buffer> foveatedRenderingBuffer = CreateBuffer(mDevice.Get(), fovVars);
+	gFoveationVars = fovVars;
+	mFoveatedRenderingBuffer = CreateBuffer(mDevice.Get(), fovVars, D3D11_USAGE_DEFAULT);
 
 	std::vector<uint8_t> quadShaderCSO(QUAD_SHADER_CSO_PTR, QUAD_SHADER_CSO_PTR + QUAD_SHADER_CSO_LEN);
 	mQuadVertexShader = CreateVertexShader(mDevice.Get(), quadShaderCSO);
@@ -91,7 +96,7 @@ void FFR::Initialize(ID3D11Texture2D* compositionTexture) {
 		std::vector<uint8_t> compressAxisAlignedShaderCSO(COMPRESS_AXIS_ALIGNED_CSO_PTR, COMPRESS_AXIS_ALIGNED_CSO_PTR + COMPRESS_AXIS_ALIGNED_CSO_LEN);
 		auto compressAxisAlignedPipeline = RenderPipeline(mDevice.Get());
 		compressAxisAlignedPipeline.Initialize({ compositionTexture }, mQuadVertexShader.Get(),
-			compressAxisAlignedShaderCSO, mOptimizedTexture.Get(), foveatedRenderingBuffer.Get());
+			compressAxisAlignedShaderCSO, mOptimizedTexture.Get(), mFoveatedRenderingBuffer.Get());
 
 		mPipelines.push_back(compressAxisAlignedPipeline);
 	} else {
@@ -100,6 +105,15 @@ void FFR::Initialize(ID3D11Texture2D* compositionTexture) {
 }
 
 void FFR::Render() {
+	// Parameters changed while streaming are applied only if the encoded frame size is the same
+	auto fovVars = CalculateFoveationVars();
+	if (memcmp(&fovVars, &gFoveationVars, sizeof(FoveationVars)) != 0 &&
+		fovVars.optimizedEyeWidth == gFoveationVars.optimizedEyeWidth &&
+		fovVars.optimizedEyeHeight == gFoveationVars.optimizedEyeHeight) {
+		gFoveationVars = fovVars;
+		UpdateBuffer(mContext.Get(), mFoveatedRenderingBuffer.Get(), &gFoveationVars);
+	}
+
 	for (auto &p : mPipelines) {
 		p.Render();
 	}
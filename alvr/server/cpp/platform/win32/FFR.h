@@ -15,6 +15,8 @@ private:
 	Microsoft::WRL::ComPtr<ID3D11Device> mDevice;
 	Microsoft::WRL::ComPtr<ID3D11Texture2D> mOptimizedTexture;
 	Microsoft::WRL::ComPtr<ID3D11VertexShader> mQuadVertexShader;
+	Microsoft::WRL::ComPtr<ID3D11Buffer> mFoveatedRenderingBuffer;
+	Microsoft::WRL::ComPtr<ID3D11DeviceContext> mContext;
 
 	std::vector<d3d_render_utils::RenderPipeline> mPipelines;
 };
@@ -16,6 +16,15 @@ FrameRender::FrameRender(std::shared_ptr<CD3DRender> pD3DRender)
 }
 
 
+FrameRender::ColorCorrection FrameRender::GetColorCorrection()
+{
+	return { (float)Settings::Instance().m_renderWidth, (float)Settings::Instance().m_renderHeight,
+			 Settings::Instance().m_brightness, Settings::Instance().m_contrast + 1.f,
+			 Settings::Instance().m_saturation + 1.f, Settings::Instance().m_gamma,
+			 Settings::Instance().m_sharpening };
+}
+
+
 FrameRender::~FrameRender()
 {
 }
@@ -281,25 +290,12 @@ bool FrameRender::Startup()
 			Settings::Instance().m_renderWidth, Settings::Instance().m_renderHeight,
 			DXGI_FORMAT_R8G8B8A8_UNORM_SRGB);
 
-		struct ColorCorrection {
-			float renderWidth;
-			float renderHeight;
-			float brightness;
-			float contrast;
-			float saturation;
-			float gamma;
-			float sharpening;
-			float _align;
-		};
-		ColorCorrection colorCorrectionStruct = { (float)Settings::Instance().m_renderWidth, (float)Settings::Instance().m_renderHeight,
-												  Settings::Instance().m_brightness, Settings::Instance().m_contrast + 1.f,
-												  Settings::Instance().m_saturation + 1.f, Settings::Instance().m_gamma,
-												  Settings::Instance().m_sharpening };
-		ComPtr<ID3D11Buffer> colorCorrectionBuffer = CreateBuffer(m_pD3DRender->GetDevice(), colorCorrectionStruct);
+		m_colorCorrection = GetColorCorrection();
+		m_colorCorrectionBuffer = CreateBuffer(m_pD3DRender->GetDevice(), m_colorCorrection, D3D11_USAGE_DEFAULT);
 
 		m_colorCorrectionPipeline = std::make_unique<RenderPipeline>(m_pD3DRender->GetDevice());
 		m_colorCorrectionPipeline->Initialize({ m_pStagingTexture.Get() }, quadVertexShader.Get(), colorCorrectionShaderCSO,
-											  colorCorrectedTexture.Get(), colorCorrectionBuffer.Get());
+											  colorCorrectedTexture.Get(), m_colorCorrectionBuffer.Get());
 
 		m_pStagingTexture = colorCorrectedTexture;
 	}
@@ -463,6 +459,12 @@ bool FrameRender::RenderFrame(ID3D11Texture2D *pTexture[][2], vr::VRTextureBound
 	}
 
 	if (enableColorCorrection) {
+		auto colorCorrection = GetColorCorrection();
+		if (memcmp(&colorCorrection, &m_colorCorrection, sizeof(ColorCorrection)) != 0) {
+			m_colorCorrection = colorCorrection;
+			UpdateBuffer(m_pD3DRender->GetContext(), m_colorCorrectionBuffer.Get(), &m_colorCorrection);
+		}
+
 		m_colorCorrectionPipeline->Render();
 	}
 
@@ -85,7 +85,22 @@ private:
 	// Parameter for Draw method. 2-triangles for both eyes.
 	static const int VERTEX_INDEX_COUNT = 12;
 
+	struct ColorCorrection {
+		float renderWidth;
+		float renderHeight;
+		float brightness;
+		float contrast;
+		float saturation;
+		float gamma;
+		float sharpening;
+		float _align;
+	};
+	static ColorCorrection GetColorCorrection();
+
 	std::unique_ptr<d3d_render_utils::RenderPipeline> m_colorCorrectionPipeline;
+	// Updated when the parameters are changed while streaming
+	ComPtr<ID3D11Buffer> m_colorCorrectionBuffer;
+	ColorCorrection m_colorCorrection;
 	bool enableColorCorrection;
 
 	std::unique_ptr<FFR> m_ffr;
@@ -0,0 +1,86 @@
+use crate::statistics::StatisticsManager;
+use alvr_sockets::{Haptics, VideoFrameHeaderPacket};
+use std::{collections::HashMap, time::Duration};
+use tokio::sync::mpsc;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClientRole {
+    // Drives the game: its tracking, inputs and statistics are used and it receives haptics
+    Primary,
+    // Receives the same video and audio as the primary client. Its tracking and inputs are ignored
+    Spectator,
+}
+
+pub struct ClientSession {
+    pub role: ClientRole,
+    pub video_sender: Option<mpsc::UnboundedSender<(VideoFrameHeaderPacket, Vec<u8>)>>,
+    pub haptics_sender: Option<mpsc::UnboundedSender<Haptics>>,
+    // Only the primary client reports frame statistics, since its tracking paces the game
+    pub statistics_manager: Option<StatisticsManager>,
+    pub last_average_total_latency: Duration,
+}
+
+impl ClientSession {
+    pub fn new(role: ClientRole) -> Self {
+        Self {
+            role,
+            video_sender: None,
+            haptics_sender: None,
+            statistics_manager: None,
+            last_average_total_latency: Duration::ZERO,
+        }
+    }
+}
+
+// Sessions of the connected clients, keyed by hostname
+#[derive(Default)]
+pub struct ClientSessions(HashMap<String, ClientSession>);
+
+impl ClientSessions {
+    pub fn get(&self, hostname: &str) -> Option<&ClientSession> {
+        self.0.get(hostname)
+    }
+
+    pub fn get_mut(&mut self, hostname: &str) -> Option<&mut ClientSession> {
+        self.0.get_mut(hostname)
+    }
+
+    pub fn insert(&mut self, hostname: String, session: ClientSession) {
+        self.0.insert(hostname, session);
+    }
+
+    pub fn remove(&mut self, hostname: &str) {
+        self.0.remove(hostname);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &ClientSession)> {
+        self.0.iter()
+    }
+
+    pub fn primary_mut(&mut self) -> Option<&mut ClientSession> {
+        self.0
+            .values_mut()
+            .find(|session| session.role == ClientRole::Primary)
+    }
+
+    pub fn primary_statistics(&mut self) -> Option<&mut StatisticsManager> {
+        self.primary_mut()?.statistics_manager.as_mut()
+    }
+
+    // Role of the next client to connect, or None if there is no room left
+    pub fn next_role(&self, max_spectators: usize) -> Option<ClientRole> {
+        let spectators_count = self
+            .0
+            .values()
+            .filter(|session| session.role == ClientRole::Spectator)
+            .count();
+
+        if !self.0.values().any(|s| s.role == ClientRole::Primary) {
+            Some(ClientRole::Primary)
+        } else if spectators_count < max_spectators {
+            Some(ClientRole::Spectator)
+        } else {
+            None
+        }
+    }
+}
@@ -1,10 +1,14 @@
 use crate::{
-    buttons::BUTTON_PATH_FROM_ID, connection_utils, statistics::StatisticsManager,
-    tracking::TrackingManager, AlvrButtonType_BUTTON_TYPE_BINARY,
-    AlvrButtonType_BUTTON_TYPE_SCALAR, AlvrButtonValue, AlvrButtonValue__bindgen_ty_1,
-    AlvrDeviceMotion, AlvrQuat, EyeFov, OculusHand, CLIENTS_UPDATED_NOTIFIER,
-    DISCONNECT_CLIENT_NOTIFIER, HAPTICS_SENDER, LAST_AVERAGE_TOTAL_LATENCY, RESTART_NOTIFIER,
-    SERVER_DATA_MANAGER, STATISTICS_MANAGER, VIDEO_SENDER,
+    buttons::BUTTON_PATH_FROM_ID,
+    client_session::{ClientRole, ClientSession},
+    connection_utils,
+    logging_backend::CONNECTION_ID,
+    statistics::StatisticsManager,
+    tracking::TrackingManager,
+    AlvrButtonType_BUTTON_TYPE_BINARY, AlvrButtonType_BUTTON_TYPE_SCALAR, AlvrButtonValue,
+    AlvrButtonValue__bindgen_ty_1, AlvrDeviceMotion, AlvrQuat, EyeFov, OculusHand,
+    CLIENTS_UPDATED_NOTIFIER, CLIENT_SESSIONS, DISCONNECT_CLIENT_NOTIFIER, FILESYSTEM_LAYOUT,
+    RESTART_NOTIFIER, SERVER_DATA_MANAGER,
 };
 use alvr_audio::{AudioDevice, AudioDeviceType};
 use alvr_common::{
@@ -14,17 +18,20 @@ use alvr_common::{
     HEAD_ID,
 };
 use alvr_events::{ButtonEvent, ButtonValue, EventType};
-use alvr_session::{CodecType, FrameSize, OpenvrConfig};
+use alvr_session::{
+    CodecType, FrameSize, OpenvrConfig, OpenvrLiveConfig, Settings, SocketProtocol,
+};
 use alvr_sockets::{
     spawn_cancelable, ClientConfigPacket, ClientConnectionResult, ClientControlPacket,
-    ClientListAction, ClientStatistics, ControlSocketReceiver, ControlSocketSender, PeerType,
-    ProtoControlSocket, ServerControlPacket, StreamSocketBuilder, Tracking, AUDIO, HAPTICS,
-    STATISTICS, TRACKING, VIDEO,
+    ClientListAction, ClientStatistics, ControlSocketReceiver, ControlSocketSender, FecConfig,
+    FoveationConfig, NetworkEstimator, PeerType, ProtoControlSocket, ServerControlPacket,
+    StreamKey, StreamRecorder, StreamSocketBuilder, Tracking, AUDIO, HAPTICS, PROBE,
+    PROBE_INTERVAL, STATISTICS, TRACKING, VIDEO,
 };
 use futures::future::{BoxFuture, Either};
 use settings_schema::Switch;
 use std::{
-    future,
+    fs, future,
     net::IpAddr,
     process::Command,
     str::FromStr,
@@ -44,6 +51,12 @@ const CONTROL_CONNECT_RETRY_PAUSE: Duration = Duration::from_millis(500);
 const RETRY_CONNECT_MIN_INTERVAL: Duration = Duration::from_secs(1);
 const NETWORK_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
 const CLEANUP_PAUSE: Duration = Duration::from_millis(500);
+// Frames are split into many packets, so bigger groups with less redundancy can be used
+const VIDEO_FEC_CONFIG: FecConfig = FecConfig {
+    group_size: 20,
+    min_redundancy: 0.05,
+    max_redundancy: 0.5,
+};
 
 fn align32(value: f32) -> u32 {
     ((value / 32.).floor() * 32.) as u32
@@ -53,31 +66,159 @@ fn mbits_to_bytes(value: u64) -> u32 {
     (value * 1024 * 1024 / 8) as u32
 }
 
+// Driver properties that can be changed while streaming
+fn openvr_live_config(settings: &Settings) -> OpenvrLiveConfig {
+    let mut config = OpenvrLiveConfig {
+        encode_bitrate_mbs: settings.video.encode_bitrate_mbs,
+        ..Default::default()
+    };
+
+    if let Switch::Enabled(bitrate_config) = &settings.video.adaptive_bitrate {
+        config.enable_adaptive_bitrate = true;
+        config.bitrate_maximum = bitrate_config.bitrate_maximum;
+        config.latency_target = bitrate_config.latency_target;
+        if let Switch::Enabled(frametime_config) = &bitrate_config.latency_use_frametime {
+            config.latency_use_frametime = true;
+            config.latency_target_maximum = frametime_config.latency_target_maximum;
+            config.latency_target_offset = frametime_config.latency_target_offset;
+        }
+        config.latency_threshold = bitrate_config.latency_threshold;
+        config.bitrate_up_rate = bitrate_config.bitrate_up_rate;
+        config.bitrate_down_rate = bitrate_config.bitrate_down_rate;
+        config.bitrate_light_load_threshold = bitrate_config.bitrate_light_load_threshold;
+        config.bitrate_use_network_estimate = bitrate_config.use_network_estimate;
+    }
+
+    if let Switch::Enabled(controllers_config) = &settings.headset.controllers {
+        config.steamvr_hmd_prediction_multiplier =
+            controllers_config.steamvr_hmd_prediction_multiplier;
+        config.steamvr_ctrl_prediction_multiplier =
+            controllers_config.steamvr_ctrl_prediction_multiplier;
+        config.haptics_intensity = controllers_config.haptics_intensity;
+        config.haptics_amplitude_curve = controllers_config.haptics_amplitude_curve;
+        config.haptics_min_duration = controllers_config.haptics_min_duration;
+        config.haptics_low_duration_amplitude_multiplier =
+            controllers_config.haptics_low_duration_amplitude_multiplier;
+        config.haptics_low_duration_range = controllers_config.haptics_low_duration_range;
+    }
+
+    if let Switch::Enabled(foveation_config) = &settings.video.foveated_rendering {
+        config.foveation_center_size_x = foveation_config.center_size_x;
+        config.foveation_center_size_y = foveation_config.center_size_y;
+        config.foveation_center_shift_x = foveation_config.center_shift_x;
+        config.foveation_center_shift_y = foveation_config.center_shift_y;
+        config.foveation_edge_ratio_x = foveation_config.edge_ratio_x;
+        config.foveation_edge_ratio_y = foveation_config.edge_ratio_y;
+    }
+
+    if let Switch::Enabled(color_config) = &settings.video.color_correction {
+        config.brightness = color_config.brightness;
+        config.contrast = color_config.contrast;
+        config.saturation = color_config.saturation;
+        config.gamma = color_config.gamma;
+        config.sharpening = color_config.sharpening;
+    }
+
+    config
+}
+
+fn foveation_config(live_config: &OpenvrLiveConfig) -> FoveationConfig {
+    FoveationConfig {
+        center_size_x: live_config.foveation_center_size_x,
+        center_size_y: live_config.foveation_center_size_y,
+        center_shift_x: live_config.foveation_center_shift_x,
+        center_shift_y: live_config.foveation_center_shift_y,
+        edge_ratio_x: live_config.foveation_edge_ratio_x,
+        edge_ratio_y: live_config.foveation_edge_ratio_y,
+    }
+}
+
+// Bitrate parameters are copied periodically by the keepalive loop
+fn push_live_config(live_config: &OpenvrLiveConfig) {
+    unsafe {
+        crate::SetHapticsParameters(
+            live_config.haptics_intensity,
+            live_config.haptics_amplitude_curve,
+            live_config.haptics_min_duration,
+            live_config.haptics_low_duration_amplitude_multiplier,
+            live_config.haptics_low_duration_range,
+        );
+        crate::SetFoveationParameters(
+            live_config.foveation_center_size_x,
+            live_config.foveation_center_size_y,
+            live_config.foveation_center_shift_x,
+            live_config.foveation_center_shift_y,
+            live_config.foveation_edge_ratio_x,
+            live_config.foveation_edge_ratio_y,
+        );
+        crate::SetColorCorrectionParameters(
+            live_config.brightness,
+            live_config.contrast,
+            live_config.saturation,
+            live_config.gamma,
+            live_config.sharpening,
+        );
+    }
+}
+
 #[derive(Clone)]
 struct ClientId {
     hostname: String,
     ip: IpAddr,
 }
 
-async fn client_discovery(auto_trust_clients: bool) -> StrResult<ClientId> {
+async fn client_discovery() -> StrResult<ClientId> {
     let (ip, handshake_packet) =
         connection_utils::search_client_loop(|handshake_packet| async move {
+            // reserved1 carries the fingerprint of the client certificate
+            let certificate_fingerprint = handshake_packet.reserved1;
+
             let mut data_manager_ref = SERVER_DATA_MANAGER.write();
             data_manager_ref.update_client_list(
                 handshake_packet.hostname.clone(),
                 ClientListAction::AddIfMissing {
                     display_name: handshake_packet.device_name,
+                    certificate_fingerprint: Some(certificate_fingerprint.clone()),
                 },
                 Some(&CLIENTS_UPDATED_NOTIFIER),
             );
+            data_manager_ref.update_client_list(
+                handshake_packet.hostname.clone(),
+                ClientListAction::PinCertificate(certificate_fingerprint.clone()),
+                Some(&CLIENTS_UPDATED_NOTIFIER),
+            );
 
-            if let Some(connection_desc) = data_manager_ref
+            let (trusted, certificate_matches) = data_manager_ref
                 .client_list()
                 .get(&handshake_packet.hostname)
-            {
-                connection_desc.trusted || auto_trust_clients
-            } else {
+                .map(|connection_desc| {
+                    (
+                        connection_desc.trusted,
+                        connection_desc.certificate_fingerprint.as_ref()
+                            == Some(&certificate_fingerprint),
+                    )
+                })
+                .unwrap_or_default();
+
+            if !trusted {
+                // reserved2 carries the pairing code displayed by the client. The client becomes
+                // trusted only when the same code is submitted from the dashboard.
+                data_manager_ref.add_pairing_request(
+                    handshake_packet.reserved2,
+                    handshake_packet.hostname,
+                    certificate_fingerprint,
+                );
+
+                false
+            } else if !certificate_matches {
+                warn!(
+                    "Client {} does not match the trusted certificate",
+                    handshake_packet.hostname
+                );
+
                 false
+            } else {
+                true
             }
         })
         .await?;
@@ -89,7 +230,10 @@ async fn client_discovery(auto_trust_clients: bool) -> StrResult<ClientId> {
 }
 
 struct ConnectionInfo {
+    hostname: String,
+    role: ClientRole,
     client_ip: IpAddr,
+    stream_key: StreamKey,
     version: Option<Version>,
     control_sender: ControlSocketSender<ServerControlPacket>,
     control_receiver: ControlSocketReceiver<ClientControlPacket>,
@@ -98,22 +242,45 @@ struct ConnectionInfo {
 
 async fn client_handshake(
     trusted_discovered_client_id: Option<ClientId>,
+    role: ClientRole,
 ) -> StrResult<ConnectionInfo> {
-    let client_ips = if let Some(id) = trusted_discovered_client_id {
-        vec![id.ip]
+    // (hostname, ip, pinned certificate fingerprint)
+    let clients_info = if let Some(id) = trusted_discovered_client_id {
+        let certificate_fingerprint = SERVER_DATA_MANAGER
+            .read()
+            .client_list()
+            .get(&id.hostname)
+            .and_then(|client| client.certificate_fingerprint.clone());
+
+        vec![(id.hostname, id.ip, certificate_fingerprint)]
     } else {
+        let sessions = CLIENT_SESSIONS.lock();
         SERVER_DATA_MANAGER.read().client_list().iter().fold(
             Vec::new(),
-            |mut clients_info, (_, client)| {
-                clients_info.extend(client.manual_ips.clone());
+            |mut clients_info, (hostname, client)| {
+                // skip clients that are already connected
+                if sessions.get(hostname).is_some() {
+                    return clients_info;
+                }
+
+                clients_info.extend(
+                    client
+                        .manual_ips
+                        .iter()
+                        .map(|&ip| (hostname.clone(), ip, client.certificate_fingerprint.clone())),
+                );
                 clients_info
             },
         )
     };
+    let client_peers = clients_info
+        .iter()
+        .map(|(_, ip, certificate_fingerprint)| (*ip, certificate_fingerprint.clone()))
+        .collect::<Vec<_>>();
 
     let (mut proto_socket, headset_info, client_ip, server_ip) = loop {
         if let Ok((mut proto_socket, client_ip)) =
-            ProtoControlSocket::connect_to(PeerType::AnyClient(client_ips.clone())).await
+            ProtoControlSocket::connect_to(PeerType::AnyClient(client_peers.clone())).await
         {
             if let ClientConnectionResult::ServerAccepted {
                 headset_info,
@@ -131,7 +298,22 @@ async fn client_handshake(
         time::sleep(CONTROL_CONNECT_RETRY_PAUSE).await;
     };
 
-    let settings = SERVER_DATA_MANAGER.read().settings().clone();
+    let hostname = clients_info
+        .iter()
+        .find(|(_, ip, _)| *ip == client_ip)
+        .map(|(hostname, ..)| hostname.clone())
+        .ok_or_else(enone!())?;
+
+    // Clients added manually by IP are pinned the first time they connect
+    SERVER_DATA_MANAGER.write().update_client_list(
+        hostname.clone(),
+        ClientListAction::PinCertificate(proto_socket.client_fingerprint().to_owned()),
+        None,
+    );
+    let stream_key = proto_socket.stream_key();
+
+    // Each client can use a different profile
+    let settings = SERVER_DATA_MANAGER.read().client_settings(&hostname);
 
     let (eye_width, eye_height) = match settings.video.render_resolution {
         FrameSize::Scale(scale) => (
@@ -140,8 +322,8 @@ async fn client_handshake(
         ),
         FrameSize::Absolute { width, height } => (width as f32 / 2_f32, height as f32),
     };
-    let video_eye_width = align32(eye_width);
-    let video_eye_height = align32(eye_height);
+    let mut video_eye_width = align32(eye_width);
+    let mut video_eye_height = align32(eye_height);
 
     let (eye_width, eye_height) = match settings.video.recommended_target_resolution {
         FrameSize::Scale(scale) => (
@@ -153,7 +335,7 @@ async fn client_handshake(
     let target_eye_width = align32(eye_width);
     let target_eye_height = align32(eye_height);
 
-    let fps = {
+    let mut fps = {
         let mut best_match = 0_f32;
         let mut min_diff = f32::MAX;
         for rr in &headset_info.available_refresh_rates {
@@ -173,6 +355,15 @@ async fn client_handshake(
         warn!("Chosen refresh rate not supported. Using {fps}Hz");
     }
 
+    // Spectators receive the stream encoded for the primary client
+    if role == ClientRole::Spectator {
+        let data_manager = SERVER_DATA_MANAGER.read();
+        let openvr_config = &data_manager.session().openvr_config;
+        video_eye_width = openvr_config.eye_resolution_width;
+        video_eye_height = openvr_config.eye_resolution_height;
+        fps = openvr_config.refresh_rate as _;
+    }
+
     let dashboard_url = format!(
         "http://{server_ip}:{}/",
         settings.connection.web_server_port
@@ -208,6 +399,8 @@ async fn client_handshake(
     let client_config = ClientConfigPacket {
         session_desc: {
             let mut session = SERVER_DATA_MANAGER.read().session().clone();
+            // the client reads its settings from session_settings
+            session.session_settings = session.client_session_settings(&hostname).clone();
             if cfg!(target_os = "linux") {
                 session.session_settings.video.foveated_rendering.enabled = false;
             }
@@ -226,40 +419,20 @@ async fn client_handshake(
 
     let (mut control_sender, control_receiver) = proto_socket.split();
 
-    let mut bitrate_maximum = 0;
-    let mut latency_target = 0;
-    let mut latency_use_frametime = false;
-    let mut latency_target_maximum = 0;
-    let mut latency_target_offset = 0;
-    let mut latency_threshold = 0;
-    let mut bitrate_up_rate = 0;
-    let mut bitrate_down_rate = 0;
-    let mut bitrate_light_load_threshold = 0.0;
-    let enable_adaptive_bitrate = if let Switch::Enabled(config) = settings.video.adaptive_bitrate {
-        bitrate_maximum = config.bitrate_maximum;
-        latency_target = config.latency_target;
-
-        latency_use_frametime = if let Switch::Enabled(config) = config.latency_use_frametime {
-            latency_target_maximum = config.latency_target_maximum;
-            latency_target_offset = config.latency_target_offset;
-
-            true
-        } else {
-            false
-        };
-
-        latency_threshold = config.latency_threshold;
-        bitrate_up_rate = config.bitrate_up_rate;
-        bitrate_down_rate = config.bitrate_down_rate;
-        bitrate_light_load_threshold = config.bitrate_light_load_threshold;
-
-        true
-    } else {
-        false
-    };
+    // Spectators cannot change the driver configuration
+    if role == ClientRole::Spectator {
+        return Ok(ConnectionInfo {
+            hostname,
+            role,
+            client_ip,
+            stream_key,
+            version,
+            control_sender,
+            control_receiver,
+            microphone_sample_rate: headset_info.microphone_sample_rate,
+        });
+    }
 
-    let mut steamvr_hmd_prediction_multiplier = 0.0;
-    let mut steamvr_ctrl_prediction_multiplier = 0.0;
     let mut controllers_mode_idx = 0;
     let mut controllers_tracking_system_name = "".into();
     let mut controllers_manufacturer_name = "".into();
@@ -275,15 +448,8 @@ async fn client_handshake(
     let mut angular_velocity_cutoff = 0.0;
     let mut position_offset_left = [0.0; 3];
     let mut rotation_offset_left = [0.0; 3];
-    let mut haptics_intensity = 0.0;
-    let mut haptics_amplitude_curve = 0.0;
-    let mut haptics_min_duration = 0.0;
-    let mut haptics_low_duration_amplitude_multiplier = 0.0;
-    let mut haptics_low_duration_range = 0.0;
     let mut use_headset_tracking_system = false;
-    let controllers_enabled = if let Switch::Enabled(config) = settings.headset.controllers {
-        steamvr_hmd_prediction_multiplier = config.steamvr_hmd_prediction_multiplier;
-        steamvr_ctrl_prediction_multiplier = config.steamvr_ctrl_prediction_multiplier;
+    let controllers_enabled = if let Switch::Enabled(config) = &settings.headset.controllers {
         controllers_mode_idx = config.mode_idx;
         controllers_tracking_system_name = config.tracking_system_name.clone();
         controllers_manufacturer_name = config.manufacturer_name.clone();
@@ -299,63 +465,21 @@ async fn client_handshake(
         angular_velocity_cutoff = config.angular_velocity_cutoff;
         position_offset_left = config.position_offset_left;
         rotation_offset_left = config.rotation_offset_left;
-        haptics_intensity = config.haptics_intensity;
-        haptics_amplitude_curve = config.haptics_amplitude_curve;
-        haptics_min_duration = config.haptics_min_duration;
-        haptics_low_duration_amplitude_multiplier =
-            config.haptics_low_duration_amplitude_multiplier;
-        haptics_low_duration_range = config.haptics_low_duration_range;
         use_headset_tracking_system = config.use_headset_tracking_system;
         true
     } else {
         false
     };
 
-    let mut foveation_center_size_x = 0.0;
-    let mut foveation_center_size_y = 0.0;
-    let mut foveation_center_shift_x = 0.0;
-    let mut foveation_center_shift_y = 0.0;
-    let mut foveation_edge_ratio_x = 0.0;
-    let mut foveation_edge_ratio_y = 0.0;
-    let enable_foveated_rendering =
-        if let Switch::Enabled(config) = settings.video.foveated_rendering {
-            foveation_center_size_x = config.center_size_x;
-            foveation_center_size_y = config.center_size_y;
-            foveation_center_shift_x = config.center_shift_x;
-            foveation_center_shift_y = config.center_shift_y;
-            foveation_edge_ratio_x = config.edge_ratio_x;
-            foveation_edge_ratio_y = config.edge_ratio_y;
-
-            true
-        } else {
-            false
-        };
-
-    let mut brightness = 0.0;
-    let mut contrast = 0.0;
-    let mut saturation = 0.0;
-    let mut gamma = 0.0;
-    let mut sharpening = 0.0;
-    let enable_color_correction = if let Switch::Enabled(config) = settings.video.color_correction {
-        brightness = config.brightness;
-        contrast = config.contrast;
-        saturation = config.saturation;
-        gamma = config.gamma;
-        sharpening = config.sharpening;
-        true
-    } else {
-        false
-    };
-
     let new_openvr_config = OpenvrConfig {
         universe_id: settings.headset.universe_id,
-        headset_serial_number: settings.headset.serial_number,
-        headset_tracking_system_name: settings.headset.tracking_system_name,
-        headset_model_number: settings.headset.model_number,
-        headset_driver_version: settings.headset.driver_version,
-        headset_manufacturer_name: settings.headset.manufacturer_name,
-        headset_render_model_name: settings.headset.render_model_name,
-        headset_registered_device_type: settings.headset.registered_device_type,
+        headset_serial_number: settings.headset.serial_number.clone(),
+        headset_tracking_system_name: settings.headset.tracking_system_name.clone(),
+        headset_model_number: settings.headset.model_number.clone(),
+        headset_driver_version: settings.headset.driver_version.clone(),
+        headset_manufacturer_name: settings.headset.manufacturer_name.clone(),
+        headset_render_model_name: settings.headset.render_model_name.clone(),
+        headset_registered_device_type: settings.headset.registered_device_type.clone(),
         eye_resolution_width: video_eye_width,
         eye_resolution_height: video_eye_height,
         target_eye_resolution_width: target_eye_width,
@@ -371,21 +495,8 @@ async fn client_handshake(
         use_10bit_encoder: settings.video.use_10bit_encoder,
         force_sw_encoding: settings.video.force_sw_encoding,
         sw_thread_count: settings.video.sw_thread_count,
-        encode_bitrate_mbs: settings.video.encode_bitrate_mbs,
-        enable_adaptive_bitrate,
-        bitrate_maximum,
-        latency_target,
-        latency_use_frametime,
-        latency_target_maximum,
-        latency_target_offset,
-        latency_threshold,
-        bitrate_up_rate,
-        bitrate_down_rate,
-        bitrate_light_load_threshold,
         position_offset: settings.headset.position_offset,
         controllers_enabled,
-        steamvr_hmd_prediction_multiplier,
-        steamvr_ctrl_prediction_multiplier,
         controllers_mode_idx,
         controllers_tracking_system_name,
         controllers_manufacturer_name,
@@ -401,30 +512,15 @@ async fn client_handshake(
         angular_velocity_cutoff,
         position_offset_left,
         rotation_offset_left,
-        haptics_intensity,
-        haptics_amplitude_curve,
-        haptics_min_duration,
-        haptics_low_duration_amplitude_multiplier,
-        haptics_low_duration_range,
         use_headset_tracking_system,
-        enable_foveated_rendering,
-        foveation_center_size_x,
-        foveation_center_size_y,
-        foveation_center_shift_x,
-        foveation_center_shift_y,
-        foveation_edge_ratio_x,
-        foveation_edge_ratio_y,
-        enable_color_correction,
-        brightness,
-        contrast,
-        saturation,
-        gamma,
-        sharpening,
-        enable_fec: settings.connection.enable_fec,
+        enable_foveated_rendering: matches!(settings.video.foveated_rendering, Switch::Enabled(_)),
+        enable_color_correction: matches!(settings.video.color_correction, Switch::Enabled(_)),
         linux_async_reprojection: settings.extra.patches.linux_async_reprojection,
+        live: openvr_live_config(&settings),
     };
 
-    if SERVER_DATA_MANAGER.read().session().openvr_config != new_openvr_config {
+    let old_openvr_config = SERVER_DATA_MANAGER.read().session().openvr_config.clone();
+    if old_openvr_config.requires_restart(&new_openvr_config) {
         SERVER_DATA_MANAGER.write().session_mut().openvr_config = new_openvr_config;
 
         control_sender
@@ -436,10 +532,16 @@ async fn client_handshake(
 
         // waiting for execution canceling
         future::pending::<()>().await;
+    } else if old_openvr_config != new_openvr_config {
+        // The live fields are pushed to the driver when the stream starts
+        SERVER_DATA_MANAGER.write().session_mut().openvr_config = new_openvr_config;
     }
 
     Ok(ConnectionInfo {
+        hostname,
+        role,
         client_ip,
+        stream_key,
         version,
         control_sender,
         control_receiver,
@@ -454,6 +556,9 @@ impl Drop for StreamCloseGuard {
     fn drop(&mut self) {
         unsafe { crate::DeinitializeStreaming() };
 
+        // spectators cannot receive anything without the primary client
+        DISCONNECT_CLIENT_NOTIFIER.notify_waiters();
+
         let on_disconnect_script = SERVER_DATA_MANAGER
             .read()
             .settings()
@@ -472,24 +577,50 @@ impl Drop for StreamCloseGuard {
     }
 }
 
+// remove the client session on Drop
+struct ClientSessionGuard(String);
+
+impl Drop for ClientSessionGuard {
+    fn drop(&mut self) {
+        CLIENT_SESSIONS.lock().remove(&self.0);
+    }
+}
+
 async fn connection_pipeline() -> StrResult {
+    let max_spectators = {
+        let data_manager = SERVER_DATA_MANAGER.read();
+        let connection_settings = &data_manager.settings().connection;
+
+        // With UDP the stream port can be bound only once
+        if matches!(
+            connection_settings.stream_protocol,
+            SocketProtocol::Udp | SocketProtocol::ThrottledUdp { .. }
+        ) {
+            0
+        } else {
+            connection_settings.max_spectators as usize
+        }
+    };
+    let role = if let Some(role) = CLIENT_SESSIONS.lock().next_role(max_spectators) {
+        role
+    } else {
+        return Ok(());
+    };
+
     let mut trusted_discovered_client_id = None;
     let connection_info = loop {
-        let client_discovery_config = SERVER_DATA_MANAGER
+        let client_discovery_enabled = SERVER_DATA_MANAGER
             .read()
             .settings()
             .connection
-            .client_discovery
-            .clone();
+            .client_discovery;
 
         let try_connection_future: BoxFuture<Either<StrResult<ClientId>, _>> =
-            if let (Switch::Enabled(config), None) =
-                (client_discovery_config, &trusted_discovered_client_id)
-            {
+            if let (true, None) = (client_discovery_enabled, &trusted_discovered_client_id) {
                 Box::pin(async move {
                     let either = futures::future::select(
-                        Box::pin(client_discovery(config.auto_trust_clients)),
-                        Box::pin(client_handshake(None)),
+                        Box::pin(client_discovery()),
+                        Box::pin(client_handshake(None, role)),
                     )
                     .await;
 
@@ -499,9 +630,8 @@ async fn connection_pipeline() -> StrResult {
                     }
                 })
             } else {
-                Box::pin(async {
-                    Either::Right(client_handshake(trusted_discovered_client_id.clone()).await)
-                })
+                let client_id = trusted_discovered_client_id.clone();
+                Box::pin(async move { Either::Right(client_handshake(client_id, role).await) })
             };
 
         tokio::select! {
@@ -530,8 +660,31 @@ async fn connection_pipeline() -> StrResult {
         time::sleep(CLEANUP_PAUSE).await;
     };
 
+    let hostname = connection_info.hostname.clone();
+    CLIENT_SESSIONS
+        .lock()
+        .insert(hostname.clone(), ClientSession::new(role));
+
+    // Stream on a separate task so other clients can connect in the meantime. Its logs are tagged
+    // with the hostname
+    tokio::spawn(CONNECTION_ID.scope(hostname.clone(), async move {
+        let _session_guard = ClientSessionGuard(hostname);
+
+        alvr_common::show_err(stream_pipeline(connection_info).await);
+
+        // let any running task or socket shutdown
+        time::sleep(CLEANUP_PAUSE).await;
+    }));
+
+    Ok(())
+}
+
+async fn stream_pipeline(connection_info: ConnectionInfo) -> StrResult {
     let ConnectionInfo {
+        hostname,
+        role,
         client_ip,
+        stream_key,
         version: _,
         control_sender,
         mut control_receiver,
@@ -555,45 +708,82 @@ async fn connection_pipeline() -> StrResult {
         }
     }
 
-    let settings = SERVER_DATA_MANAGER.read().settings().clone();
+    let settings = SERVER_DATA_MANAGER.read().client_settings(&hostname);
 
-    let stream_socket = tokio::select! {
+    let mut stream_socket = tokio::select! {
         res = StreamSocketBuilder::connect_to_client(
             client_ip,
             settings.connection.stream_port,
             settings.connection.stream_protocol,
-            mbits_to_bytes(settings.video.encode_bitrate_mbs)
+            mbits_to_bytes(settings.video.encode_bitrate_mbs),
+            stream_key,
+            settings.connection.max_packet_size as _,
         ) => res?,
         _ = time::sleep(Duration::from_secs(5)) => {
             return fmt_e!("Timeout while setting up streams");
         }
     };
+
+    if settings.extra.record_streams {
+        let recordings_dir = FILESYSTEM_LAYOUT.recordings_dir();
+        let recording_path = recordings_dir.join(format!(
+            "{hostname}_{}.alvrrec",
+            chrono::Local::now().format("%Y-%m-%d_%H-%M-%S")
+        ));
+
+        let res = fs::create_dir_all(&recordings_dir)
+            .map_err(err!())
+            .and_then(|_| {
+                StreamRecorder::new(&recording_path, SERVER_DATA_MANAGER.read().session())
+            });
+        match res {
+            Ok(recorder) => {
+                info!("Recording streams to {}", recording_path.display());
+                stream_socket.set_recorder(recorder);
+            }
+            Err(e) => warn!("Failed to start recording: {e}"),
+        }
+    }
+
     let stream_socket = Arc::new(stream_socket);
 
-    *STATISTICS_MANAGER.lock() = Some(StatisticsManager::new(
-        settings.connection.statistics_history_size as _,
-    ));
+    // The driver loads the OpenvrConfig stored during the handshake, newer live values are pushed
+    // by the keepalive loop
+    let stream_openvr_config = SERVER_DATA_MANAGER.read().session().openvr_config.clone();
+    let live_config = Arc::new(parking_lot::Mutex::new(stream_openvr_config.live.clone()));
 
-    alvr_events::send_event(EventType::ClientConnected);
+    let is_primary = role == ClientRole::Primary;
+    info!("Client {hostname} connected as {role:?}");
 
-    {
-        let on_connect_script = settings.connection.on_connect_script;
+    // The encoder and the connection lifecycle events follow the primary client only
+    let _stream_guard = if is_primary {
+        if let Some(session) = CLIENT_SESSIONS.lock().get_mut(&hostname) {
+            session.statistics_manager = Some(StatisticsManager::new(
+                settings.connection.statistics_history_size as _,
+            ));
+        }
 
+        alvr_events::send_event(EventType::ClientConnected);
+
+        let on_connect_script = &settings.connection.on_connect_script;
         if !on_connect_script.is_empty() {
             info!("Running on connect script (connect): {on_connect_script}");
-            if let Err(e) = Command::new(&on_connect_script)
+            if let Err(e) = Command::new(on_connect_script)
                 .env("ACTION", "connect")
                 .spawn()
             {
                 warn!("Failed to run connect script: {e}");
             }
         }
-    }
 
-    unsafe { crate::InitializeStreaming() };
-    let _stream_guard = StreamCloseGuard;
+        unsafe { crate::InitializeStreaming() };
+        Some(StreamCloseGuard)
+    } else {
+        None
+    };
     let game_audio_loop: BoxFuture<_> = if let Switch::Enabled(desc) = settings.audio.game_audio {
-        let sender = stream_socket.request_stream(AUDIO).await?;
+        let fec = settings.connection.enable_fec.then(FecConfig::default);
+        let sender = stream_socket.request_stream(AUDIO, fec).await?;
         Box::pin(async move {
             loop {
                 let device = match AudioDevice::new(
@@ -608,21 +798,23 @@ async fn connection_pipeline() -> StrResult {
                         continue;
                     }
                 };
-                let mute_when_streaming = desc.mute_when_streaming;
+                let mute_when_streaming = desc.mute_when_streaming && is_primary;
 
                 #[cfg(windows)]
-                unsafe {
+                if is_primary {
                     let device_id = match alvr_audio::get_windows_device_id(&device) {
                         Ok(data) => data,
                         Err(_) => continue,
                     };
-                    crate::SetOpenvrProperty(
-                        *HEAD_ID,
-                        crate::to_cpp_openvr_prop(
-                            OpenvrPropertyKey::AudioDefaultPlaybackDeviceId,
-                            OpenvrPropValue::String(device_id),
-                        ),
-                    )
+                    unsafe {
+                        crate::SetOpenvrProperty(
+                            *HEAD_ID,
+                            crate::to_cpp_openvr_prop(
+                                OpenvrPropertyKey::AudioDefaultPlaybackDeviceId,
+                                OpenvrPropValue::String(device_id),
+                            ),
+                        )
+                    }
                 }
                 let new_sender = sender.clone();
                 match alvr_audio::record_audio_loop(device, 2, mute_when_streaming, new_sender)
@@ -633,7 +825,7 @@ async fn connection_pipeline() -> StrResult {
                 };
 
                 #[cfg(windows)]
-                {
+                if is_primary {
                     let default_device = match AudioDevice::new(
                         None,
                         &alvr_session::AudioDeviceId::Default,
@@ -662,7 +854,13 @@ async fn connection_pipeline() -> StrResult {
     } else {
         Box::pin(future::pending())
     };
-    let microphone_loop: BoxFuture<_> = if let Switch::Enabled(desc) = settings.audio.microphone {
+    // only the primary client can use the microphone
+    let microphone = if is_primary {
+        settings.audio.microphone
+    } else {
+        Switch::Disabled
+    };
+    let microphone_loop: BoxFuture<_> = if let Switch::Enabled(desc) = microphone {
         let input_device = AudioDevice::new(
             Some(settings.audio.linux_backend),
             &desc.input_device_id,
@@ -703,15 +901,30 @@ async fn connection_pipeline() -> StrResult {
     };
 
     let video_send_loop = {
-        let mut socket_sender = stream_socket.request_stream(VIDEO).await?;
+        let fec = settings.connection.enable_fec.then(|| VIDEO_FEC_CONFIG);
+        let mut socket_sender = stream_socket.request_stream(VIDEO, fec).await?;
+        let hostname = hostname.clone();
         async move {
             let (data_sender, mut data_receiver) = tmpsc::unbounded_channel();
-            *VIDEO_SENDER.lock() = Some(data_sender);
+            if let Some(session) = CLIENT_SESSIONS.lock().get_mut(&hostname) {
+                session.video_sender = Some(data_sender);
+            }
 
             while let Some((header, data)) = data_receiver.recv().await {
                 let mut buffer = socket_sender.new_buffer(&header, data.len())?;
                 buffer.get_mut().extend(data);
                 socket_sender.send_buffer(buffer).await.ok();
+
+                if let Some(stats) = CLIENT_SESSIONS
+                    .lock()
+                    .get_mut(&hostname)
+                    .and_then(|session| session.statistics_manager.as_mut())
+                {
+                    if let Some(redundancy) = socket_sender.fec_redundancy() {
+                        stats.report_fec_percentage((redundancy * 100.) as u32);
+                    }
+                    stats.report_video_packets_dropped(socket_sender.packets_dropped() as _);
+                }
             }
 
             Ok(())
@@ -719,10 +932,13 @@ async fn connection_pipeline() -> StrResult {
     };
 
     let haptics_send_loop = {
-        let mut socket_sender = stream_socket.request_stream(HAPTICS).await?;
+        let mut socket_sender = stream_socket.request_stream(HAPTICS, None).await?;
+        let hostname = hostname.clone();
         async move {
             let (data_sender, mut data_receiver) = tmpsc::unbounded_channel();
-            *HAPTICS_SENDER.lock() = Some(data_sender);
+            if let Some(session) = CLIENT_SESSIONS.lock().get_mut(&hostname) {
+                session.haptics_sender = Some(data_sender);
+            }
 
             while let Some(haptics) = data_receiver.recv().await {
                 socket_sender
@@ -735,10 +951,35 @@ async fn connection_pipeline() -> StrResult {
         }
     };
 
+    // Measures the round-trip time and the bandwidth, the client answers on the control socket
+    let network_estimator = Arc::new(parking_lot::Mutex::new(NetworkEstimator::default()));
+    let probe_loop = {
+        let mut probe_sender = stream_socket.request_stream(PROBE, None).await?;
+        let control_sender = Arc::clone(&control_sender);
+        let network_estimator = Arc::clone(&network_estimator);
+        async move {
+            let mut train_index = 0;
+            loop {
+                let ping_timestamp = network_estimator.lock().ping_timestamp();
+                control_sender
+                    .lock()
+                    .await
+                    .send(&ServerControlPacket::Ping(ping_timestamp))
+                    .await
+                    .ok();
+
+                alvr_sockets::send_probe_train(&mut probe_sender, train_index).await?;
+                train_index = train_index.wrapping_add(1);
+
+                time::sleep(PROBE_INTERVAL).await;
+            }
+        }
+    };
+
     let (playspace_sync_sender, playspace_sync_receiver) = smpsc::channel::<Vec2>();
 
     let is_tracking_ref_only = settings.headset.tracking_ref_only;
-    if !is_tracking_ref_only {
+    if !is_tracking_ref_only && is_primary {
         // use a separate thread because SetChaperone() is blocking
         thread::spawn(move || {
             while let Ok(packet) = playspace_sync_receiver.recv() {
@@ -762,29 +1003,18 @@ async fn connection_pipeline() -> StrResult {
         let mut receiver = stream_socket
             .subscribe_to_stream::<Tracking>(TRACKING)
             .await?;
+        let hostname = hostname.clone();
+        let live_config = Arc::clone(&live_config);
         async move {
-            let hmd_multiplier = settings
-                .headset
-                .controllers
-                .clone()
-                .into_option()
-                .map(|c| c.steamvr_hmd_prediction_multiplier)
-                .unwrap_or_default()
-                * -1.0;
-
-            let controller_multiplier = settings
-                .headset
-                .controllers
-                .clone()
-                .into_option()
-                .map(|c| c.steamvr_ctrl_prediction_multiplier)
-                .unwrap_or_default()
-                * -1.0;
-
             let tracking_manager = TrackingManager::new(settings.headset);
             loop {
                 let tracking = receiver.recv().await?.header;
 
+                // the tracking of spectators is ignored
+                if !is_primary {
+                    continue;
+                }
+
                 let mut device_motions = vec![];
                 for (id, motion) in tracking.device_motions {
                     let motion = if id == *HEAD_ID {
@@ -840,13 +1070,28 @@ async fn connection_pipeline() -> StrResult {
                     }
                 };
 
-                if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                let mut sessions = CLIENT_SESSIONS.lock();
+                let session = if let Some(session) = sessions.get_mut(&hostname) {
+                    session
+                } else {
+                    continue;
+                };
+                let last_average_total_latency = session.last_average_total_latency;
+
+                if let Some(stats) = &mut session.statistics_manager {
                     stats.report_tracking_received(tracking.target_timestamp);
 
+                    let (hmd_multiplier, controller_multiplier) = {
+                        let live_config = live_config.lock();
+                        (
+                            live_config.steamvr_hmd_prediction_multiplier * -1.0,
+                            live_config.steamvr_ctrl_prediction_multiplier * -1.0,
+                        )
+                    };
                     let head_prediction_s =
-                        LAST_AVERAGE_TOTAL_LATENCY.lock().as_secs_f32() * hmd_multiplier;
+                        last_average_total_latency.as_secs_f32() * hmd_multiplier;
                     let controllers_prediction_s =
-                        LAST_AVERAGE_TOTAL_LATENCY.lock().as_secs_f32() * controller_multiplier;
+                        last_average_total_latency.as_secs_f32() * controller_multiplier;
 
                     unsafe {
                         crate::SetTracking(
@@ -868,12 +1113,20 @@ async fn connection_pipeline() -> StrResult {
         let mut receiver = stream_socket
             .subscribe_to_stream::<ClientStatistics>(STATISTICS)
             .await?;
+        let hostname = hostname.clone();
         async move {
             loop {
                 let client_stats = receiver.recv().await?.header;
-                *LAST_AVERAGE_TOTAL_LATENCY.lock() = client_stats.average_total_pipeline_latency;
 
-                if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                let mut sessions = CLIENT_SESSIONS.lock();
+                let session = if let Some(session) = sessions.get_mut(&hostname) {
+                    session
+                } else {
+                    continue;
+                };
+                session.last_average_total_latency = client_stats.average_total_pipeline_latency;
+
+                if let Some(stats) = &mut session.statistics_manager {
                     let game_frame_interval =
                         Duration::from_nanos(unsafe { crate::GetGameFrameIntervalNs() });
                     let network_latency =
@@ -886,6 +1139,8 @@ async fn connection_pipeline() -> StrResult {
 
     let keepalive_loop = {
         let control_sender = Arc::clone(&control_sender);
+        let hostname = hostname.clone();
+        let live_config = Arc::clone(&live_config);
         async move {
             loop {
                 let res = control_sender
@@ -894,34 +1149,71 @@ async fn connection_pipeline() -> StrResult {
                     .send(&ServerControlPacket::KeepAlive)
                     .await;
                 if let Err(e) = res {
-                    alvr_events::send_event(EventType::ClientDisconnected);
+                    if is_primary {
+                        alvr_events::send_event(EventType::ClientDisconnected);
+                    }
                     info!("Client disconnected. Cause: {e}");
                     break Ok(());
                 }
                 time::sleep(NETWORK_KEEPALIVE_INTERVAL).await;
 
-                // copy some settings periodically into c++
-                let data_manager = SERVER_DATA_MANAGER.read();
-                let settings = data_manager.settings();
-
-                let mut bitrate_maximum = 0;
-                let adaptive_bitrate_enabled = if let Switch::Enabled(config) =
-                    &SERVER_DATA_MANAGER.read().settings().video.adaptive_bitrate
-                {
-                    bitrate_maximum = config.bitrate_maximum;
+                if !is_primary {
+                    continue;
+                }
 
-                    true
-                } else {
-                    false
-                };
+                // copy some settings periodically into c++
+                let settings = SERVER_DATA_MANAGER.read().client_settings(&hostname);
+                let mut new_live_config = openvr_live_config(&settings);
 
                 unsafe {
                     crate::SetBitrateParameters(
-                        settings.video.encode_bitrate_mbs,
-                        adaptive_bitrate_enabled,
-                        bitrate_maximum,
+                        new_live_config.encode_bitrate_mbs,
+                        new_live_config.enable_adaptive_bitrate,
+                        new_live_config.bitrate_maximum,
+                        new_live_config.bitrate_use_network_estimate,
                     )
                 };
+
+                let old_live_config = live_config.lock().clone();
+                if new_live_config == old_live_config {
+                    continue;
+                }
+
+                // Foveation parameters that change the size of the encoded frames are applied
+                // after SteamVR is restarted on the next connection
+                let new_encoded_resolution = OpenvrConfig {
+                    live: new_live_config.clone(),
+                    ..stream_openvr_config.clone()
+                }
+                .encoded_eye_resolution();
+                if new_encoded_resolution != stream_openvr_config.encoded_eye_resolution() {
+                    new_live_config.foveation_center_size_x =
+                        old_live_config.foveation_center_size_x;
+                    new_live_config.foveation_center_size_y =
+                        old_live_config.foveation_center_size_y;
+                    new_live_config.foveation_center_shift_x =
+                        old_live_config.foveation_center_shift_x;
+                    new_live_config.foveation_center_shift_y =
+                        old_live_config.foveation_center_shift_y;
+                    new_live_config.foveation_edge_ratio_x = old_live_config.foveation_edge_ratio_x;
+                    new_live_config.foveation_edge_ratio_y = old_live_config.foveation_edge_ratio_y;
+                }
+
+                push_live_config(&new_live_config);
+
+                let new_foveation_config = foveation_config(&new_live_config);
+                if stream_openvr_config.enable_foveated_rendering
+                    && new_foveation_config != foveation_config(&old_live_config)
+                {
+                    control_sender
+                        .lock()
+                        .await
+                        .send(&ServerControlPacket::FoveationConfig(new_foveation_config))
+                        .await
+                        .ok();
+                }
+
+                *live_config.lock() = new_live_config;
             }
         }
     };
@@ -929,16 +1221,49 @@ async fn connection_pipeline() -> StrResult {
     let control_loop = async move {
         loop {
             match control_receiver.recv().await {
-                Ok(ClientControlPacket::PlayspaceSync(packet)) => {
+                // spectators can only request keyframes
+                Ok(ClientControlPacket::PlayspaceSync(packet)) if is_primary => {
                     if !is_tracking_ref_only {
                         playspace_sync_sender.send(packet).ok();
                     }
                 }
                 Ok(ClientControlPacket::RequestIdr) => unsafe { crate::RequestIDR() },
-                Ok(ClientControlPacket::VideoErrorReport) => unsafe {
-                    crate::VideoErrorReportReceive()
-                },
-                Ok(ClientControlPacket::ViewsConfig(config)) => unsafe {
+                // Sent when a frame could not be recovered with FEC
+                Ok(ClientControlPacket::VideoErrorReport) => {
+                    if let Some(stats) = CLIENT_SESSIONS
+                        .lock()
+                        .get_mut(&hostname)
+                        .and_then(|session| session.statistics_manager.as_mut())
+                    {
+                        stats.report_fec_failure();
+                    }
+
+                    unsafe { crate::VideoErrorReportReceive() };
+                }
+                Ok(ClientControlPacket::Pong(timestamp)) => {
+                    network_estimator.lock().report_pong(timestamp)
+                }
+                Ok(ClientControlPacket::ProbeReport(report)) => {
+                    let estimate = {
+                        let mut network_estimator = network_estimator.lock();
+                        network_estimator.report_probe_train(&report);
+                        network_estimator.estimate()
+                    };
+
+                    if is_primary {
+                        unsafe {
+                            crate::ReportNetworkEstimate(
+                                estimate.bandwidth_bps.unwrap_or_default() as _,
+                                estimate.loss_rate,
+                            )
+                        };
+
+                        if let Some(stats) = CLIENT_SESSIONS.lock().primary_statistics() {
+                            stats.report_network_estimate(estimate);
+                        }
+                    }
+                }
+                Ok(ClientControlPacket::ViewsConfig(config)) if is_primary => unsafe {
                     crate::SetViewsConfig(crate::ViewsConfigData {
                         fov: [
                             EyeFov {
@@ -957,14 +1282,14 @@ async fn connection_pipeline() -> StrResult {
                         ipd_m: config.ipd_m,
                     });
                 },
-                Ok(ClientControlPacket::Battery(packet)) => unsafe {
+                Ok(ClientControlPacket::Battery(packet)) if is_primary => unsafe {
                     crate::SetBattery(packet.device_id, packet.gauge_value, packet.is_plugged);
 
-                    if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+                    if let Some(stats) = CLIENT_SESSIONS.lock().primary_statistics() {
                         stats.report_battery(packet.device_id, packet.gauge_value);
                     }
                 },
-                Ok(ClientControlPacket::Button { path_id, value }) => {
+                Ok(ClientControlPacket::Button { path_id, value }) if is_primary => {
                     if settings.extra.log_button_presses {
                         alvr_events::send_event(EventType::Button(ButtonEvent {
                             path: BUTTON_PATH_FROM_ID
@@ -991,7 +1316,9 @@ async fn connection_pipeline() -> StrResult {
                 }
                 Ok(_) => (),
                 Err(e) => {
-                    alvr_events::send_event(EventType::ClientDisconnected);
+                    if is_primary {
+                        alvr_events::send_event(EventType::ClientDisconnected);
+                    }
                     info!("Client disconnected. Cause: {e}");
                     break;
                 }
@@ -1001,23 +1328,32 @@ async fn connection_pipeline() -> StrResult {
         Ok(())
     };
 
+    let send_loop = {
+        let stream_socket = Arc::clone(&stream_socket);
+        async move { stream_socket.send_loop().await }
+    };
+
     let receive_loop = async move { stream_socket.receive_loop().await };
 
     tokio::select! {
         // Spawn new tasks and let the runtime manage threading
         res = spawn_cancelable(receive_loop) => {
-            alvr_events::send_event(EventType::ClientDisconnected);
+            if is_primary {
+                alvr_events::send_event(EventType::ClientDisconnected);
+            }
             if let Err(e) = res {
                 info!("Client disconnected. Cause: {e}" );
             }
 
             Ok(())
         },
+        res = spawn_cancelable(send_loop) => res,
         res = spawn_cancelable(game_audio_loop) => res,
         res = spawn_cancelable(microphone_loop) => res,
         res = spawn_cancelable(video_send_loop) => res,
         res = spawn_cancelable(statistics_receive_loop) => res,
         res = spawn_cancelable(haptics_send_loop) => res,
+        res = spawn_cancelable(probe_loop) => res,
         res = spawn_cancelable(tracking_receive_loop) => res,
 
         // Leave these loops on the current task
@@ -1038,14 +1374,13 @@ async fn connection_pipeline() -> StrResult {
     }
 }
 
+// Each iteration waits for one client to connect. The streaming of connected clients runs on
+// separate tasks
 pub async fn connection_lifecycle_loop() {
     loop {
         tokio::join!(
             async {
                 alvr_common::show_err(connection_pipeline().await);
-
-                // let any running task or socket shutdown
-                time::sleep(CLEANUP_PAUSE).await;
             },
             time::sleep(RETRY_CONNECT_MIN_INTERVAL),
         );
@@ -1,8 +1,10 @@
 mod buttons;
+mod client_session;
 mod connection;
 mod connection_utils;
 mod dashboard;
 mod logging_backend;
+mod metrics;
 mod statistics;
 mod tracking;
 mod web_server;
@@ -24,15 +26,18 @@ use alvr_common::{
     parking_lot::{Mutex, RwLock},
     ALVR_VERSION,
 };
-use alvr_events::EventType;
+use alvr_events::{Event, EventType};
 use alvr_filesystem::{self as afs, Layout};
 use alvr_server_data::ServerDataManager;
 use alvr_session::{OpenvrPropValue, OpenvrPropertyKey};
 use alvr_sockets::{ClientListAction, GpuVendor, Haptics, VideoFrameHeaderPacket};
-use statistics::StatisticsManager;
+use client_session::ClientSessions;
+use metrics::MetricsManager;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     ffi::{c_void, CStr, CString},
+    fs,
+    ops::Range,
     os::raw::c_char,
     ptr,
     sync::{
@@ -44,7 +49,10 @@ use std::{
 };
 use tokio::{
     runtime::Runtime,
-    sync::{broadcast, mpsc, Notify},
+    sync::{
+        broadcast::{self, error::RecvError},
+        Notify,
+    },
 };
 
 static FILESYSTEM_LAYOUT: Lazy<Layout> = Lazy::new(|| {
@@ -55,13 +63,9 @@ static SERVER_DATA_MANAGER: Lazy<RwLock<ServerDataManager>> =
 static RUNTIME: Lazy<Mutex<Option<Runtime>>> = Lazy::new(|| Mutex::new(Runtime::new().ok()));
 static WINDOW: Lazy<Mutex<Option<Arc<alcro::UI>>>> = Lazy::new(|| Mutex::new(None));
 
-static LAST_AVERAGE_TOTAL_LATENCY: Lazy<Mutex<Duration>> = Lazy::new(|| Mutex::new(Duration::ZERO));
-static STATISTICS_MANAGER: Lazy<Mutex<Option<StatisticsManager>>> = Lazy::new(|| Mutex::new(None));
-
-static VIDEO_SENDER: Lazy<Mutex<Option<mpsc::UnboundedSender<(VideoFrameHeaderPacket, Vec<u8>)>>>> =
-    Lazy::new(|| Mutex::new(None));
-static HAPTICS_SENDER: Lazy<Mutex<Option<mpsc::UnboundedSender<Haptics>>>> =
-    Lazy::new(|| Mutex::new(None));
+static CLIENT_SESSIONS: Lazy<Mutex<ClientSessions>> =
+    Lazy::new(|| Mutex::new(ClientSessions::default()));
+static METRICS: Lazy<Mutex<MetricsManager>> = Lazy::new(|| Mutex::new(MetricsManager::default()));
 
 static CLIENTS_UPDATED_NOTIFIER: Lazy<Notify> = Lazy::new(Notify::new);
 static DISCONNECT_CLIENT_NOTIFIER: Lazy<Notify> = Lazy::new(Notify::new);
@@ -160,12 +164,69 @@ pub fn notify_application_update() {
     alvr_commands::invoke_application_update(&FILESYSTEM_LAYOUT.launcher_exe()).ok();
 }
 
+// Events are received asynchronously, so callbacks can change the session without deadlocks
+async fn script_events_loop(mut events_receiver: broadcast::Receiver<Event>) {
+    // Sequences of the events sent while the callbacks were running. The session events among them
+    // are caused by the scripts
+    let mut script_sequences = VecDeque::<Range<u64>>::new();
+
+    loop {
+        let event = events_receiver.recv().await;
+
+        // Events are received in order, older ranges cannot match anymore
+        if let Ok(event) = &event {
+            while matches!(script_sequences.front(), Some(range) if range.end <= event.sequence) {
+                script_sequences.pop_front();
+            }
+        }
+
+        match event {
+            // Log events are skipped, otherwise a callback that prints would call itself
+            Ok(Event {
+                event_type: EventType::Log(_),
+                ..
+            }) => (),
+            // Session events caused by the scripts are skipped, otherwise a callback that changes a
+            // setting on "session" would call itself
+            Ok(Event {
+                sequence,
+                event_type: EventType::Session(_) | EventType::SessionUpdated,
+                ..
+            }) if script_sequences
+                .iter()
+                .any(|range| range.contains(&sequence)) => {}
+            Ok(event) => {
+                let event_json = match serde_json::to_value(&event) {
+                    Ok(event_json) => event_json,
+                    Err(_) => continue,
+                };
+
+                let event_id = event_json["id"].as_str().unwrap_or_default();
+                if SERVER_DATA_MANAGER.read().has_script_callbacks(event_id) {
+                    // Session events are sent only while holding the data manager lock, so all the
+                    // ones inside the range come from the callbacks
+                    let mut data_manager = SERVER_DATA_MANAGER.write();
+                    let first_sequence = alvr_events::next_event_sequence();
+                    data_manager.run_script_callbacks(&event_json);
+                    script_sequences.push_back(first_sequence..alvr_events::next_event_sequence());
+                }
+            }
+            Err(RecvError::Lagged(_)) => {
+                log::warn!("Some events were not delivered to the scripts")
+            }
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
+
 fn init() {
     let (log_sender, _) = broadcast::channel(web_server::WS_BROADCAST_CAPACITY);
-    let (events_sender, _) = broadcast::channel(web_server::WS_BROADCAST_CAPACITY);
-    logging_backend::init_logging(log_sender.clone(), events_sender.clone());
+    logging_backend::init_logging(log_sender.clone());
 
     if let Some(runtime) = RUNTIME.lock().as_mut() {
+        // Only the events sent from now on are passed to the scripts
+        let (_, events_receiver) = alvr_events::subscribe_events();
+
         // Acquire and drop the data manager lock to create session.json if not present
         // this is needed until Settings.cpp is replaced with Rust. todo: remove
         SERVER_DATA_MANAGER.write().session_mut();
@@ -186,10 +247,7 @@ fn init() {
                 }
             }
 
-            let web_server = alvr_common::show_err_async(web_server::web_server(
-                log_sender,
-                events_sender.clone(),
-            ));
+            let web_server = alvr_common::show_err_async(web_server::web_server(log_sender));
 
             tokio::select! {
                 _ = web_server => (),
@@ -197,6 +255,13 @@ fn init() {
             }
         });
 
+        runtime.spawn(async move {
+            tokio::select! {
+                _ = script_events_loop(events_receiver) => (),
+                _ = SHUTDOWN_NOTIFIER.notified() => (),
+            }
+        });
+
         thread::spawn(|| alvr_common::show_err(dashboard::ui_thread()));
     }
 
@@ -216,6 +281,12 @@ fn init() {
             session_ref.server_version = ALVR_VERSION.clone();
             session_ref.client_connections.clear();
         }
+
+        if let Ok(code) = fs::read_to_string(FILESYSTEM_LAYOUT.automation_script()) {
+            if let Err(e) = data_manager.execute_script(&code) {
+                log::warn!("Automation script failed: {e}");
+            }
+        }
     }
 
     unsafe {
@@ -294,15 +365,19 @@ pub unsafe extern "C" fn HmdDriverFactory(
     }
 
     extern "C" fn video_send(header: VideoFrame, buffer_ptr: *mut u8, len: i32) {
-        if let Some(sender) = &*VIDEO_SENDER.lock() {
+        let mut sessions = CLIENT_SESSIONS.lock();
+        let senders = sessions
+            .iter()
+            .filter_map(|(_, session)| session.video_sender.as_ref())
+            .collect::<Vec<_>>();
+
+        if let Some((&last_sender, other_senders)) = senders.split_last() {
             let header = VideoFrameHeaderPacket {
                 packet_counter: header.packetCounter,
                 tracking_frame_index: header.trackingFrameIndex,
                 video_frame_index: header.videoFrameIndex,
                 sent_time: header.sentTime,
                 frame_byte_size: header.frameByteSize,
-                fec_index: header.fecIndex,
-                fec_percentage: header.fecPercentage,
             };
 
             let mut vec_buffer = vec![0; len as _];
@@ -312,16 +387,25 @@ pub unsafe extern "C" fn HmdDriverFactory(
                 ptr::copy_nonoverlapping(buffer_ptr, vec_buffer.as_mut_ptr(), len as _);
             }
 
-            sender.send((header, vec_buffer)).ok();
+            // every client receives the same encoded frames. The buffer is copied only when
+            // spectators are connected
+            for sender in other_senders {
+                sender.send((header.clone(), vec_buffer.clone())).ok();
+            }
+            last_sender.send((header, vec_buffer)).ok();
 
-            if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+            if let Some(stats) = sessions.primary_statistics() {
                 stats.report_video_packet(len as _);
             }
         }
     }
 
     extern "C" fn haptics_send(path: u64, duration_s: f32, frequency: f32, amplitude: f32) {
-        if let Some(sender) = &*HAPTICS_SENDER.lock() {
+        if let Some(sender) = CLIENT_SESSIONS
+            .lock()
+            .primary_mut()
+            .and_then(|session| session.haptics_sender.as_ref())
+        {
             let haptics = Haptics {
                 path,
                 duration: Duration::from_secs_f32(duration_s),
@@ -362,29 +446,23 @@ pub unsafe extern "C" fn HmdDriverFactory(
     }
 
     extern "C" fn report_present(timestamp_ns: u64) {
-        if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+        if let Some(stats) = CLIENT_SESSIONS.lock().primary_statistics() {
             stats.report_frame_present(Duration::from_nanos(timestamp_ns));
         }
     }
 
     extern "C" fn report_composed(timestamp_ns: u64) {
-        if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+        if let Some(stats) = CLIENT_SESSIONS.lock().primary_statistics() {
             stats.report_frame_composed(Duration::from_nanos(timestamp_ns));
         }
     }
 
     extern "C" fn report_encoded(timestamp_ns: u64) {
-        if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
+        if let Some(stats) = CLIENT_SESSIONS.lock().primary_statistics() {
             stats.report_frame_encoded(Duration::from_nanos(timestamp_ns));
         }
     }
 
-    extern "C" fn report_fec_failure(percentage: i32) {
-        if let Some(stats) = &mut *STATISTICS_MANAGER.lock() {
-            stats.report_fec_failure(percentage as u32);
-        }
-    }
-
     LogError = Some(log_error);
     LogWarn = Some(log_warn);
     LogInfo = Some(log_info);
@@ -398,7 +476,6 @@ pub unsafe extern "C" fn HmdDriverFactory(
     ReportPresent = Some(report_present);
     ReportComposed = Some(report_composed);
     ReportEncoded = Some(report_encoded);
-    ReportFecFailure = Some(report_fec_failure);
 
     // cast to usize to allow the variables to cross thread boundaries
     let interface_name_usize = interface_name as usize;
@@ -1,67 +1,234 @@
+// Logs are written to files in the session logs folder, if enabled. Each server session writes to
+// its own files, named "<session start>_<part>.<extension>". A new part is started when the
+// current one becomes too big or too old, and the files of the oldest sessions are deleted.
+
 use crate::{FILESYSTEM_LAYOUT, SERVER_DATA_MANAGER};
-use alvr_common::log::{self, LevelFilter};
+use alvr_common::{
+    log::{self, LevelFilter},
+    once_cell::sync::Lazy,
+    parking_lot::Mutex,
+    prelude::*,
+};
 use alvr_events::{EventSeverity, EventType, LogEvent};
-use fern::Dispatch;
-use std::fs;
+use alvr_session::{LogFilesDesc, LogFormat};
+use fern::{Dispatch, Output};
+use serde::Serialize;
+use serde_json as json;
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
 use tokio::sync::broadcast::Sender;
 
-pub fn init_logging(log_sender: Sender<String>, events_sender: Sender<String>) {
-    let mut log_dispatch = Dispatch::new().format(move |out, message, record| {
-        let maybe_event = format!("{message}");
-        if maybe_event.contains("#{") {
-            let event_data = maybe_event.replace("#{", "{").replace("}#", "}");
-            events_sender.send(event_data).ok();
-        } else {
-            let severity = match record.level() {
-                log::Level::Error => EventSeverity::Error,
-                log::Level::Warn => EventSeverity::Warning,
-                log::Level::Info => EventSeverity::Info,
-                log::Level::Debug | log::Level::Trace => EventSeverity::Debug,
-            };
-
-            let event = EventType::Log(LogEvent {
-                timestamp: chrono::Local::now().format("%H:%M:%S.%f").to_string(),
-                severity,
-                content: message.to_string(),
-            });
-
-            events_sender
-                .send(serde_json::to_string(&event).unwrap())
-                .ok();
+// Start time of the server session, used to name its log files
+static LOG_SESSION: Lazy<String> =
+    Lazy::new(|| chrono::Local::now().format("%Y-%m-%d_%H-%M-%S").to_string());
+
+tokio::task_local! {
+    // Hostname of the client streamed by the current task. Logs of the tasks spawned by the
+    // stream are not tagged.
+    pub static CONNECTION_ID: String;
+}
+
+#[derive(Serialize)]
+struct JsonLogLine<'a> {
+    timestamp: String,
+    level: &'a str,
+    target: &'a str,
+    connection: Option<String>,
+    message: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogFileInfo {
+    pub name: String,
+    pub size: u64,
+    // seconds since the UNIX epoch
+    pub modified: u64,
+    // The file is being written by the current session
+    pub current: bool,
+}
+
+fn log_line(message: &str, level: log::Level) -> String {
+    format!(
+        "{} [{level}] {message}",
+        chrono::Local::now().format("%H:%M:%S.%f"),
+    )
+}
+
+// The session start is used as prefix, so sorting file names also sorts sessions
+fn file_session(file_name: &str) -> Option<&str> {
+    file_name.rsplit_once('_').map(|(session, _)| session)
+}
+
+struct LogFiles {
+    dir: PathBuf,
+    format: LogFormat,
+    max_file_size: u64,
+    max_file_age: Duration,
+    kept_sessions: usize,
+    part: u32,
+    file: Option<File>,
+    file_size: u64,
+    file_start: Instant,
+}
+
+impl LogFiles {
+    fn new(dir: &Path, config: &LogFilesDesc) -> Self {
+        Self {
+            dir: dir.to_owned(),
+            format: config.format,
+            max_file_size: config.max_file_size_mb * 1024 * 1024,
+            max_file_age: Duration::from_secs(config.max_file_age_hours * 60 * 60),
+            kept_sessions: config.kept_sessions as usize,
+            part: 0,
+            file: None,
+            file_size: 0,
+            file_start: Instant::now(),
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self.format {
+            LogFormat::Text => "txt",
+            LogFormat::JsonLines => "jsonl",
+        }
+    }
+
+    fn file_name(&self) -> String {
+        // Padded, so the parts are sorted by name
+        format!("{}_{:03}.{}", *LOG_SESSION, self.part, self.extension())
+    }
+
+    // Deletes the files of the sessions before the last kept_sessions ones
+    fn delete_old_sessions(&self) {
+        let mut file_names = fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        file_names.sort();
+
+        let mut sessions = file_names
+            .iter()
+            .filter_map(|name| file_session(name))
+            .filter(|session| *session != LOG_SESSION.as_str())
+            .collect::<Vec<_>>();
+        sessions.dedup();
+
+        let deleted_sessions_count = sessions.len().saturating_sub(self.kept_sessions);
+        for name in &file_names {
+            if file_session(name).map_or(false, |session| {
+                sessions[..deleted_sessions_count].contains(&session)
+            }) {
+                fs::remove_file(self.dir.join(name)).ok();
+            }
+        }
+    }
+
+    fn open_file(&mut self) -> StrResult {
+        fs::create_dir_all(&self.dir).map_err(err!())?;
+
+        self.file = Some(File::create(self.dir.join(self.file_name())).map_err(err!())?);
+        self.file_size = 0;
+        self.file_start = Instant::now();
+
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &log::Record) -> StrResult {
+        let line = match self.format {
+            LogFormat::Text => log_line(&record.args().to_string(), record.level()),
+            LogFormat::JsonLines => json::to_string(&JsonLogLine {
+                timestamp: chrono::Local::now().to_rfc3339(),
+                level: record.level().as_str(),
+                target: record.target(),
+                connection: CONNECTION_ID.try_with(|id| id.clone()).ok(),
+                message: record.args().to_string(),
+            })
+            .map_err(err!())?,
+        };
+
+        if self.file.is_some()
+            && (self.file_size >= self.max_file_size
+                || self.file_start.elapsed() >= self.max_file_age)
+        {
+            self.part += 1;
+            self.file = None;
+        }
+        if self.file.is_none() {
+            self.open_file()?;
+        }
+
+        if let Some(file) = &mut self.file {
+            writeln!(file, "{line}").map_err(err!())?;
+            self.file_size += line.len() as u64 + 1;
         }
-        let log_line = format!(
-            "{} [{}] {message}",
-            chrono::Local::now().format("%H:%M:%S.%f"),
-            record.level()
+
+        Ok(())
+    }
+}
+
+pub fn init_logging(log_sender: Sender<String>) {
+    let extra_settings = SERVER_DATA_MANAGER.read().settings().extra.clone();
+
+    let log_files = extra_settings.log_to_disk.then(|| {
+        let log_files = LogFiles::new(
+            &FILESYSTEM_LAYOUT.session_logs_dir(),
+            &extra_settings.log_files,
         );
-        log_sender.send(log_line.clone()).ok();
-        out.finish(format_args!("{}", log_line));
+        log_files.delete_old_sessions();
+
+        Mutex::new(log_files)
     });
 
+    let mut log_dispatch = Dispatch::new().chain(Output::call(move |record| {
+        let severity = match record.level() {
+            log::Level::Error => EventSeverity::Error,
+            log::Level::Warn => EventSeverity::Warning,
+            log::Level::Info => EventSeverity::Info,
+            log::Level::Debug | log::Level::Trace => EventSeverity::Debug,
+        };
+        alvr_events::send_event(EventType::Log(LogEvent {
+            timestamp: chrono::Local::now().format("%H:%M:%S.%f").to_string(),
+            severity,
+            content: record.args().to_string(),
+        }));
+
+        let log_line = log_line(&record.args().to_string(), record.level());
+        log_sender.send(log_line.clone()).ok();
+
+        if let Some(log_files) = &log_files {
+            // Errors cannot be logged from here
+            if let Err(e) = log_files.lock().write_record(record) {
+                eprintln!("Failed to write log file: {e}");
+            }
+        } else {
+            println!("{log_line}");
+        }
+    }));
+
     if cfg!(debug_assertions) {
         log_dispatch = log_dispatch.level(LevelFilter::Debug)
     } else {
         log_dispatch = log_dispatch.level(LevelFilter::Info);
     }
 
-    if SERVER_DATA_MANAGER.read().settings().extra.log_to_disk {
-        log_dispatch = log_dispatch.chain(
-            fs::OpenOptions::new()
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(FILESYSTEM_LAYOUT.session_log())
-                .unwrap(),
-        );
-    } else {
-        // this sink is required to make sure all log gets processed and forwarded to the websocket
-        log_dispatch = log_dispatch.chain(std::io::stdout());
-    }
-
     log_dispatch
         .chain(
             Dispatch::new()
                 .level(LevelFilter::Error)
+                .format(|out, message, record| {
+                    out.finish(format_args!(
+                        "{}",
+                        log_line(&message.to_string(), record.level())
+                    ))
+                })
                 .chain(fern::log_file(FILESYSTEM_LAYOUT.crash_log()).unwrap()),
         )
         .apply()
@@ -69,3 +236,202 @@ pub fn init_logging(log_sender: Sender<String>, events_sender: Sender<String>) {
 
     alvr_common::set_panic_hook();
 }
+
+// Files of the current and previous sessions, sorted from the newest
+pub fn log_files() -> Vec<LogFileInfo> {
+    let entries = match fs::read_dir(FILESYSTEM_LAYOUT.session_logs_dir()) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut files = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let metadata = entry.metadata().ok()?;
+            if !metadata.is_file() {
+                return None;
+            }
+
+            let name = entry.file_name().into_string().ok()?;
+            let current = file_session(&name) == Some(LOG_SESSION.as_str());
+
+            Some(LogFileInfo {
+                name,
+                size: metadata.len(),
+                modified: metadata
+                    .modified()
+                    .ok()?
+                    .duration_since(UNIX_EPOCH)
+                    .ok()?
+                    .as_secs(),
+                current,
+            })
+        })
+        .collect::<Vec<_>>();
+    files.sort_by(|a, b| b.name.cmp(&a.name));
+
+    files
+}
+
+// The name is looked up among the listed files, so it cannot be used to read other files
+pub fn read_log_file(name: &str) -> StrResult<String> {
+    if !log_files().iter().any(|file| file.name == name) {
+        return fmt_e!("Log file {name} not found");
+    }
+
+    fs::read_to_string(FILESYSTEM_LAYOUT.session_logs_dir().join(name)).map_err(err!())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    // Removes the logs folder when the test ends
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("alvr_logs_test_{name}_{}", process::id()));
+            fs::remove_dir_all(&dir).ok();
+            fs::create_dir_all(&dir).unwrap();
+
+            Self(dir)
+        }
+
+        fn file_names(&self) -> Vec<String> {
+            let mut file_names = fs::read_dir(&self.0)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+                .collect::<Vec<_>>();
+            file_names.sort();
+
+            file_names
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    fn log_files(dir: &TestDir, kept_sessions: u64) -> LogFiles {
+        LogFiles::new(
+            &dir.0,
+            &LogFilesDesc {
+                format: LogFormat::Text,
+                max_file_size_mb: 1,
+                max_file_age_hours: 1,
+                kept_sessions,
+            },
+        )
+    }
+
+    fn write_message(log_files: &mut LogFiles, message: &str) {
+        log_files
+            .write_record(
+                &log::Record::builder()
+                    .args(format_args!("{message}"))
+                    .level(log::Level::Info)
+                    .build(),
+            )
+            .unwrap();
+    }
+
+    // Two previous sessions, with two parts and with two formats, and the current session
+    fn write_sessions(dir: &TestDir) -> String {
+        let current_file = format!("{}_000.txt", *LOG_SESSION);
+        for name in [
+            "2020-01-01_10-00-00_000.txt",
+            "2020-01-01_10-00-00_001.txt",
+            "2020-01-02_10-00-00_000.jsonl",
+            &current_file,
+        ] {
+            fs::write(dir.0.join(name), "").unwrap();
+        }
+
+        current_file
+    }
+
+    #[test]
+    fn session_is_the_name_before_the_part() {
+        assert_eq!(
+            file_session("2026-10-17_02-22-19_000.txt"),
+            Some("2026-10-17_02-22-19")
+        );
+        assert_eq!(
+            file_session("2026-10-17_02-22-19_012.jsonl"),
+            Some("2026-10-17_02-22-19")
+        );
+        assert_eq!(file_session("notes.txt"), None);
+    }
+
+    #[test]
+    fn oldest_sessions_are_deleted() {
+        let dir = TestDir::new("oldest");
+        let current_file = write_sessions(&dir);
+
+        log_files(&dir, 1).delete_old_sessions();
+
+        assert_eq!(
+            dir.file_names(),
+            vec!["2020-01-02_10-00-00_000.jsonl".to_owned(), current_file]
+        );
+    }
+
+    #[test]
+    fn only_the_current_session_is_kept() {
+        let dir = TestDir::new("current");
+        let current_file = write_sessions(&dir);
+
+        log_files(&dir, 0).delete_old_sessions();
+
+        assert_eq!(dir.file_names(), vec![current_file]);
+    }
+
+    #[test]
+    fn sessions_within_the_limit_are_kept() {
+        let dir = TestDir::new("limit");
+        write_sessions(&dir);
+
+        log_files(&dir, 2).delete_old_sessions();
+
+        assert_eq!(dir.file_names().len(), 4);
+    }
+
+    #[test]
+    fn new_part_is_started_when_the_file_is_too_big() {
+        let dir = TestDir::new("size");
+        let mut log_files = log_files(&dir, 1);
+        log_files.max_file_size = 10;
+
+        write_message(&mut log_files, "first");
+        write_message(&mut log_files, "second");
+        write_message(&mut log_files, "third");
+
+        let part_names = (0..3)
+            .map(|part| format!("{}_{part:03}.txt", *LOG_SESSION))
+            .collect::<Vec<_>>();
+        assert_eq!(dir.file_names(), part_names);
+        assert!(fs::read_to_string(dir.0.join(&part_names[1]))
+            .unwrap()
+            .ends_with("[INFO] second\n"));
+    }
+
+    #[test]
+    fn part_is_kept_until_it_is_full() {
+        let dir = TestDir::new("full");
+        let mut log_files = log_files(&dir, 1);
+
+        write_message(&mut log_files, "first");
+        write_message(&mut log_files, "second");
+
+        let file_name = format!("{}_000.txt", *LOG_SESSION);
+        assert_eq!(dir.file_names(), vec![file_name.clone()]);
+        assert_eq!(
+            log_files.file_size,
+            fs::metadata(dir.0.join(file_name)).unwrap().len()
+        );
+    }
+}
@@ -0,0 +1,447 @@
+// Streaming statistics exported in the Prometheus text format on /metrics. Unlike the statistics
+// events, histograms and counters accumulate for the whole lifetime of the server.
+
+use alvr_common::{HEAD_ID, LEFT_HAND_ID, RIGHT_HAND_ID};
+use std::{
+    collections::HashMap,
+    fmt::{Display, Write},
+    time::Duration,
+};
+
+// seconds
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.015, 0.02, 0.03, 0.04, 0.05, 0.075, 0.1, 0.15, 0.2, 0.3, 0.5, 1.,
+];
+
+struct Histogram {
+    bucket_counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            bucket_counts: vec![0; LATENCY_BUCKETS.len()],
+            sum: 0.,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: Duration) {
+        let value = value.as_secs_f64();
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&mut self.bucket_counts) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn encode(&self, out: &mut String, name: &str, help: &str) {
+        writeln!(out, "# HELP {name} {help}").ok();
+        writeln!(out, "# TYPE {name} histogram").ok();
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&self.bucket_counts) {
+            writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {count}").ok();
+        }
+        writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", self.count).ok();
+        writeln!(out, "{name}_sum {}", self.sum).ok();
+        writeln!(out, "{name}_count {}", self.count).ok();
+    }
+}
+
+fn encode_single(out: &mut String, name: &str, type_: &str, help: &str, value: impl Display) {
+    writeln!(out, "# HELP {name} {help}").ok();
+    writeln!(out, "# TYPE {name} {type_}").ok();
+    writeln!(out, "{name} {value}").ok();
+}
+
+// Prometheus does not accept infinite values. None until the first frame interval is measured
+fn frame_rate(frame_interval: Duration) -> Option<f32> {
+    (!frame_interval.is_zero()).then(|| 1. / frame_interval.as_secs_f32())
+}
+
+fn device_label(device_id: u64) -> String {
+    if device_id == *HEAD_ID {
+        "head".into()
+    } else if device_id == *LEFT_HAND_ID {
+        "left_hand".into()
+    } else if device_id == *RIGHT_HAND_ID {
+        "right_hand".into()
+    } else {
+        format!("{device_id:#x}")
+    }
+}
+
+pub struct MetricsManager {
+    total_latency: Histogram,
+    network_latency: Histogram,
+    encode_latency: Histogram,
+    decode_latency: Histogram,
+    client_fps: f32,
+    server_fps: f32,
+    video_packets_total: u64,
+    video_bytes_total: u64,
+    video_mbits_per_sec: f32,
+    video_packets_dropped: u64,
+    video_packets_reordered: u64,
+    video_packets_late: u64,
+    video_packets_duplicated: u64,
+    fec_errors_total: u64,
+    fec_percentage: u32,
+    battery_gauges: HashMap<u64, f32>,
+    network_rtt: Option<Duration>,
+    network_bandwidth_bps: Option<f64>,
+    network_loss_rate: f32,
+}
+
+impl Default for MetricsManager {
+    fn default() -> Self {
+        Self {
+            total_latency: Histogram::new(),
+            network_latency: Histogram::new(),
+            encode_latency: Histogram::new(),
+            decode_latency: Histogram::new(),
+            client_fps: 0.,
+            server_fps: 0.,
+            video_packets_total: 0,
+            video_bytes_total: 0,
+            video_mbits_per_sec: 0.,
+            video_packets_dropped: 0,
+            video_packets_reordered: 0,
+            video_packets_late: 0,
+            video_packets_duplicated: 0,
+            fec_errors_total: 0,
+            fec_percentage: 0,
+            battery_gauges: HashMap::new(),
+            network_rtt: None,
+            network_bandwidth_bps: None,
+            network_loss_rate: 0.,
+        }
+    }
+}
+
+impl MetricsManager {
+    pub fn report_frame(
+        &mut self,
+        total_latency: Duration,
+        network_latency: Duration,
+        encode_latency: Duration,
+        decode_latency: Duration,
+        client_frame_interval: Duration,
+        game_frame_interval: Duration,
+    ) {
+        self.total_latency.observe(total_latency);
+        self.network_latency.observe(network_latency);
+        self.encode_latency.observe(encode_latency);
+        self.decode_latency.observe(decode_latency);
+        // The previous values are kept if an interval is zero
+        if let Some(client_fps) = frame_rate(client_frame_interval) {
+            self.client_fps = client_fps;
+        }
+        if let Some(server_fps) = frame_rate(game_frame_interval) {
+            self.server_fps = server_fps;
+        }
+    }
+
+    pub fn report_video_packet(&mut self, bytes_count: usize) {
+        self.video_packets_total += 1;
+        self.video_bytes_total += bytes_count as u64;
+    }
+
+    pub fn report_video_bitrate(&mut self, mbits_per_sec: f32) {
+        self.video_mbits_per_sec = mbits_per_sec;
+    }
+
+    pub fn report_video_packets_dropped(&mut self, packets_dropped: usize) {
+        self.video_packets_dropped = packets_dropped as u64;
+    }
+
+    pub fn report_video_reorder(&mut self, reordered: u64, late: u64, duplicated: u64) {
+        self.video_packets_reordered = reordered;
+        self.video_packets_late = late;
+        self.video_packets_duplicated = duplicated;
+    }
+
+    pub fn report_fec_failure(&mut self, fec_percentage: u32) {
+        self.fec_errors_total += 1;
+        self.fec_percentage = fec_percentage;
+    }
+
+    pub fn report_battery(&mut self, device_id: u64, gauge_value: f32) {
+        self.battery_gauges.insert(device_id, gauge_value);
+    }
+
+    pub fn report_network_estimate(
+        &mut self,
+        rtt: Option<Duration>,
+        bandwidth_bps: Option<f64>,
+        loss_rate: f32,
+    ) {
+        self.network_rtt = rtt;
+        self.network_bandwidth_bps = bandwidth_bps;
+        self.network_loss_rate = loss_rate;
+    }
+
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+
+        self.total_latency.encode(
+            &mut out,
+            "alvr_total_latency_seconds",
+            "Motion-to-photon latency of the pipeline",
+        );
+        self.network_latency.encode(
+            &mut out,
+            "alvr_network_latency_seconds",
+            "Estimated network latency",
+        );
+        self.encode_latency
+            .encode(&mut out, "alvr_encode_latency_seconds", "Encoder latency");
+        self.decode_latency
+            .encode(&mut out, "alvr_decode_latency_seconds", "Decoder latency");
+
+        encode_single(
+            &mut out,
+            "alvr_client_fps",
+            "gauge",
+            "Frame rate of the client",
+            self.client_fps,
+        );
+        encode_single(
+            &mut out,
+            "alvr_server_fps",
+            "gauge",
+            "Frame rate of the game",
+            self.server_fps,
+        );
+        encode_single(
+            &mut out,
+            "alvr_video_packets_total",
+            "counter",
+            "Video packets sent",
+            self.video_packets_total,
+        );
+        encode_single(
+            &mut out,
+            "alvr_video_bytes_total",
+            "counter",
+            "Video bytes sent",
+            self.video_bytes_total,
+        );
+        encode_single(
+            &mut out,
+            "alvr_video_bitrate_mbits_per_second",
+            "gauge",
+            "Video bitrate",
+            self.video_mbits_per_sec,
+        );
+        encode_single(
+            &mut out,
+            "alvr_video_packets_dropped_total",
+            "counter",
+            "Video packets dropped because the send queue was full",
+            self.video_packets_dropped,
+        );
+        encode_single(
+            &mut out,
+            "alvr_video_packets_reordered_total",
+            "counter",
+            "Video packets that arrived out of order at the client",
+            self.video_packets_reordered,
+        );
+        encode_single(
+            &mut out,
+            "alvr_video_packets_late_total",
+            "counter",
+            "Video packets that arrived at the client after being skipped",
+            self.video_packets_late,
+        );
+        encode_single(
+            &mut out,
+            "alvr_video_packets_duplicated_total",
+            "counter",
+            "Video packets that arrived at the client more than once",
+            self.video_packets_duplicated,
+        );
+        encode_single(
+            &mut out,
+            "alvr_fec_errors_total",
+            "counter",
+            "Frames that could not be recovered with FEC",
+            self.fec_errors_total,
+        );
+        encode_single(
+            &mut out,
+            "alvr_fec_percentage",
+            "gauge",
+            "FEC redundancy percentage",
+            self.fec_percentage,
+        );
+
+        // Not exported until measured
+        if let Some(rtt) = self.network_rtt {
+            encode_single(
+                &mut out,
+                "alvr_network_rtt_seconds",
+                "gauge",
+                "Smoothed round-trip time of the control connection",
+                rtt.as_secs_f64(),
+            );
+        }
+        if let Some(bandwidth_bps) = self.network_bandwidth_bps {
+            encode_single(
+                &mut out,
+                "alvr_network_bandwidth_bits_per_second",
+                "gauge",
+                "Bandwidth measured with probe trains",
+                bandwidth_bps,
+            );
+        }
+        encode_single(
+            &mut out,
+            "alvr_network_loss_ratio",
+            "gauge",
+            "Smoothed loss rate of the probe packets",
+            self.network_loss_rate,
+        );
+
+        writeln!(
+            out,
+            "# HELP alvr_battery_ratio Battery charge of each device"
+        )
+        .ok();
+        writeln!(out, "# TYPE alvr_battery_ratio gauge").ok();
+        for (device_id, gauge_value) in &self.battery_gauges {
+            writeln!(
+                out,
+                "alvr_battery_ratio{{device=\"{}\"}} {gauge_value}",
+                device_label(*device_id)
+            )
+            .ok();
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_metric_name(name: &str) -> bool {
+        !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+    }
+
+    // Checks the lines of the Prometheus text format. Returns the value of each sample by name,
+    // including the labels
+    fn parse(text: &str) -> HashMap<String, f64> {
+        let mut types = HashMap::new();
+        let mut samples = HashMap::new();
+        for line in text.lines() {
+            if let Some(comment) = line.strip_prefix("# ") {
+                let mut parts = comment.splitn(3, ' ');
+                let keyword = parts.next().unwrap();
+                let name = parts.next().unwrap();
+                assert!(is_metric_name(name), "{line}");
+                if keyword == "TYPE" {
+                    let type_ = parts.next().unwrap();
+                    assert!(["counter", "gauge", "histogram"].contains(&type_), "{line}");
+                    types.insert(name.to_owned(), type_.to_owned());
+                } else {
+                    assert_eq!(keyword, "HELP", "{line}");
+                }
+
+                continue;
+            }
+
+            let (series, value) = line.rsplit_once(' ').unwrap();
+            let name = series.split('{').next().unwrap();
+            assert!(is_metric_name(name), "{line}");
+            if let Some(labels) = series
+                .strip_prefix(name)
+                .filter(|labels| !labels.is_empty())
+            {
+                assert!(labels.starts_with('{') && labels.ends_with('}'), "{line}");
+            }
+            let family = ["_bucket", "_sum", "_count"]
+                .iter()
+                .find_map(|suffix| name.strip_suffix(suffix))
+                .filter(|family| types.get(*family).map(String::as_str) == Some("histogram"))
+                .unwrap_or(name);
+            assert!(types.contains_key(family), "Sample before TYPE: {line}");
+
+            let value = value.parse::<f64>().unwrap();
+            assert!(value.is_finite(), "{line}");
+            samples.insert(series.to_owned(), value);
+        }
+
+        samples
+    }
+
+    #[test]
+    fn encoded_metrics_are_valid() {
+        let mut metrics = MetricsManager::default();
+        metrics.report_frame(
+            Duration::from_millis(60),
+            Duration::from_millis(10),
+            Duration::from_millis(5),
+            Duration::from_millis(8),
+            Duration::from_secs_f32(1. / 72.),
+            Duration::from_secs_f32(1. / 90.),
+        );
+        metrics.report_video_packet(1000);
+        metrics.report_video_packets_dropped(3);
+        metrics.report_video_reorder(4, 2, 1);
+        metrics.report_battery(*HEAD_ID, 0.5);
+        metrics.report_network_estimate(Some(Duration::from_millis(4)), Some(1e8), 0.01);
+
+        let samples = parse(&metrics.encode());
+        assert_eq!(samples["alvr_total_latency_seconds_count"], 1.);
+        assert_eq!(
+            samples["alvr_total_latency_seconds_bucket{le=\"0.075\"}"],
+            1.
+        );
+        assert_eq!(
+            samples["alvr_total_latency_seconds_bucket{le=\"0.05\"}"],
+            0.
+        );
+        assert!((samples["alvr_client_fps"] - 72.).abs() < 0.01);
+        assert_eq!(samples["alvr_video_bytes_total"], 1000.);
+        assert_eq!(samples["alvr_video_packets_dropped_total"], 3.);
+        assert_eq!(samples["alvr_video_packets_late_total"], 2.);
+        assert_eq!(samples["alvr_battery_ratio{device=\"head\"}"], 0.5);
+        assert_eq!(samples["alvr_network_bandwidth_bits_per_second"], 1e8);
+    }
+
+    #[test]
+    fn zero_frame_intervals_are_skipped() {
+        let mut metrics = MetricsManager::default();
+        metrics.report_frame(
+            Duration::ZERO,
+            Duration::ZERO,
+            Duration::ZERO,
+            Duration::ZERO,
+            Duration::from_secs_f32(1. / 72.),
+            Duration::ZERO,
+        );
+        metrics.report_frame(
+            Duration::ZERO,
+            Duration::ZERO,
+            Duration::ZERO,
+            Duration::ZERO,
+            Duration::ZERO,
+            Duration::ZERO,
+        );
+
+        let samples = parse(&metrics.encode());
+        assert!((samples["alvr_client_fps"] - 72.).abs() < 0.01);
+        assert_eq!(samples["alvr_server_fps"], 0.);
+        assert!(!samples.contains_key("alvr_network_rtt_seconds"));
+    }
+}
@@ -1,6 +1,10 @@
+use crate::METRICS;
 use alvr_common::{HEAD_ID, LEFT_HAND_ID, RIGHT_HAND_ID};
-use alvr_events::{EventType, GraphStatistics, Statistics};
-use alvr_sockets::ClientStatistics;
+use alvr_events::{
+    EventType, GraphStatistics, LatencyPercentiles, LatencyStatistics, NetworkStatistics,
+    Statistics,
+};
+use alvr_sockets::{ClientStatistics, NetworkEstimate};
 use std::{
     collections::{HashMap, VecDeque},
     time::{Duration, Instant},
@@ -31,8 +35,56 @@ impl Default for HistoryFrame {
     }
 }
 
+// Latency of each pipeline stage for a single frame
+#[derive(Clone, Copy)]
+struct StageLatencies {
+    total_pipeline: Duration,
+    game_time: Duration,
+    server_compositor: Duration,
+    encoder: Duration,
+    network: Duration,
+    decoder: Duration,
+    client_compositor: Duration,
+    vsync_queue: Duration,
+}
+
+fn latency_percentiles(latencies: impl Iterator<Item = Duration>) -> LatencyPercentiles {
+    let latencies = latencies.collect::<Vec<_>>();
+    if latencies.is_empty() {
+        return LatencyPercentiles::default();
+    }
+
+    let jitter_ms = if latencies.len() > 1 {
+        let sum_s = latencies
+            .windows(2)
+            .map(|pair| (pair[1].as_secs_f32() - pair[0].as_secs_f32()).abs())
+            .sum::<f32>();
+        sum_s / (latencies.len() - 1) as f32 * 1000.
+    } else {
+        0.
+    };
+
+    let mut sorted = latencies;
+    sorted.sort();
+
+    // nearest-rank method
+    let percentile_ms = |percentile: f32| {
+        let rank = (percentile / 100. * sorted.len() as f32).ceil() as usize;
+        sorted[rank.clamp(1, sorted.len()) - 1].as_secs_f32() * 1000.
+    };
+
+    LatencyPercentiles {
+        p50_ms: percentile_ms(50.),
+        p95_ms: percentile_ms(95.),
+        p99_ms: percentile_ms(99.),
+        jitter_ms,
+    }
+}
+
 pub struct StatisticsManager {
     history_buffer: VecDeque<HistoryFrame>,
+    // oldest first
+    latency_history: VecDeque<StageLatencies>,
     max_history_size: usize,
     last_full_report_instant: Instant,
     video_packets_total: usize,
@@ -42,6 +94,10 @@ pub struct StatisticsManager {
     fec_errors_total: usize,
     fec_failures_partial_sum: usize,
     fec_percentage: u32,
+    video_packets_dropped: usize,
+    video_packets_reordered: usize,
+    video_packets_late: usize,
+    video_packets_duplicated: usize,
     battery_gauges: HashMap<u64, f32>,
 }
 
@@ -50,6 +106,7 @@ impl StatisticsManager {
     pub fn new(history_size: usize) -> Self {
         Self {
             history_buffer: VecDeque::new(),
+            latency_history: VecDeque::new(),
             max_history_size: history_size,
             last_full_report_instant: Instant::now(),
             video_packets_total: 0,
@@ -59,6 +116,10 @@ impl StatisticsManager {
             fec_errors_total: 0,
             fec_failures_partial_sum: 0,
             fec_percentage: 0,
+            video_packets_dropped: 0,
+            video_packets_reordered: 0,
+            video_packets_late: 0,
+            video_packets_duplicated: 0,
             battery_gauges: HashMap::new(),
         }
     }
@@ -116,16 +177,69 @@ impl StatisticsManager {
         self.video_packets_partial_sum += 1;
         self.video_bytes_total += bytes_count;
         self.video_bytes_partial_sum += bytes_count;
+
+        METRICS.lock().report_video_packet(bytes_count);
     }
 
-    pub fn report_fec_failure(&mut self, fec_percentage: u32) {
+    pub fn report_fec_percentage(&mut self, fec_percentage: u32) {
         self.fec_percentage = fec_percentage;
+    }
+
+    // Total of the video packets dropped because the send queue was full
+    pub fn report_video_packets_dropped(&mut self, packets_dropped: usize) {
+        self.video_packets_dropped = packets_dropped;
+
+        METRICS.lock().report_video_packets_dropped(packets_dropped);
+    }
+
+    pub fn report_fec_failure(&mut self) {
         self.fec_errors_total += 1;
         self.fec_failures_partial_sum += 1;
+
+        METRICS.lock().report_fec_failure(self.fec_percentage);
     }
 
     pub fn report_battery(&mut self, device_id: u64, gauge_value: f32) {
         *self.battery_gauges.entry(device_id).or_default() = gauge_value;
+
+        METRICS.lock().report_battery(device_id, gauge_value);
+    }
+
+    // Called for each probe train
+    pub fn report_network_estimate(&mut self, estimate: NetworkEstimate) {
+        METRICS.lock().report_network_estimate(
+            estimate.rtt,
+            estimate.bandwidth_bps,
+            estimate.loss_rate,
+        );
+
+        alvr_events::send_event(EventType::NetworkStatistics(NetworkStatistics {
+            rtt_ms: estimate.rtt.map(|rtt| rtt.as_secs_f32() * 1000.),
+            rtt_variation_ms: estimate
+                .rtt
+                .map(|_| estimate.rtt_variation.as_secs_f32() * 1000.),
+            bandwidth_mbits_per_sec: estimate.bandwidth_bps.map(|bps| (bps / 1e6) as _),
+            loss_percentage: estimate.loss_rate * 100.,
+        }));
+    }
+
+    // Percentiles and jitter over the last `history_size` frames
+    pub fn latency_statistics(&self) -> LatencyStatistics {
+        let stage = |select: fn(&StageLatencies) -> Duration| {
+            latency_percentiles(self.latency_history.iter().map(select))
+        };
+
+        LatencyStatistics {
+            frames_count: self.latency_history.len(),
+            total_pipeline: stage(|frame| frame.total_pipeline),
+            game_time: stage(|frame| frame.game_time),
+            server_compositor: stage(|frame| frame.server_compositor),
+            encoder: stage(|frame| frame.encoder),
+            network: stage(|frame| frame.network),
+            decoder: stage(|frame| frame.decoder),
+            client_compositor: stage(|frame| frame.client_compositor),
+            vsync_queue: stage(|frame| frame.vsync_queue),
+        }
     }
 
     // Called every frame. Some statistics are reported once every frame
@@ -135,6 +249,15 @@ impl StatisticsManager {
         client_stats: ClientStatistics,
         game_frame_interval: Duration,
     ) -> Duration {
+        self.video_packets_reordered = client_stats.video_packets_reordered as _;
+        self.video_packets_late = client_stats.video_packets_late as _;
+        self.video_packets_duplicated = client_stats.video_packets_duplicated as _;
+        METRICS.lock().report_video_reorder(
+            client_stats.video_packets_reordered,
+            client_stats.video_packets_late,
+            client_stats.video_packets_duplicated,
+        );
+
         if let Some(frame) = self
             .history_buffer
             .iter_mut()
@@ -169,18 +292,47 @@ impl StatisticsManager {
                     + client_stats.vsync_queue,
             );
 
+            self.latency_history.push_back(StageLatencies {
+                total_pipeline: client_stats.total_pipeline_latency,
+                game_time: game_time_latency,
+                server_compositor: server_compositor_latency,
+                encoder: encoder_latency,
+                network: network_latency,
+                decoder: client_stats.video_decode,
+                client_compositor: client_stats.rendering,
+                vsync_queue: client_stats.vsync_queue,
+            });
+            if self.latency_history.len() > self.max_history_size {
+                self.latency_history.pop_front();
+            }
+
+            METRICS.lock().report_frame(
+                client_stats.total_pipeline_latency,
+                network_latency,
+                encoder_latency,
+                client_stats.video_decode,
+                client_stats.frame_interval,
+                game_frame_interval,
+            );
+
             if self.last_full_report_instant + FULL_REPORT_INTERVAL < Instant::now() {
                 self.last_full_report_instant += FULL_REPORT_INTERVAL;
 
                 let interval_secs = FULL_REPORT_INTERVAL.as_secs_f32();
+                let video_mbits_per_sec =
+                    self.video_bytes_partial_sum as f32 / interval_secs * 8. / 1e6;
+                METRICS.lock().report_video_bitrate(video_mbits_per_sec);
 
                 alvr_events::send_event(EventType::Statistics(Statistics {
                     video_packets_total: self.video_packets_total,
                     video_packets_per_sec: (self.video_packets_partial_sum as f32 / interval_secs)
                         as _,
                     video_mbytes_total: (self.video_bytes_total as f32 / 1e6) as usize,
-                    video_mbits_per_sec: self.video_bytes_partial_sum as f32 / interval_secs * 8.
-                        / 1e6,
+                    video_mbits_per_sec,
+                    video_packets_dropped: self.video_packets_dropped,
+                    video_packets_reordered: self.video_packets_reordered,
+                    video_packets_late: self.video_packets_late,
+                    video_packets_duplicated: self.video_packets_duplicated,
                     total_latency_ms: client_stats.total_pipeline_latency.as_secs_f32() * 1000.,
                     network_latency_ms: network_latency.as_secs_f32() * 1000.,
                     encode_latency_ms: encoder_latency.as_secs_f32() * 1000.,
@@ -210,6 +362,8 @@ impl StatisticsManager {
                         * 100.) as _,
                 }));
 
+                alvr_events::send_event(EventType::LatencyStatistics(self.latency_statistics()));
+
                 self.video_packets_partial_sum = 0;
                 self.video_bytes_partial_sum = 0;
                 self.fec_failures_partial_sum = 0;
@@ -236,3 +390,60 @@ impl StatisticsManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_ms(value_ms: f32, expected_ms: f32) {
+        assert!(
+            (value_ms - expected_ms).abs() < 1e-3,
+            "{value_ms} != {expected_ms}"
+        );
+    }
+
+    fn percentiles_of(latencies_ms: &[u64]) -> LatencyPercentiles {
+        latency_percentiles(latencies_ms.iter().map(|ms| Duration::from_millis(*ms)))
+    }
+
+    #[test]
+    fn empty_history() {
+        let percentiles = percentiles_of(&[]);
+
+        assert_ms(percentiles.p50_ms, 0.);
+        assert_ms(percentiles.p95_ms, 0.);
+        assert_ms(percentiles.p99_ms, 0.);
+        assert_ms(percentiles.jitter_ms, 0.);
+    }
+
+    #[test]
+    fn single_sample() {
+        let percentiles = percentiles_of(&[20]);
+
+        assert_ms(percentiles.p50_ms, 20.);
+        assert_ms(percentiles.p95_ms, 20.);
+        assert_ms(percentiles.p99_ms, 20.);
+        assert_ms(percentiles.jitter_ms, 0.);
+    }
+
+    #[test]
+    fn known_percentiles() {
+        // 1ms to 100ms, in reverse order to check the sorting
+        let latencies_ms = (1..=100).rev().collect::<Vec<_>>();
+        let percentiles = percentiles_of(&latencies_ms);
+
+        assert_ms(percentiles.p50_ms, 50.);
+        assert_ms(percentiles.p95_ms, 95.);
+        assert_ms(percentiles.p99_ms, 99.);
+        assert_ms(percentiles.jitter_ms, 1.);
+
+        // With the nearest-rank method the high percentiles of a short history are its maximum
+        let percentiles = percentiles_of(&[10, 30, 20, 40]);
+
+        assert_ms(percentiles.p50_ms, 20.);
+        assert_ms(percentiles.p95_ms, 40.);
+        assert_ms(percentiles.p99_ms, 40.);
+        // (20 + 10 + 20) / 3
+        assert_ms(percentiles.jitter_ms, 50. / 3.);
+    }
+}
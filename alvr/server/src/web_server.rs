@@ -1,73 +1,240 @@
 use crate::{
-    CLIENTS_UPDATED_NOTIFIER, DISCONNECT_CLIENT_NOTIFIER, FILESYSTEM_LAYOUT, SERVER_DATA_MANAGER,
+    logging_backend, CLIENTS_UPDATED_NOTIFIER, CLIENT_SESSIONS, DISCONNECT_CLIENT_NOTIFIER,
+    FILESYSTEM_LAYOUT, METRICS, SERVER_DATA_MANAGER,
 };
-use alvr_common::{prelude::*, ALVR_VERSION};
+use alvr_common::{once_cell::sync::Lazy, prelude::*, ALVR_VERSION};
 use alvr_events::EventType;
+use alvr_session::DiagnosticSeverity;
 use alvr_sockets::ClientListAction;
 use bytes::Buf;
-use futures::SinkExt;
+use futures::{future::BoxFuture, FutureExt, SinkExt};
 use headers::HeaderMapExt;
 use hyper::{
-    header::{self, HeaderValue, ACCESS_CONTROL_ALLOW_ORIGIN, CACHE_CONTROL, CONTENT_TYPE},
-    service, Body, Request, Response, StatusCode,
+    header::{
+        HeaderValue, ACCESS_CONTROL_ALLOW_ORIGIN, ALLOW, CACHE_CONTROL, CONTENT_TYPE, ETAG, HOST,
+        ORIGIN, SET_COOKIE,
+    },
+    http::uri::Authority,
+    server::conn::AddrStream,
+    service, Body, Method, Request, Response, StatusCode,
 };
-use serde::{de::DeserializeOwned, Serialize};
+use rand::Rng;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json as json;
-use std::{env::consts::OS, fs, io::Write, net::SocketAddr, path::PathBuf};
+use std::{
+    env::consts::OS,
+    fs,
+    future::Future,
+    io::Write,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+};
 use tokio::sync::broadcast::{self, error::RecvError};
 use tokio_tungstenite::{tungstenite::protocol, WebSocketStream};
 use tokio_util::codec::{BytesCodec, FramedRead};
 
 pub const WS_BROADCAST_CAPACITY: usize = 256;
 
-fn reply(code: StatusCode) -> StrResult<Response<Body>> {
-    Response::builder()
+const TOKEN_COOKIE: &str = "alvr_token";
+
+// Routes that can be used without the token
+const PUBLIC_PATHS: &[&str] = &["/api/auth/login", "/api/openapi.json"];
+
+const LOGIN_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+    <title>ALVR dashboard</title>
+    <meta charset="utf-8">
+</head>
+<body>
+    <form id="login">
+        <p>Enter the token saved in web_server_token.txt, in the ALVR configuration folder</p>
+        <input id="token" type="password" autofocus>
+        <button type="submit">Log in</button>
+        <p id="error"></p>
+    </form>
+    <script>
+        document.getElementById("login").addEventListener("submit", async (event) => {
+            event.preventDefault();
+            const response = await fetch("/api/auth/login", {
+                method: "POST",
+                headers: { "Content-Type": "application/json" },
+                body: JSON.stringify(document.getElementById("token").value),
+            });
+            if (response.ok) {
+                location.reload();
+            } else {
+                document.getElementById("error").textContent = (await response.json()).error;
+            }
+        });
+    </script>
+</body>
+</html>
+"#;
+
+// Token required by the API routes. It is created on first launch and can be replaced by editing
+// the file
+static AUTH_TOKEN: Lazy<String> = Lazy::new(|| {
+    let token_path = FILESYSTEM_LAYOUT.web_server_token();
+
+    if let Ok(token) = fs::read_to_string(&token_path) {
+        let token = token.trim();
+        if !token.is_empty() {
+            return token.to_owned();
+        }
+    }
+
+    let token = rand::thread_rng()
+        .gen::<[u8; 16]>()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect::<String>();
+    if let Err(e) = fs::write(&token_path, &token) {
+        warn!("Failed to save web server token: {e}");
+    }
+
+    token
+});
+
+// The comparison takes the same time wherever the tokens differ, so that the token cannot be
+// guessed one character at a time
+fn is_auth_token(token: &str) -> bool {
+    let auth_token = AUTH_TOKEN.as_bytes();
+
+    token.len() == auth_token.len()
+        && token
+            .bytes()
+            .zip(auth_token)
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+// Errors are returned to the client with a JSON body: {"error": "<message>", "details": ...}
+struct ApiError {
+    status: StatusCode,
+    message: String,
+    // Structured information about the error, like the settings diagnostics
+    details: Option<json::Value>,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    fn with_details(mut self, details: json::Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+}
+
+// Unexpected errors, usually coming from StrResult
+impl From<String> for ApiError {
+    fn from(message: String) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+}
+
+type ApiResult = Result<Response<Body>, ApiError>;
+
+fn reply(code: StatusCode) -> ApiResult {
+    Ok(Response::builder()
         .status(code)
         .body(Body::empty())
-        .map_err(err!())
+        .map_err(err!())?)
 }
 
-fn reply_json<T: Serialize>(obj: &T) -> StrResult<Response<Body>> {
-    Response::builder()
-        .header(header::CONTENT_TYPE, "application/json")
+fn reply_json<T: Serialize>(obj: &T) -> ApiResult {
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, "application/json")
         .body(json::to_string(obj).map_err(err!())?.into())
-        .map_err(err!())
+        .map_err(err!())?)
+}
+
+fn reply_text(content_type: &'static str, text: String) -> ApiResult {
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, content_type)
+        .body(text.into())
+        .map_err(err!())?)
+}
+
+fn with_token_cookie(mut response: Response<Body>) -> ApiResult {
+    // SameSite prevents other websites from using the cookie of the dashboard
+    response.headers_mut().insert(
+        SET_COOKIE,
+        HeaderValue::from_str(&format!(
+            "{TOKEN_COOKIE}={}; Path=/; HttpOnly; SameSite=Strict",
+            *AUTH_TOKEN
+        ))
+        .map_err(err!())?,
+    );
+
+    Ok(response)
+}
+
+fn error_response(error: &ApiError) -> Response<Body> {
+    let mut body = json::json!({ "error": error.message });
+    if let Some(details) = &error.details {
+        body["details"] = details.clone();
+    }
+
+    let mut response = Response::new(Body::from(body.to_string()));
+    *response.status_mut() = error.status;
+    response
+        .headers_mut()
+        .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+
+    response
 }
 
 async fn from_request_body<T: DeserializeOwned>(request: Request<Body>) -> StrResult<T> {
-    json::from_reader(
-        hyper::body::aggregate(request)
-            .await
-            .map_err(err!())?
-            .reader(),
-    )
-    .map_err(err!())
+    let body = hyper::body::aggregate(request).await.map_err(err!())?;
+
+    // An empty body is treated as null, so it can be used with Option and ()
+    if body.has_remaining() {
+        json::from_reader(body.reader()).map_err(err!())
+    } else {
+        json::from_value(json::Value::Null).map_err(err!())
+    }
 }
 
-async fn text_websocket(
+// The replayed messages are sent first, then the ones received from the channel
+async fn text_websocket<T: Clone + Send + 'static>(
     request: Request<Body>,
-    sender: broadcast::Sender<String>,
-) -> StrResult<Response<Body>> {
+    replay: Vec<T>,
+    mut receiver: broadcast::Receiver<T>,
+    to_text: fn(&T) -> String,
+) -> ApiResult {
     if let Some(key) = request.headers().typed_get::<headers::SecWebsocketKey>() {
         tokio::spawn(async move {
             match hyper::upgrade::on(request).await {
                 Ok(upgraded) => {
-                    let mut log_receiver = sender.subscribe();
-
                     let mut ws =
                         WebSocketStream::from_raw_socket(upgraded, protocol::Role::Server, None)
                             .await;
 
+                    for message in replay {
+                        if let Err(e) = ws.send(protocol::Message::text(to_text(&message))).await {
+                            info!("Failed to send message with websocket: {e}");
+                            return;
+                        }
+                    }
+
                     loop {
-                        match log_receiver.recv().await {
-                            Ok(line) => {
-                                if let Err(e) = ws.send(protocol::Message::text(line)).await {
-                                    info!("Failed to send log with websocket: {e}");
+                        match receiver.recv().await {
+                            Ok(message) => {
+                                let res = ws.send(protocol::Message::text(to_text(&message))).await;
+                                if let Err(e) = res {
+                                    info!("Failed to send message with websocket: {e}");
                                     break;
                                 }
                             }
                             Err(RecvError::Lagged(_)) => {
-                                warn!("Some log lines have been lost because the buffer is full");
+                                warn!("Some messages have been lost because the buffer is full");
                             }
                             Err(RecvError::Closed) => break,
                         }
@@ -91,108 +258,633 @@ async fn text_websocket(
 
         Ok(response)
     } else {
-        reply(StatusCode::BAD_REQUEST)
+        Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "Expected a websocket upgrade request",
+        ))
     }
 }
 
-async fn http_api(
+struct ApiRequest {
     request: Request<Body>,
     log_sender: broadcast::Sender<String>,
-    events_sender: broadcast::Sender<String>,
-) -> StrResult<Response<Body>> {
-    let mut response = match request.uri().path() {
-        "/api/settings-schema" => reply_json(&alvr_session::settings_schema(
-            alvr_session::session_settings_default(),
-        ))?,
-        "/api/session/load" => reply_json(SERVER_DATA_MANAGER.read().session())?,
-        "/api/session/store-settings" => {
-            if let Ok(session_settings) = from_request_body::<json::Value>(request).await {
-                let res = SERVER_DATA_MANAGER
-                    .write()
-                    .session_mut()
-                    .merge_from_json(&json::json!({ "session_settings": session_settings }));
-                if let Err(e) = res {
-                    warn!("{e}");
-                    // HTTP Code: WARNING
-                    reply(StatusCode::from_u16(199).map_err(err!())?)?
-                } else {
-                    reply(StatusCode::OK)?
-                }
-            } else {
-                reply(StatusCode::BAD_REQUEST)?
-            }
-        }
-        "/api/session/store" => {
-            if let Ok(data) = from_request_body::<json::Value>(request).await {
-                if let Some(value) = data.get("session") {
-                    let res = SERVER_DATA_MANAGER
-                        .write()
-                        .session_mut()
-                        .merge_from_json(value);
-                    if let Err(e) = res {
-                        warn!("{e}");
-                        // HTTP Code: WARNING
-                        reply(StatusCode::from_u16(199).map_err(err!())?)?
-                    } else {
-                        reply(StatusCode::OK)?
-                    }
-                } else {
-                    reply(StatusCode::BAD_REQUEST)?
-                }
-            } else {
-                reply(StatusCode::BAD_REQUEST)?
-            }
+}
+
+type Handler = Box<dyn Fn(ApiRequest) -> BoxFuture<'static, ApiResult> + Send + Sync>;
+
+fn handler<F, Fut>(handler: F) -> Handler
+where
+    F: Fn(ApiRequest) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ApiResult> + Send + 'static,
+{
+    Box::new(move |request| handler(request).boxed())
+}
+
+// The request body is deserialized before calling the handler. Malformed bodies are rejected with
+// BAD_REQUEST
+fn handler_with_body<T, F, Fut>(handler: F) -> Handler
+where
+    T: DeserializeOwned + Send + 'static,
+    F: Fn(T) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = ApiResult> + Send + 'static,
+{
+    Box::new(move |request| {
+        let handler = handler.clone();
+        async move {
+            let body = from_request_body::<T>(request.request).await.map_err(|e| {
+                ApiError::new(
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid request body: {e}"),
+                )
+            })?;
+
+            handler(body).await
         }
-        "/api/log" => text_websocket(request, log_sender).await?,
-        "/api/events" => text_websocket(request, events_sender).await?,
-        "/api/driver/register" => {
-            if alvr_commands::driver_registration(
-                &[FILESYSTEM_LAYOUT.openvr_driver_root_dir.clone()],
-                true,
+        .boxed()
+    })
+}
+
+enum ResponseContent {
+    Empty,
+    // JSON schema of the body
+    Json(json::Value),
+    // Media type of the body
+    Text(&'static str),
+    WebSocket,
+}
+
+struct Route {
+    method: Method,
+    path: &'static str,
+    summary: &'static str,
+    // JSON schema of the body
+    request_body: Option<json::Value>,
+    response: ResponseContent,
+    handler: Handler,
+}
+
+fn string_schema() -> json::Value {
+    json::json!({ "type": "string" })
+}
+
+fn diagnostics_schema() -> json::Value {
+    json::json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "severity": { "enum": ["error", "warning"] },
+                "path": string_schema(),
+                "message": string_schema(),
+            },
+        },
+    })
+}
+
+fn session_changes_schema() -> json::Value {
+    json::json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "path": string_schema(),
+                "old": {},
+                "new": {},
+            },
+        },
+    })
+}
+
+fn latency_statistics_schema() -> json::Value {
+    let percentiles = json::json!({
+        "type": "object",
+        "properties": {
+            "p50Ms": { "type": "number" },
+            "p95Ms": { "type": "number" },
+            "p99Ms": { "type": "number" },
+            "jitterMs": { "type": "number" },
+        },
+    });
+
+    json::json!({
+        "type": "object",
+        "properties": {
+            "framesCount": { "type": "integer" },
+            "totalPipeline": percentiles,
+            "gameTime": percentiles,
+            "serverCompositor": percentiles,
+            "encoder": percentiles,
+            "network": percentiles,
+            "decoder": percentiles,
+            "clientCompositor": percentiles,
+            "vsyncQueue": percentiles,
+        },
+    })
+}
+
+#[derive(Deserialize)]
+struct SessionValuePatch {
+    path: String,
+    value: json::Value,
+    // If set, the value is stored only if the session has not been changed since this revision
+    revision: Option<u64>,
+}
+
+fn store_session(session_json: &json::Value) -> ApiResult {
+    // merge_from_json() only checks the types. Values out of bounds or in conflict with other
+    // settings are rejected before storing anything
+    if let Some(session_settings_json) = session_json.get("sessionSettings") {
+        let errors =
+            alvr_session::validate_session_settings_json(session_settings_json, "sessionSettings")
+                .into_iter()
+                .filter(|diagnostic| diagnostic.severity == DiagnosticSeverity::Error)
+                .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(ApiError::new(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "The session settings are not valid",
             )
-            .is_ok()
-            {
-                reply(StatusCode::OK)?
-            } else {
-                reply(StatusCode::INTERNAL_SERVER_ERROR)?
-            }
+            .with_details(json::to_value(&errors).map_err(err!())?));
         }
-        "/api/driver/unregister" => {
-            if let Ok(path) = from_request_body::<PathBuf>(request).await {
-                if alvr_commands::driver_registration(&[path], false).is_ok() {
-                    reply(StatusCode::OK)?
-                } else {
-                    reply(StatusCode::INTERNAL_SERVER_ERROR)?
-                }
-            } else {
-                reply(StatusCode::BAD_REQUEST)?
+    }
+
+    let res = SERVER_DATA_MANAGER
+        .write()
+        .session_mut()
+        .merge_from_json(session_json);
+    if let Err(e) = res {
+        warn!("{e}");
+        // The valid part of the session has been stored anyway
+        Err(ApiError::new(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("The session has been only partially stored: {e}"),
+        ))
+    } else {
+        reply(StatusCode::OK)
+    }
+}
+
+fn firewall_rules(add: bool) -> ApiResult {
+    let maybe_err = alvr_commands::firewall_rules(add).err();
+    if let Some(e) = &maybe_err {
+        error!("Setting firewall rules failed: code {e}");
+    }
+
+    reply_json(&maybe_err.unwrap_or(0))
+}
+
+async fn download_update(url: String) -> ApiResult {
+    let redirection_response = reqwest::get(&url).await.map_err(err!())?;
+    let mut resource_response = reqwest::get(redirection_response.url().clone())
+        .await
+        .map_err(err!())?;
+
+    let mut file = fs::File::create(alvr_filesystem::installer_path()).map_err(err!())?;
+
+    let mut downloaded_bytes_count = 0;
+    loop {
+        match resource_response.chunk().await {
+            Ok(Some(chunk)) => {
+                downloaded_bytes_count += chunk.len();
+                file.write_all(&chunk).map_err(err!())?;
+                alvr_events::send_event(EventType::UpdateDownloadedBytesCount(
+                    downloaded_bytes_count,
+                ));
             }
-        }
-        "/api/driver/list" => {
-            reply_json(&alvr_commands::get_registered_drivers().unwrap_or_default())?
-        }
-        uri @ ("/api/firewall-rules/add" | "/api/firewall-rules/remove") => {
-            let add = uri.ends_with("add");
-            let maybe_err = alvr_commands::firewall_rules(add).err();
-            if let Some(e) = &maybe_err {
-                error!("Setting firewall rules failed: code {e}");
+            Ok(None) => break,
+            Err(e) => {
+                alvr_events::send_event(EventType::UpdateDownloadError);
+                error!("Download update failed: {e}");
+                return Err(ApiError::new(
+                    StatusCode::BAD_GATEWAY,
+                    format!("Download update failed: {e}"),
+                ));
             }
-            reply_json(&maybe_err.unwrap_or(0))?
         }
-        "/api/audio-devices" => reply_json(&SERVER_DATA_MANAGER.read().get_audio_devices_list()?)?,
-        "/api/graphics-devices" => reply_json(&[SERVER_DATA_MANAGER.read().get_gpu_name()])?,
-        "/restart-steamvr" => {
-            crate::notify_restart_driver();
-            reply(StatusCode::OK)?
-        }
-        "/api/client/add" => {
-            if let Ok((display_name, hostname, ip)) =
-                from_request_body::<(_, String, _)>(request).await
-            {
+    }
+
+    crate::notify_application_update();
+
+    reply(StatusCode::OK)
+}
+
+fn routes() -> Vec<Route> {
+    vec![
+        Route {
+            method: Method::POST,
+            path: "/api/auth/login",
+            summary: "Check the token and store it in a cookie, used by the dashboard",
+            request_body: Some(string_schema()),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|token: String| async move {
+                if is_auth_token(&token) {
+                    with_token_cookie(reply(StatusCode::OK)?)
+                } else {
+                    Err(ApiError::new(StatusCode::UNAUTHORIZED, "Wrong token"))
+                }
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/openapi.json",
+            summary: "OpenAPI description of this API",
+            request_body: None,
+            response: ResponseContent::Json(json::json!({ "type": "object" })),
+            handler: handler(|_| async { reply_json(&openapi_document()) }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/settings-schema",
+            summary: "Schema of the settings, used to build the settings page",
+            request_body: None,
+            response: ResponseContent::Json(json::json!({ "type": "object" })),
+            handler: handler(|_| async {
+                reply_json(&alvr_session::settings_schema(
+                    alvr_session::session_settings_default(),
+                ))
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/session/load",
+            summary: "Current session. The ETag header contains the session revision",
+            request_body: None,
+            response: ResponseContent::Json(json::json!({ "type": "object" })),
+            handler: handler(|_| async {
+                let data_manager = SERVER_DATA_MANAGER.read();
+
+                let mut response = reply_json(data_manager.session())?;
+                response.headers_mut().insert(
+                    ETAG,
+                    HeaderValue::from_str(&format!("\"{}\"", data_manager.session_revision()))
+                        .map_err(err!())?,
+                );
+
+                Ok(response)
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/session/diagnostics",
+            summary: "Problems found in the session settings and in the profiles",
+            request_body: None,
+            response: ResponseContent::Json(diagnostics_schema()),
+            handler: handler(|_| async {
+                reply_json(&SERVER_DATA_MANAGER.read().session().validate())
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/session/history",
+            summary: "Recorded revisions of the session, from the newest. Each revision lists the \
+                values changed since the previous one",
+            request_body: None,
+            response: ResponseContent::Json(json::json!({
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "integer" },
+                        "timestamp": { "type": "integer" },
+                        "changes": session_changes_schema(),
+                    },
+                },
+            })),
+            handler: handler(|_| async {
+                reply_json(&SERVER_DATA_MANAGER.read().session_history())
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/session/history/diff",
+            summary: "Values changed between two revisions of the session, given as [from, to]. \
+                If \"to\" is null, the current session is used",
+            request_body: Some(json::json!({
+                "type": "array",
+                "prefixItems": [
+                    { "description": "from", "type": "integer" },
+                    { "description": "to", "type": ["integer", "null"] },
+                ],
+            })),
+            response: ResponseContent::Json(session_changes_schema()),
+            handler: handler_with_body(|(from, to): (u64, Option<u64>)| async move {
+                let changes = SERVER_DATA_MANAGER
+                    .read()
+                    .session_history_diff(from, to)
+                    .map_err(|e| ApiError::new(StatusCode::NOT_FOUND, e))?;
+
+                reply_json(&changes)
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/session/history/rollback",
+            summary: "Restore the session settings and the profiles of a revision. The client \
+                list is not changed",
+            request_body: Some(json::json!({ "type": "integer" })),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|id: u64| async move {
+                SERVER_DATA_MANAGER
+                    .write()
+                    .rollback_session(id)
+                    .map_err(|e| ApiError::new(StatusCode::NOT_FOUND, e))?;
+
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::PATCH,
+            path: "/api/session/value",
+            summary: "Set a single value of the session settings, given a path like \
+                session_settings.video.encode_bitrate_mbs. If the revision is set and the session \
+                has been changed since then, the request fails with CONFLICT. Returns the new \
+                revision",
+            request_body: Some(json::json!({
+                "type": "object",
+                "properties": {
+                    "path": string_schema(),
+                    "value": {},
+                    "revision": { "type": "integer" },
+                },
+                "required": ["path", "value"],
+            })),
+            response: ResponseContent::Json(json::json!({
+                "type": "object",
+                "properties": { "revision": { "type": "integer" } },
+            })),
+            handler: handler_with_body(|patch: SessionValuePatch| async move {
+                let mut data_manager = SERVER_DATA_MANAGER.write();
+
+                let revision = data_manager.session_revision();
+                if matches!(patch.revision, Some(expected) if expected != revision) {
+                    return Err(ApiError::new(
+                        StatusCode::CONFLICT,
+                        format!("The session has been changed, the current revision is {revision}"),
+                    ));
+                }
+
+                data_manager
+                    .set_session_settings_value(&patch.path, patch.value)
+                    .map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, e))?;
+
+                reply_json(&json::json!({ "revision": data_manager.session_revision() }))
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/session/store-settings",
+            summary: "Replace the session settings. Fields with the wrong type keep their current \
+                value. Fails with the list of errors if values are out of bounds or in conflict",
+            request_body: Some(json::json!({ "type": "object" })),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|session_settings: json::Value| async move {
+                store_session(&json::json!({ "sessionSettings": session_settings }))
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/session/store",
+            summary: "Replace the session. Fields with the wrong type keep their current value. \
+                Fails with the list of errors if settings are out of bounds or in conflict",
+            request_body: Some(json::json!({
+                "type": "object",
+                "properties": { "session": { "type": "object" } },
+                "required": ["session"],
+            })),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|data: json::Value| async move {
+                if let Some(session_json) = data.get("session") {
+                    store_session(session_json)
+                } else {
+                    Err(ApiError::new(
+                        StatusCode::BAD_REQUEST,
+                        "Missing \"session\" field",
+                    ))
+                }
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/script/execute",
+            summary: "Run a rhai script and return its result. Scripts can register event \
+                callbacks with on(event_id, callback) and change settings with set_setting(path, \
+                value)",
+            request_body: Some(string_schema()),
+            response: ResponseContent::Json(json::json!({
+                "type": "object",
+                "properties": { "result": string_schema() },
+            })),
+            handler: handler_with_body(|code: String| async move {
+                let result = SERVER_DATA_MANAGER
+                    .write()
+                    .execute_script(&code)
+                    .map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, e))?;
+
+                reply_json(&json::json!({ "result": result }))
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/script/callbacks",
+            summary: "IDs of the events with a script callback",
+            request_body: None,
+            response: ResponseContent::Json(json::json!({
+                "type": "array",
+                "items": string_schema(),
+            })),
+            handler: handler(|_| async {
+                reply_json(&SERVER_DATA_MANAGER.read().script_callback_events())
+            }),
+        },
+        Route {
+            method: Method::DELETE,
+            path: "/api/script/callbacks",
+            summary: "Remove all the script callbacks",
+            request_body: None,
+            response: ResponseContent::Empty,
+            handler: handler(|_| async {
+                SERVER_DATA_MANAGER.write().clear_script_callbacks();
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/log",
+            summary: "Server log lines",
+            request_body: None,
+            response: ResponseContent::WebSocket,
+            handler: handler(|request| {
+                let log_receiver = request.log_sender.subscribe();
+                text_websocket(request.request, vec![], log_receiver, String::clone)
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/events",
+            summary: "Server events, serialized as JSON. The latest events, except logs, are sent \
+                again on connection, use the sequence field to skip the ones already received",
+            request_body: None,
+            response: ResponseContent::WebSocket,
+            handler: handler(|request| {
+                let (replay, events_receiver) = alvr_events::subscribe_events();
+                text_websocket(request.request, replay, events_receiver, |event| {
+                    json::to_string(event).unwrap_or_default()
+                })
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/logs",
+            summary: "Log files of the current and previous server sessions, from the newest. \
+                Files are written only if logging to disk is enabled",
+            request_body: None,
+            response: ResponseContent::Json(json::json!({
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "size": { "description": "bytes", "type": "integer" },
+                        "modified": {
+                            "description": "seconds since the UNIX epoch",
+                            "type": "integer",
+                        },
+                        "current": { "type": "boolean" },
+                    },
+                },
+            })),
+            handler: handler(|_| async { reply_json(&logging_backend::log_files()) }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/logs/download",
+            summary: "Content of a log file, given its name",
+            request_body: Some(string_schema()),
+            response: ResponseContent::Text("text/plain; charset=utf-8"),
+            handler: handler_with_body(|name: String| async move {
+                let content = logging_backend::read_log_file(&name)
+                    .map_err(|e| ApiError::new(StatusCode::NOT_FOUND, e))?;
+
+                reply_text("text/plain; charset=utf-8", content)
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/driver/register",
+            summary: "Register the ALVR driver in SteamVR",
+            request_body: None,
+            response: ResponseContent::Empty,
+            handler: handler(|_| async {
+                alvr_commands::driver_registration(
+                    &[FILESYSTEM_LAYOUT.openvr_driver_root_dir.clone()],
+                    true,
+                )?;
+
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/driver/unregister",
+            summary: "Unregister a SteamVR driver, given its path",
+            request_body: Some(string_schema()),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|path: PathBuf| async move {
+                alvr_commands::driver_registration(&[path], false)?;
+
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/driver/list",
+            summary: "Paths of the drivers registered in SteamVR",
+            request_body: None,
+            response: ResponseContent::Json(json::json!({
+                "type": "array",
+                "items": string_schema(),
+            })),
+            handler: handler(|_| async {
+                reply_json(&alvr_commands::get_registered_drivers().unwrap_or_default())
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/firewall-rules/add",
+            summary: "Add the firewall rules. Returns the exit code of the script",
+            request_body: None,
+            response: ResponseContent::Json(json::json!({ "type": "integer" })),
+            handler: handler(|_| async { firewall_rules(true) }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/firewall-rules/remove",
+            summary: "Remove the firewall rules. Returns the exit code of the script",
+            request_body: None,
+            response: ResponseContent::Json(json::json!({ "type": "integer" })),
+            handler: handler(|_| async { firewall_rules(false) }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/audio-devices",
+            summary: "Names of the audio devices",
+            request_body: None,
+            response: ResponseContent::Json(json::json!({
+                "type": "object",
+                "properties": {
+                    "output": { "type": "array", "items": string_schema() },
+                    "input": { "type": "array", "items": string_schema() },
+                },
+            })),
+            handler: handler(|_| async {
+                reply_json(&SERVER_DATA_MANAGER.read().get_audio_devices_list()?)
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/graphics-devices",
+            summary: "Names of the GPUs",
+            request_body: None,
+            response: ResponseContent::Json(json::json!({
+                "type": "array",
+                "items": string_schema(),
+            })),
+            handler: handler(|_| async {
+                reply_json(&[SERVER_DATA_MANAGER.read().get_gpu_name()])
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/restart-steamvr",
+            summary: "Restart SteamVR",
+            request_body: None,
+            response: ResponseContent::Empty,
+            handler: handler(|_| async {
+                crate::notify_restart_driver();
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/client/add",
+            summary: "Add a trusted client manually",
+            request_body: Some(json::json!({
+                "type": "array",
+                "prefixItems": [
+                    { "description": "display name", "type": "string" },
+                    { "description": "hostname", "type": "string" },
+                    { "description": "IP address", "type": "string" },
+                ],
+            })),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|(display_name, hostname, ip): (_, String, _)| async move {
                 let mut data_manager_ref = SERVER_DATA_MANAGER.write();
                 data_manager_ref.update_client_list(
                     hostname.clone(),
-                    ClientListAction::AddIfMissing { display_name },
+                    ClientListAction::AddIfMissing {
+                        display_name,
+                        certificate_fingerprint: None,
+                    },
                     Some(&CLIENTS_UPDATED_NOTIFIER),
                 );
                 data_manager_ref.update_client_list(
@@ -201,25 +893,62 @@ async fn http_api(
                     Some(&CLIENTS_UPDATED_NOTIFIER),
                 );
 
-                reply(StatusCode::OK)?
-            } else {
-                reply(StatusCode::BAD_REQUEST)?
-            }
-        }
-        "/api/client/trust" => {
-            if let Ok((hostname, maybe_ip)) = from_request_body(request).await {
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/client/pair",
+            summary: "Trust the client showing the given pairing code",
+            request_body: Some(string_schema()),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|pairing_code: String| async move {
+                let res = SERVER_DATA_MANAGER
+                    .write()
+                    .pair_client(&pairing_code, Some(&CLIENTS_UPDATED_NOTIFIER));
+                if let Err(e) = res {
+                    warn!("{e}");
+                    Err(ApiError::new(StatusCode::FORBIDDEN, e))
+                } else {
+                    reply(StatusCode::OK)
+                }
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/client/add-ip",
+            summary: "Add an IP address to a trusted client",
+            request_body: Some(json::json!({
+                "type": "array",
+                "prefixItems": [
+                    { "description": "hostname", "type": "string" },
+                    { "description": "IP address", "type": "string" },
+                ],
+            })),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|(hostname, ip)| async move {
                 SERVER_DATA_MANAGER.write().update_client_list(
                     hostname,
-                    ClientListAction::TrustAndMaybeAddIp(maybe_ip),
+                    ClientListAction::AddIp(ip),
                     Some(&CLIENTS_UPDATED_NOTIFIER),
                 );
-                reply(StatusCode::OK)?
-            } else {
-                reply(StatusCode::BAD_REQUEST)?
-            }
-        }
-        "/api/client/remove" => {
-            if let Ok((hostname, maybe_ip)) = from_request_body(request).await {
+
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/client/remove",
+            summary: "Remove an IP address of a client, or the whole client if the IP is null",
+            request_body: Some(json::json!({
+                "type": "array",
+                "prefixItems": [
+                    { "description": "hostname", "type": "string" },
+                    { "description": "IP address", "type": ["string", "null"] },
+                ],
+            })),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|(hostname, maybe_ip)| async move {
                 SERVER_DATA_MANAGER.write().update_client_list(
                     hostname,
                     ClientListAction::RemoveIpOrEntry(maybe_ip),
@@ -227,83 +956,495 @@ async fn http_api(
                 );
                 DISCONNECT_CLIENT_NOTIFIER.notify_waiters();
 
-                reply(StatusCode::OK)?
-            } else {
-                reply(StatusCode::BAD_REQUEST)?
-            }
-        }
-        "/api/version" => Response::new(ALVR_VERSION.to_string().into()),
-        "/api/open" => {
-            if let Ok(url) = from_request_body::<String>(request).await {
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/client/set-profile",
+            summary: "Assign a settings profile to a client. A null profile selects the default \
+                session settings",
+            request_body: Some(json::json!({
+                "type": "array",
+                "prefixItems": [
+                    { "description": "hostname", "type": "string" },
+                    { "description": "profile name", "type": ["string", "null"] },
+                ],
+            })),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|(hostname, profile)| async move {
+                SERVER_DATA_MANAGER
+                    .write()
+                    .set_client_profile(hostname, profile, Some(&CLIENTS_UPDATED_NOTIFIER))
+                    .map_err(|e| ApiError::new(StatusCode::NOT_FOUND, e))?;
+
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/profiles",
+            summary: "Names of the settings profiles",
+            request_body: None,
+            response: ResponseContent::Json(json::json!({
+                "type": "array",
+                "items": string_schema(),
+            })),
+            handler: handler(|_| async {
+                let mut names = SERVER_DATA_MANAGER
+                    .read()
+                    .session()
+                    .profiles
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                names.sort();
+
+                reply_json(&names)
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/profiles/save",
+            summary: "Save the current session settings as a profile, overwriting a profile with \
+                the same name",
+            request_body: Some(string_schema()),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|name: String| async move {
+                SERVER_DATA_MANAGER.write().save_profile(name);
+
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/profiles/load",
+            summary: "Replace the session settings with the content of a profile",
+            request_body: Some(string_schema()),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|name: String| async move {
+                SERVER_DATA_MANAGER
+                    .write()
+                    .load_profile(&name)
+                    .map_err(|e| ApiError::new(StatusCode::NOT_FOUND, e))?;
+
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/profiles/remove",
+            summary:
+                "Remove a profile. Clients that use it go back to the default session settings",
+            request_body: Some(string_schema()),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|name: String| async move {
+                SERVER_DATA_MANAGER
+                    .write()
+                    .remove_profile(&name)
+                    .map_err(|e| ApiError::new(StatusCode::NOT_FOUND, e))?;
+
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/presets",
+            summary: "Settings presets shipped with the server",
+            request_body: None,
+            response: ResponseContent::Json(json::json!({
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "id": string_schema(),
+                        "name": string_schema(),
+                        "description": string_schema(),
+                        "sessionSettings": { "type": "object" },
+                    },
+                },
+            })),
+            handler: handler(|_| async {
+                reply_json(&alvr_session::load_presets(
+                    &FILESYSTEM_LAYOUT.presets_dir(),
+                ))
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/presets/apply",
+            summary: "Overwrite the session settings with the values contained in a preset",
+            request_body: Some(string_schema()),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|id: String| async move {
+                let preset = alvr_session::load_preset(&FILESYSTEM_LAYOUT.presets_dir(), &id)
+                    .map_err(|e| ApiError::new(StatusCode::NOT_FOUND, e))?;
+                SERVER_DATA_MANAGER
+                    .write()
+                    .apply_preset(&preset)
+                    .map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, e))?;
+
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/version",
+            summary: "Version of the server",
+            request_body: None,
+            response: ResponseContent::Text("text/plain"),
+            handler: handler(|_| async { reply_text("text/plain", ALVR_VERSION.to_string()) }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/server-os",
+            summary: "Operating system of the server",
+            request_body: None,
+            response: ResponseContent::Text("text/plain"),
+            handler: handler(|_| async { reply_text("text/plain", OS.into()) }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/api/statistics/latency",
+            summary:
+                "Latency percentiles of the primary client. Not found if no client is streaming",
+            request_body: None,
+            response: ResponseContent::Json(latency_statistics_schema()),
+            handler: handler(|_| async {
+                let maybe_latency_statistics = CLIENT_SESSIONS
+                    .lock()
+                    .primary_statistics()
+                    .map(|stats| stats.latency_statistics());
+                if let Some(latency_statistics) = maybe_latency_statistics {
+                    reply_json(&latency_statistics)
+                } else {
+                    Err(ApiError::new(
+                        StatusCode::NOT_FOUND,
+                        "No client is streaming",
+                    ))
+                }
+            }),
+        },
+        Route {
+            method: Method::GET,
+            path: "/metrics",
+            summary: "Streaming statistics in the Prometheus text format",
+            request_body: None,
+            response: ResponseContent::Text("text/plain; version=0.0.4"),
+            handler: handler(|_| async {
+                reply_text("text/plain; version=0.0.4", METRICS.lock().encode())
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/open",
+            summary: "Open a URL in the default browser of the server",
+            request_body: Some(string_schema()),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(|url: String| async move {
                 webbrowser::open(&url).ok();
-                reply(StatusCode::OK)?
-            } else {
-                reply(StatusCode::BAD_REQUEST)?
+                reply(StatusCode::OK)
+            }),
+        },
+        Route {
+            method: Method::POST,
+            path: "/api/update",
+            summary: "Download the installer at the given URL and start it",
+            request_body: Some(string_schema()),
+            response: ResponseContent::Empty,
+            handler: handler_with_body(download_update),
+        },
+    ]
+}
+
+static ROUTES: Lazy<Vec<Route>> = Lazy::new(routes);
+
+fn openapi_document() -> json::Value {
+    let mut paths = json::Map::new();
+    for route in ROUTES.iter() {
+        let (status, mut response) = match &route.response {
+            ResponseContent::Empty => ("200", json::json!({ "description": "Success" })),
+            ResponseContent::Json(schema) => (
+                "200",
+                json::json!({
+                    "description": "Success",
+                    "content": { "application/json": { "schema": schema } },
+                }),
+            ),
+            ResponseContent::Text(media_type) => {
+                let mut content = json::Map::new();
+                content.insert(
+                    media_type.to_string(),
+                    json::json!({ "schema": string_schema() }),
+                );
+                (
+                    "200",
+                    json::json!({ "description": "Success", "content": content }),
+                )
             }
+            ResponseContent::WebSocket => (
+                "101",
+                json::json!({ "description": "Switched to a websocket of text messages" }),
+            ),
+        };
+        response = json::json!({
+            status: response,
+            "default": {
+                "description": "Error",
+                "content": {
+                    "application/json": { "schema": { "$ref": "#/components/schemas/Error" } },
+                },
+            },
+        });
+
+        let mut operation = json::json!({ "summary": route.summary, "responses": response });
+        if PUBLIC_PATHS.contains(&route.path) {
+            operation["security"] = json::json!([]);
+        }
+        if let Some(schema) = &route.request_body {
+            operation["requestBody"] = json::json!({
+                "required": true,
+                "content": { "application/json": { "schema": schema } },
+            });
         }
-        "/api/server-os" => Response::new(OS.into()),
-        "/api/update" => {
-            if let Ok(url) = from_request_body::<String>(request).await {
-                let redirection_response = reqwest::get(&url).await.map_err(err!())?;
-                let mut resource_response = reqwest::get(redirection_response.url().clone())
-                    .await
-                    .map_err(err!())?;
-
-                let mut file =
-                    fs::File::create(alvr_filesystem::installer_path()).map_err(err!())?;
-
-                let mut downloaded_bytes_count = 0;
-                loop {
-                    match resource_response.chunk().await {
-                        Ok(Some(chunk)) => {
-                            downloaded_bytes_count += chunk.len();
-                            file.write_all(&chunk).map_err(err!())?;
-                            alvr_events::send_event(EventType::UpdateDownloadedBytesCount(
-                                downloaded_bytes_count,
-                            ));
-                        }
-                        Ok(None) => break,
-                        Err(e) => {
-                            alvr_events::send_event(EventType::UpdateDownloadError);
-                            error!("Download update failed: {e}");
-                            return reply(StatusCode::BAD_GATEWAY);
-                        }
-                    }
-                }
 
-                crate::notify_application_update();
-            }
-            reply(StatusCode::BAD_REQUEST)?
+        paths.entry(route.path).or_insert_with(|| json::json!({}))
+            [route.method.as_str().to_lowercase()] = operation;
+    }
+
+    json::json!({
+        "openapi": "3.1.0",
+        "info": { "title": "ALVR server", "version": ALVR_VERSION.to_string() },
+        "paths": paths,
+        "security": [{ "token": [] }, { "cookie": [] }],
+        "components": {
+            "securitySchemes": {
+                "token": { "type": "http", "scheme": "bearer" },
+                "cookie": { "type": "apiKey", "in": "cookie", "name": TOKEN_COOKIE },
+            },
+            "schemas": {
+                "Error": {
+                    "type": "object",
+                    "properties": {
+                        "error": { "type": "string" },
+                        "details": {},
+                    },
+                    "required": ["error"],
+                },
+            },
+        },
+    })
+}
+
+async fn dashboard_file(path: &str) -> ApiResult {
+    if path.contains("..") {
+        // Attempted tree traversal
+        return Err(ApiError::new(StatusCode::FORBIDDEN, "Invalid path"));
+    }
+
+    let path_branch = match path {
+        "/" => "/index.html",
+        other_path => other_path,
+    };
+
+    let maybe_file = tokio::fs::File::open(format!(
+        "{}{path_branch}",
+        FILESYSTEM_LAYOUT.dashboard_dir().to_string_lossy(),
+    ))
+    .await;
+
+    if let Ok(file) = maybe_file {
+        let mut builder = Response::builder();
+        if path.ends_with(".wasm") {
+            builder = builder.header(CONTENT_TYPE, "application/wasm");
         }
-        other_uri => {
-            if other_uri.contains("..") {
-                // Attempted tree traversal
-                reply(StatusCode::FORBIDDEN)?
-            } else {
-                let path_branch = match other_uri {
-                    "/" => "/index.html",
-                    other_path => other_path,
-                };
 
-                let maybe_file = tokio::fs::File::open(format!(
-                    "{}{path_branch}",
-                    FILESYSTEM_LAYOUT.dashboard_dir().to_string_lossy(),
-                ))
-                .await;
+        Ok(builder
+            .body(Body::wrap_stream(FramedRead::new(file, BytesCodec::new())))
+            .map_err(err!())?)
+    } else {
+        Err(ApiError::new(StatusCode::NOT_FOUND, "File not found"))
+    }
+}
 
-                if let Ok(file) = maybe_file {
-                    let mut builder = Response::builder();
-                    if other_uri.ends_with(".wasm") {
-                        builder = builder.header(CONTENT_TYPE, "application/wasm");
-                    }
+// Scripts use the Authorization header, the dashboard uses the cookie
+fn request_token(request: &Request<Body>) -> Option<String> {
+    let headers = request.headers();
+
+    if let Some(authorization) =
+        headers.typed_get::<headers::Authorization<headers::authorization::Bearer>>()
+    {
+        Some(authorization.token().to_owned())
+    } else {
+        headers
+            .typed_get::<headers::Cookie>()
+            .and_then(|cookie| cookie.get(TOKEN_COOKIE).map(|token| token.to_owned()))
+    }
+}
 
-                    builder
-                        .body(Body::wrap_stream(FramedRead::new(file, BytesCodec::new())))
-                        .map_err(err!())?
+fn is_loopback_host(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .parse::<IpAddr>()
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false)
+}
+
+// The dashboard can be reached only through the loopback names or the address the request was
+// received on. The Host header cannot be trusted: with DNS rebinding, a website resolving to this
+// PC would be same-origin with the dashboard.
+fn dashboard_host(request: &Request<Body>, local_ip: IpAddr) -> Option<Authority> {
+    let host = request
+        .headers()
+        .get(HOST)?
+        .to_str()
+        .ok()?
+        .parse::<Authority>()
+        .ok()?;
+
+    let is_local_ip = host
+        .host()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+        .map(|ip| ip == local_ip)
+        .unwrap_or(false);
+
+    if is_loopback_host(host.host()) || is_local_ip {
+        Some(host)
+    } else {
+        None
+    }
+}
+
+fn dashboard_origins(local_ip: IpAddr, port: u16) -> Vec<String> {
+    let local_host = match local_ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{ip}]"),
+    };
+
+    // Browsers omit the default port
+    let port_suffix = if port == 80 {
+        "".into()
+    } else {
+        format!(":{port}")
+    };
+
+    ["localhost", "127.0.0.1", "[::1]", &local_host]
+        .iter()
+        .map(|host| format!("http://{host}{port_suffix}"))
+        .collect()
+}
+
+async fn http_api(
+    request: Request<Body>,
+    peer_ip: IpAddr,
+    local_address: SocketAddr,
+    log_sender: broadcast::Sender<String>,
+) -> StrResult<Response<Body>> {
+    let path = request.uri().path().to_owned();
+
+    let dashboard_host = if let Some(host) = dashboard_host(&request, local_address.ip()) {
+        host
+    } else {
+        return Ok(error_response(&ApiError::new(
+            StatusCode::FORBIDDEN,
+            "Unknown host. Open the dashboard with localhost or the IP of this PC",
+        )));
+    };
+
+    let authorized = request_token(&request)
+        .map(|token| is_auth_token(&token))
+        .unwrap_or(false);
+
+    // Browsers always send the origin for cross-origin requests
+    let request_origin = request
+        .headers()
+        .get(ORIGIN)
+        .map(|origin| origin.to_str().unwrap_or_default().to_owned());
+    let dashboard_origin = request_origin.clone().filter(|origin| {
+        dashboard_origins(local_address.ip(), local_address.port()).contains(origin)
+    });
+    let foreign_origin = request_origin.is_some() && dashboard_origin.is_none();
+
+    let path_routes = ROUTES
+        .iter()
+        .filter(|route| route.path == path)
+        .collect::<Vec<_>>();
+
+    let res = if let Some(route) = path_routes
+        .iter()
+        .find(|route| route.method == request.method())
+    {
+        if foreign_origin {
+            Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "Requests from other websites are not allowed",
+            ))
+        } else if !authorized && !PUBLIC_PATHS.contains(&route.path) {
+            Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "Missing or wrong token",
+            ))
+        } else {
+            (route.handler)(ApiRequest {
+                request,
+                log_sender,
+            })
+            .await
+        }
+    } else if !path_routes.is_empty() {
+        Err(ApiError::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            format!("Method {} is not allowed", request.method()),
+        ))
+    } else if path.starts_with("/api/") {
+        Err(ApiError::new(StatusCode::NOT_FOUND, "Unknown endpoint"))
+    } else if request.method() == Method::GET {
+        if matches!(path.as_str(), "/" | "/index.html") && !authorized {
+            if peer_ip.is_loopback() && is_loopback_host(dashboard_host.host()) {
+                // The dashboard window and browsers on this PC don't need to log in
+                dashboard_file(&path).await.and_then(with_token_cookie)
+            } else {
+                reply_text("text/html", LOGIN_PAGE.into())
+            }
+        } else {
+            dashboard_file(&path).await
+        }
+    } else {
+        Err(ApiError::new(
+            StatusCode::METHOD_NOT_ALLOWED,
+            format!("Method {} is not allowed", request.method()),
+        ))
+    };
+
+    let mut response = match res {
+        Ok(response) => response,
+        Err(e) => {
+            if e.status.is_server_error() {
+                error!("{path}: {}", e.message);
+            }
+
+            let mut response = error_response(&e);
+            if e.status == StatusCode::METHOD_NOT_ALLOWED {
+                let allowed_methods = if path_routes.is_empty() {
+                    "GET".into()
                 } else {
-                    reply(StatusCode::NOT_FOUND)?
-                }
+                    path_routes
+                        .iter()
+                        .map(|route| route.method.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                response.headers_mut().insert(
+                    ALLOW,
+                    HeaderValue::from_str(&allowed_methods).map_err(err!())?,
+                );
             }
+
+            response
         }
     };
 
@@ -311,32 +1452,36 @@ async fn http_api(
         CACHE_CONTROL,
         HeaderValue::from_str("no-cache, no-store, must-revalidate").map_err(err!())?,
     );
-    response
-        .headers_mut()
-        .insert(ACCESS_CONTROL_ALLOW_ORIGIN, HeaderValue::from_static("*"));
+    if let Some(origin) = dashboard_origin {
+        response.headers_mut().insert(
+            ACCESS_CONTROL_ALLOW_ORIGIN,
+            HeaderValue::from_str(&origin).map_err(err!())?,
+        );
+    }
 
     Ok(response)
 }
 
-pub async fn web_server(
-    log_sender: broadcast::Sender<String>,
-    events_sender: broadcast::Sender<String>,
-) -> StrResult {
-    let web_server_port = SERVER_DATA_MANAGER
-        .read()
-        .settings()
-        .connection
-        .web_server_port;
-
-    let service = service::make_service_fn(|_| {
+pub async fn web_server(log_sender: broadcast::Sender<String>) -> StrResult {
+    let (web_server_port, loopback_only) = {
+        let data_manager = SERVER_DATA_MANAGER.read();
+        let connection = &data_manager.settings().connection;
+
+        (
+            connection.web_server_port,
+            connection.web_server_loopback_only,
+        )
+    };
+
+    let service = service::make_service_fn(|connection: &AddrStream| {
+        let peer_ip = connection.remote_addr().ip();
+        let local_address = connection.local_addr();
         let log_sender = log_sender.clone();
-        let events_sender = events_sender.clone();
         async move {
             StrResult::Ok(service::service_fn(move |request| {
                 let log_sender = log_sender.clone();
-                let events_sender = events_sender.clone();
                 async move {
-                    let res = http_api(request, log_sender, events_sender).await;
+                    let res = http_api(request, peer_ip, local_address, log_sender).await;
                     if let Err(e) = &res {
                         alvr_common::show_e(e);
                     }
@@ -347,11 +1492,65 @@ pub async fn web_server(
         }
     });
 
-    hyper::Server::bind(&SocketAddr::new(
-        "0.0.0.0".parse().unwrap(),
-        web_server_port,
-    ))
-    .serve(service)
-    .await
-    .map_err(err!())
+    let bind_ip = if loopback_only {
+        Ipv4Addr::LOCALHOST
+    } else {
+        Ipv4Addr::UNSPECIFIED
+    };
+
+    hyper::Server::bind(&SocketAddr::new(bind_ip.into(), web_server_port))
+        .serve(service)
+        .await
+        .map_err(err!())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_with_host(host: &str) -> Request<Body> {
+        Request::builder()
+            .header(HOST, host)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn dashboard_hosts() {
+        let local_ip = IpAddr::from([192, 168, 1, 10]);
+        let is_allowed = |host: &str| dashboard_host(&request_with_host(host), local_ip).is_some();
+
+        assert!(is_allowed("localhost:8082"));
+        assert!(is_allowed("LOCALHOST:8082"));
+        assert!(is_allowed("127.0.0.1:8082"));
+        assert!(is_allowed("[::1]:8082"));
+        assert!(is_allowed("192.168.1.10:8082"));
+
+        // A website that resolves to this PC
+        assert!(!is_allowed("evil.com:8082"));
+        assert!(!is_allowed("localhost.evil.com:8082"));
+        assert!(!is_allowed("192.168.1.11:8082"));
+        assert!(dashboard_host(&Request::new(Body::empty()), local_ip).is_none());
+    }
+
+    #[test]
+    fn loopback_hosts() {
+        assert!(is_loopback_host("localhost"));
+        assert!(is_loopback_host("127.0.0.1"));
+        assert!(is_loopback_host("[::1]"));
+        assert!(!is_loopback_host("192.168.1.10"));
+        assert!(!is_loopback_host("evil.com"));
+    }
+
+    #[test]
+    fn allowed_origins() {
+        let origins = dashboard_origins(IpAddr::from([192, 168, 1, 10]), 8082);
+
+        assert!(origins.contains(&"http://localhost:8082".to_owned()));
+        assert!(origins.contains(&"http://192.168.1.10:8082".to_owned()));
+        assert!(!origins.contains(&"http://evil.com:8082".to_owned()));
+
+        let origins = dashboard_origins(IpAddr::from([127, 0, 0, 1]), 80);
+        assert!(origins.contains(&"http://127.0.0.1".to_owned()));
+    }
 }
@@ -0,0 +1,366 @@
+// Bounded history of the session, stored in the session_history folder next to session.json. An
+// entry is recorded every time the saved session changes. Each entry contains the full session, so
+// it can be restored even if the entries before it have been pruned, and the list of values
+// changed since the previous entry.
+
+use alvr_common::prelude::*;
+use alvr_session::SessionDesc;
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const MAX_JOURNAL_ENTRIES: usize = 50;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SessionChange {
+    // Path of the value inside the session JSON, like "sessionSettings.video.encodeBitrateMbs"
+    pub path: String,
+    // null if the value has been added
+    pub old: json::Value,
+    // null if the value has been removed
+    pub new: json::Value,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct JournalEntrySummary {
+    pub id: u64,
+    // seconds since the UNIX epoch
+    pub timestamp: u64,
+    // Empty for the first recorded entry
+    pub changes: Vec<SessionChange>,
+}
+
+// The session is kept as JSON, entries saved by older server versions can still be read
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JournalEntry {
+    #[serde(flatten)]
+    summary: JournalEntrySummary,
+    session: json::Value,
+}
+
+fn diff_values(old: &json::Value, new: &json::Value, path: &str, changes: &mut Vec<SessionChange>) {
+    match (old, new) {
+        (json::Value::Object(old_fields), json::Value::Object(new_fields)) => {
+            for (name, old_value) in old_fields {
+                let field_path = if path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{path}.{name}")
+                };
+                let new_value = new_fields.get(name).unwrap_or(&json::Value::Null);
+                diff_values(old_value, new_value, &field_path, changes);
+            }
+            for (name, new_value) in new_fields {
+                if !old_fields.contains_key(name) {
+                    let field_path = if path.is_empty() {
+                        name.clone()
+                    } else {
+                        format!("{path}.{name}")
+                    };
+                    diff_values(&json::Value::Null, new_value, &field_path, changes);
+                }
+            }
+        }
+        // Arrays are compared element by element only if the length did not change
+        (json::Value::Array(old_elements), json::Value::Array(new_elements))
+            if old_elements.len() == new_elements.len() =>
+        {
+            for (idx, (old_value, new_value)) in old_elements.iter().zip(new_elements).enumerate() {
+                diff_values(old_value, new_value, &format!("{path}.{idx}"), changes);
+            }
+        }
+        _ => {
+            if old != new {
+                changes.push(SessionChange {
+                    path: path.to_owned(),
+                    old: old.clone(),
+                    new: new.clone(),
+                });
+            }
+        }
+    }
+}
+
+// Values that differ between two session JSONs
+pub fn diff_sessions(old: &json::Value, new: &json::Value) -> Vec<SessionChange> {
+    let mut changes = vec![];
+    diff_values(old, new, "", &mut changes);
+
+    changes
+}
+
+pub struct SessionJournal {
+    dir: PathBuf,
+}
+
+impl SessionJournal {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_owned(),
+        }
+    }
+
+    fn entry_path(&self, id: u64) -> PathBuf {
+        self.dir.join(format!("{id}.json"))
+    }
+
+    // Sorted from the oldest
+    fn entry_ids(&self) -> Vec<u64> {
+        let mut ids = fs::read_dir(&self.dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("json"))
+                    .filter_map(|path| path.file_stem()?.to_str()?.parse().ok())
+                    .collect::<Vec<u64>>()
+            })
+            .unwrap_or_default();
+        ids.sort_unstable();
+
+        ids
+    }
+
+    fn load_entry(&self, id: u64) -> StrResult<JournalEntry> {
+        let entry_string = fs::read_to_string(self.entry_path(id))
+            .map_err(|_| format!("Session revision {id} not found"))?;
+
+        json::from_str(&entry_string).map_err(err!())
+    }
+
+    // Nothing is recorded if the session did not change since the last entry
+    pub fn record(&self, session: &SessionDesc) -> StrResult {
+        let session_json = json::to_value(session).map_err(err!())?;
+
+        let ids = self.entry_ids();
+        let (id, changes) = match ids.last() {
+            Some(&last_id) => match self.load_entry(last_id) {
+                Ok(last_entry) => {
+                    let changes = diff_sessions(&last_entry.session, &session_json);
+                    if changes.is_empty() {
+                        return Ok(());
+                    }
+
+                    (last_id + 1, changes)
+                }
+                Err(e) => {
+                    warn!("Failed to read session revision {last_id}: {e}");
+                    (last_id + 1, vec![])
+                }
+            },
+            None => (0, vec![]),
+        };
+
+        let entry = JournalEntry {
+            summary: JournalEntrySummary {
+                id,
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or_default(),
+                changes,
+            },
+            session: session_json,
+        };
+
+        fs::create_dir_all(&self.dir).map_err(err!())?;
+        fs::write(
+            self.entry_path(id),
+            json::to_string_pretty(&entry).map_err(err!())?,
+        )
+        .map_err(err!())?;
+
+        // The new entry is not included in ids
+        let entries_count = ids.len() + 1;
+        if entries_count > MAX_JOURNAL_ENTRIES {
+            for old_id in &ids[..entries_count - MAX_JOURNAL_ENTRIES] {
+                fs::remove_file(self.entry_path(*old_id)).ok();
+            }
+        }
+
+        Ok(())
+    }
+
+    // Sorted from the newest. Entries that cannot be read are skipped
+    pub fn entries(&self) -> Vec<JournalEntrySummary> {
+        self.entry_ids()
+            .into_iter()
+            .rev()
+            .filter_map(|id| self.load_entry(id).ok())
+            .map(|entry| entry.summary)
+            .collect()
+    }
+
+    pub fn session_json(&self, id: u64) -> StrResult<json::Value> {
+        Ok(self.load_entry(id)?.session)
+    }
+
+    // Only session_settings and the profiles are restored, the client list is left untouched
+    pub fn rollback_json(&self, id: u64) -> StrResult<json::Value> {
+        let old_session_json = self.session_json(id)?;
+
+        let mut session_json = json::Map::new();
+        for name in ["sessionSettings", "profiles"] {
+            if let Some(value) = old_session_json.get(name) {
+                session_json.insert(name.to_owned(), value.clone());
+            }
+        }
+
+        Ok(json::Value::Object(session_json))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::{env, process};
+
+    // Removes the journal folder when the test ends
+    struct TestJournal(SessionJournal);
+
+    impl TestJournal {
+        fn new(name: &str) -> Self {
+            let dir = env::temp_dir().join(format!("alvr_journal_test_{name}_{}", process::id()));
+            fs::remove_dir_all(&dir).ok();
+
+            Self(SessionJournal::new(&dir))
+        }
+    }
+
+    impl Drop for TestJournal {
+        fn drop(&mut self) {
+            fs::remove_dir_all(&self.0.dir).ok();
+        }
+    }
+
+    fn change(path: &str, old: json::Value, new: json::Value) -> SessionChange {
+        SessionChange {
+            path: path.into(),
+            old,
+            new,
+        }
+    }
+
+    #[test]
+    fn objects_are_diffed_by_field() {
+        let old = json!({ "a": { "b": 1, "c": 2 }, "removed": true });
+        let new = json!({ "a": { "b": 1, "c": 3 }, "added": "x" });
+
+        assert_eq!(
+            diff_sessions(&old, &new),
+            vec![
+                change("a.c", json!(2), json!(3)),
+                change("removed", json!(true), json::Value::Null),
+                change("added", json::Value::Null, json!("x")),
+            ]
+        );
+    }
+
+    #[test]
+    fn arrays_are_diffed_by_element_if_the_length_is_the_same() {
+        let old = json!({ "list": [1, { "a": 2 }, 3] });
+        let new = json!({ "list": [1, { "a": 4 }, 3] });
+        assert_eq!(
+            diff_sessions(&old, &new),
+            vec![change("list.1.a", json!(2), json!(4))]
+        );
+
+        let new = json!({ "list": [1, 3] });
+        assert_eq!(
+            diff_sessions(&old, &new),
+            vec![change("list", json!([1, { "a": 2 }, 3]), json!([1, 3]))]
+        );
+
+        assert!(diff_sessions(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn unchanged_session_is_not_recorded() {
+        let journal = TestJournal::new("unchanged");
+        let mut session = SessionDesc::default();
+
+        journal.0.record(&session).unwrap();
+        journal.0.record(&session).unwrap();
+        assert_eq!(journal.0.entries().len(), 1);
+
+        session.session_settings.video.encode_bitrate_mbs += 10;
+        journal.0.record(&session).unwrap();
+
+        let entries = journal.0.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, 1);
+        assert_eq!(entries[0].changes.len(), 1);
+        assert_eq!(
+            entries[0].changes[0].path,
+            "sessionSettings.video.encodeBitrateMbs"
+        );
+        assert!(entries[1].changes.is_empty());
+    }
+
+    #[test]
+    fn old_entries_are_pruned() {
+        let journal = TestJournal::new("pruned");
+        let mut session = SessionDesc::default();
+
+        for _ in 0..MAX_JOURNAL_ENTRIES + 5 {
+            session.session_settings.video.encode_bitrate_mbs += 1;
+            journal.0.record(&session).unwrap();
+        }
+
+        let entries = journal.0.entries();
+        assert_eq!(entries.len(), MAX_JOURNAL_ENTRIES);
+        assert_eq!(entries[0].id, MAX_JOURNAL_ENTRIES as u64 + 4);
+        assert!(journal.0.session_json(4).is_err());
+        assert!(journal.0.session_json(5).is_ok());
+    }
+
+    #[test]
+    fn unreadable_last_entry_is_skipped() {
+        let journal = TestJournal::new("unreadable");
+        let session = SessionDesc::default();
+
+        journal.0.record(&session).unwrap();
+        fs::write(journal.0.entry_path(0), "{").unwrap();
+        journal.0.record(&session).unwrap();
+
+        let entries = journal.0.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, 1);
+        assert!(entries[0].changes.is_empty());
+    }
+
+    #[test]
+    fn rollback_restores_only_settings_and_profiles() {
+        let journal = TestJournal::new("rollback");
+        let mut session = SessionDesc::default();
+        journal.0.record(&session).unwrap();
+
+        session.session_settings.video.encode_bitrate_mbs += 10;
+        session.locale = "en".into();
+        journal.0.record(&session).unwrap();
+
+        let rollback_json = journal.0.rollback_json(0).unwrap();
+        let fields = rollback_json.as_object().unwrap();
+        assert_eq!(fields.len(), 2);
+        assert!(fields.contains_key("profiles"));
+
+        let mut restored_session = session.clone();
+        restored_session.merge_from_json(&rollback_json).unwrap();
+        assert_eq!(
+            restored_session.session_settings.video.encode_bitrate_mbs,
+            SessionDesc::default()
+                .session_settings
+                .video
+                .encode_bitrate_mbs
+        );
+        assert_eq!(restored_session.locale, "en");
+
+        assert!(journal.0.rollback_json(2).is_err());
+    }
+}
@@ -1,27 +1,88 @@
-use alvr_common::prelude::*;
+mod journal;
+mod scripting;
+
+pub use journal::{JournalEntrySummary, SessionChange};
+
+use alvr_common::{parking_lot::Mutex, prelude::*};
 use alvr_events::EventType;
-use alvr_session::{ClientConnectionDesc, SessionDesc, Settings};
+use alvr_session::{ClientConnectionDesc, Preset, SessionDesc, Settings};
 use alvr_sockets::{AudioDevicesList, ClientListAction, GpuVendor, PathSegment};
 use cpal::traits::{DeviceTrait, HostTrait};
+use journal::SessionJournal;
+use scripting::{ScriptCallback, ScriptContext};
 use serde_json as json;
 use std::{
     collections::{hash_map::Entry, HashMap, HashSet},
     fs,
     ops::{Deref, DerefMut},
     path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use tokio::sync::Notify;
 use wgpu::Adapter;
 
-fn save_session(session: &SessionDesc, path: &Path) -> StrResult {
-    fs::write(path, json::to_string_pretty(session).map_err(err!())?).map_err(err!())
+fn save_session(session: &SessionDesc, path: &Path, journal: &SessionJournal) -> StrResult {
+    fs::write(path, json::to_string_pretty(session).map_err(err!())?).map_err(err!())?;
+
+    // The history is not essential, the session has been saved anyway
+    if let Err(e) = journal.record(session) {
+        warn!("Failed to record session history: {e}");
+    }
+
+    Ok(())
+}
+
+// "session_settings.video.encode_bitrate_mbs" -> sessionSettings, video, encodeBitrateMbs
+pub fn parse_session_path(path: &str) -> Vec<PathSegment> {
+    path.split('.')
+        .map(|segment| {
+            if let Ok(index) = segment.parse() {
+                PathSegment::Index(index)
+            } else {
+                // snake_case names are converted to the camelCase used by the session JSON
+                let mut name = String::new();
+                let mut capitalize = false;
+                for c in segment.chars() {
+                    if c == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        name.extend(c.to_uppercase());
+                        capitalize = false;
+                    } else {
+                        name.push(c);
+                    }
+                }
+
+                PathSegment::Name(name)
+            }
+        })
+        .collect()
+}
+
+// Untrusted clients announce themselves every second. Requests of clients that stopped announcing
+// are forgotten
+const PAIRING_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+// Any device on the network can announce itself, the number of waiting requests is limited
+const MAX_PAIRING_REQUESTS: usize = 64;
+
+// Announced by an untrusted client together with the pairing code it displays
+struct PairingRequest {
+    hostname: String,
+    certificate_fingerprint: String,
+    last_announce: Instant,
+    // set if more than one certificate announced the same code. The code could have been sniffed
+    // by another device on the network, so it cannot be used anymore.
+    conflicting: bool,
 }
 
 // SessionDesc wrapper that saves settings.json and session.json on destruction.
 pub struct SessionLock<'a> {
     session_desc: &'a mut SessionDesc,
     session_path: &'a Path,
+    journal: &'a SessionJournal,
     settings: &'a mut Settings,
+    revision: &'a mut u64,
 }
 
 impl Deref for SessionLock<'_> {
@@ -39,8 +100,9 @@ impl DerefMut for SessionLock<'_> {
 
 impl Drop for SessionLock<'_> {
     fn drop(&mut self) {
-        save_session(self.session_desc, self.session_path).unwrap();
+        save_session(self.session_desc, self.session_path, self.journal).unwrap();
         *self.settings = self.session_desc.to_settings();
+        *self.revision += 1;
         alvr_events::send_event(EventType::SessionUpdated); // deprecated
         alvr_events::send_event(EventType::Session(Box::new(self.session_desc.clone())));
     }
@@ -54,9 +116,17 @@ impl Drop for SessionLock<'_> {
 pub struct ServerDataManager {
     session: SessionDesc,
     settings: Settings,
+    // Incremented on every change of the session. Used to detect concurrent writes. It starts from
+    // the server start time in microseconds, so a revision read before a restart is not reused
+    session_revision: u64,
     session_path: PathBuf,
+    session_journal: SessionJournal,
     script_engine: rhai::Engine,
+    script_context: Arc<Mutex<ScriptContext>>,
+    script_callbacks: Vec<ScriptCallback>,
     gpu_adapters: Vec<Adapter>,
+    // The hashmap key is the pairing code
+    pairing_requests: HashMap<String, PairingRequest>,
 }
 
 impl ServerDataManager {
@@ -64,6 +134,8 @@ impl ServerDataManager {
         let config_dir = session_path.parent().unwrap();
         fs::create_dir_all(config_dir).ok();
 
+        let session_journal = SessionJournal::new(&config_dir.join("session_history"));
+
         let session_desc = match fs::read_to_string(&session_path) {
             Ok(session_string) => {
                 let json_value = json::from_str::<json::Value>(&session_string).unwrap();
@@ -86,7 +158,7 @@ impl ServerDataManager {
                             ),
                         }
                         // not essential, but useful to avoid duplicated errors
-                        save_session(&session_desc, session_path).ok();
+                        save_session(&session_desc, session_path, &session_journal).ok();
 
                         session_desc
                     }
@@ -95,6 +167,15 @@ impl ServerDataManager {
             Err(_) => SessionDesc::default(),
         };
 
+        // The session could have been edited by hand while the server was not running
+        if let Err(e) = session_journal.record(&session_desc) {
+            warn!("Failed to record session history: {e}");
+        }
+
+        for diagnostic in session_desc.validate() {
+            warn!("Setting {}: {}", diagnostic.path, diagnostic.message);
+        }
+
         let gpu_adapters = {
             let instance = wgpu::Instance::new(wgpu::Backends::VULKAN);
 
@@ -103,14 +184,23 @@ impl ServerDataManager {
                 .collect()
         };
 
-        let script_engine = rhai::Engine::new();
+        let script_context = Arc::new(Mutex::new(ScriptContext::default()));
+        let script_engine = scripting::create_engine(Arc::clone(&script_context));
 
         Self {
             session: session_desc.clone(),
             settings: session_desc.to_settings(),
+            session_revision: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_micros() as u64)
+                .unwrap_or_default(),
             session_path: session_path.to_owned(),
+            session_journal,
             script_engine,
+            script_context,
+            script_callbacks: vec![],
             gpu_adapters,
+            pairing_requests: HashMap::new(),
         }
     }
 
@@ -123,43 +213,120 @@ impl ServerDataManager {
         SessionLock {
             session_desc: &mut self.session,
             session_path: &self.session_path,
+            journal: &self.session_journal,
             settings: &mut self.settings,
+            revision: &mut self.session_revision,
         }
     }
 
+    pub fn session_revision(&self) -> u64 {
+        self.session_revision
+    }
+
+    // Sorted from the newest
+    pub fn session_history(&self) -> Vec<JournalEntrySummary> {
+        self.session_journal.entries()
+    }
+
+    // Values changed from the revision "from" to the revision "to", or to the current session if
+    // "to" is None
+    pub fn session_history_diff(
+        &self,
+        from: u64,
+        to: Option<u64>,
+    ) -> StrResult<Vec<SessionChange>> {
+        let from_json = self.session_journal.session_json(from)?;
+        let to_json = match to {
+            Some(to) => self.session_journal.session_json(to)?,
+            None => json::to_value(&self.session).map_err(err!())?,
+        };
+
+        Ok(journal::diff_sessions(&from_json, &to_json))
+    }
+
+    // Only session_settings and the profiles are restored, the client list is left untouched. The
+    // rollback is recorded as a new revision, so it can be reverted too.
+    pub fn rollback_session(&mut self, id: u64) -> StrResult {
+        let session_json = self.session_journal.rollback_json(id)?;
+
+        self.session_mut().merge_from_json(&session_json)
+    }
+
     pub fn settings(&self) -> &Settings {
         &self.settings
     }
 
+    // Settings resolved with the profile assigned to the client
+    pub fn client_settings(&self, hostname: &str) -> Settings {
+        self.session.to_client_settings(hostname)
+    }
+
     pub fn client_list(&self) -> &HashMap<String, ClientConnectionDesc> {
         &self.session.client_connections
     }
 
-    // Note: "value" can be any session subtree, in json format.
-    pub fn set_single_value(&mut self, path: Vec<PathSegment>, value: &str) -> StrResult {
+    // Note: "value" can be any session subtree, in json format. The path uses the JSON field names.
+    pub fn set_single_value(&mut self, path: Vec<PathSegment>, value: json::Value) -> StrResult {
+        if let Some((PathSegment::Name(root), settings_path)) = path.split_first() {
+            if root == "sessionSettings" {
+                let settings_path = settings_path
+                    .iter()
+                    .map(|segment| match segment {
+                        PathSegment::Name(name) => name.clone(),
+                        PathSegment::Index(index) => index.to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                alvr_session::validate_session_settings_value(&settings_path, &value)?;
+            }
+        }
+
         let mut session_json = serde_json::to_value(self.session.clone()).map_err(err!())?;
 
         let mut session_ref = &mut session_json;
         for segment in path {
             session_ref = match segment {
-                PathSegment::Name(name) => session_ref.get_mut(name).ok_or_else(enone!())?,
-                PathSegment::Index(index) => session_ref.get_mut(index).ok_or_else(enone!())?,
+                PathSegment::Name(name) => session_ref
+                    .get_mut(&name)
+                    .ok_or_else(|| format!("Field {name} not found"))?,
+                PathSegment::Index(index) => session_ref
+                    .get_mut(index)
+                    .ok_or_else(|| format!("Index {index} not found"))?,
             };
         }
 
-        *session_ref = serde_json::from_str(value).map_err(err!())?;
+        *session_ref = value;
 
         // session_json has been updated
         self.session = serde_json::from_value(session_json).map_err(err!())?;
         self.settings = self.session.to_settings();
+        self.session_revision += 1;
 
-        save_session(&self.session, &self.session_path).unwrap();
+        save_session(&self.session, &self.session_path, &self.session_journal).unwrap();
         alvr_events::send_event(EventType::Session(Box::new(self.session.clone())));
 
         Ok(())
     }
 
-    pub fn execute_script(&self, code: &str) -> StrResult<String> {
+    // Path like "session_settings.video.encode_bitrate_mbs". Only values inside session_settings
+    // can be set this way.
+    pub fn set_session_settings_value(&mut self, path: &str, value: json::Value) -> StrResult {
+        let path = parse_session_path(path);
+        if !matches!(path.first(), Some(PathSegment::Name(name)) if name == "sessionSettings") {
+            return fmt_e!("Only values inside session_settings can be set");
+        }
+
+        self.set_single_value(path, value)
+    }
+
+    // Callbacks registered by the script with on() stay active until clear_script_callbacks()
+    pub fn execute_script(&mut self, code: &str) -> StrResult<String> {
+        let ast = self
+            .script_engine
+            .compile(code)
+            .map_err(|e| e.to_string())?;
+
+        self.script_context.lock().session_json = json::to_value(&self.session).map_err(err!())?;
+
         // Note: the scope is recreated every time to avoid cross-invocation interference
         let mut scope = rhai::Scope::new();
         scope.push_constant_dynamic(
@@ -167,10 +334,80 @@ impl ServerDataManager {
             rhai::serde::to_dynamic(self.session.clone()).unwrap(),
         );
 
-        self.script_engine
-            .eval_with_scope::<rhai::Dynamic>(&mut scope, code)
-            .map(|d| d.to_string())
-            .map_err(|e| e.to_string())
+        let res = self
+            .script_engine
+            .eval_ast_with_scope::<rhai::Dynamic>(&mut scope, &ast);
+
+        self.apply_script_requests(&Arc::new(ast));
+
+        res.map(|d| d.to_string()).map_err(|e| e.to_string())
+    }
+
+    fn apply_script_requests(&mut self, ast: &Arc<rhai::AST>) {
+        let (new_callbacks, settings_changes) = {
+            let mut context = self.script_context.lock();
+            (
+                std::mem::take(&mut context.new_callbacks),
+                std::mem::take(&mut context.settings_changes),
+            )
+        };
+
+        for (event_id, function) in new_callbacks {
+            self.script_callbacks.push(ScriptCallback {
+                event_id,
+                function,
+                ast: Arc::clone(ast),
+            });
+        }
+
+        for (path, value) in settings_changes {
+            if let Err(e) = self.set_session_settings_value(&path, value) {
+                warn!("Script failed to set {path}: {e}");
+            }
+        }
+    }
+
+    pub fn script_callback_events(&self) -> Vec<String> {
+        self.script_callbacks
+            .iter()
+            .map(|callback| callback.event_id.clone())
+            .collect()
+    }
+
+    pub fn has_script_callbacks(&self, event_id: &str) -> bool {
+        self.script_callbacks
+            .iter()
+            .any(|callback| callback.event_id == event_id)
+    }
+
+    // event_json has the same layout as the events sent to the dashboard: {"id": ..., "data": ...}
+    pub fn run_script_callbacks(&mut self, event_json: &json::Value) {
+        let event_id = event_json["id"].as_str().unwrap_or_default();
+        let event_data = rhai::serde::to_dynamic(&event_json["data"]).unwrap_or_default();
+
+        let callbacks = self
+            .script_callbacks
+            .iter()
+            .filter(|callback| callback.event_id == event_id)
+            .map(|callback| (callback.function.clone(), Arc::clone(&callback.ast)))
+            .collect::<Vec<_>>();
+
+        for (function, ast) in callbacks {
+            self.script_context.lock().session_json =
+                json::to_value(&self.session).unwrap_or_default();
+
+            let res =
+                function.call::<rhai::Dynamic>(&self.script_engine, &ast, (event_data.clone(),));
+            if let Err(e) = res {
+                warn!("Script callback for {event_id} failed: {e}");
+            }
+
+            self.apply_script_requests(&ast);
+        }
+    }
+
+    pub fn clear_script_callbacks(&mut self) {
+        self.script_callbacks.clear();
     }
 
     pub fn get_gpu_vendor(&self) -> GpuVendor {
@@ -229,20 +466,43 @@ impl ServerDataManager {
 
         let mut updated = false;
         match action {
-            ClientListAction::AddIfMissing { display_name } => {
+            ClientListAction::AddIfMissing {
+                display_name,
+                certificate_fingerprint,
+            } => {
                 if let Entry::Vacant(new_entry) = maybe_client_entry {
                     let client_connection_desc = ClientConnectionDesc {
                         trusted: false,
                         manual_ips: HashSet::new(),
                         display_name,
+                        certificate_fingerprint,
+                        profile: None,
                     };
                     new_entry.insert(client_connection_desc);
 
                     updated = true;
                 }
             }
+            ClientListAction::PinCertificate(fingerprint) => {
+                if let Entry::Occupied(mut entry) = maybe_client_entry {
+                    let client_connection_ref = entry.get_mut();
+                    let can_pin = !client_connection_ref.trusted
+                        || client_connection_ref.certificate_fingerprint.is_none();
+                    if can_pin
+                        && client_connection_ref.certificate_fingerprint.as_ref()
+                            != Some(&fingerprint)
+                    {
+                        client_connection_ref.certificate_fingerprint = Some(fingerprint);
+
+                        updated = true;
+                    }
+                }
+            }
             ClientListAction::TrustAndMaybeAddIp(maybe_ip) => {
                 if let Entry::Occupied(mut entry) = maybe_client_entry {
+                    self.pairing_requests
+                        .retain(|_, request| request.hostname != *entry.key());
+
                     let client_connection_ref = entry.get_mut();
                     client_connection_ref.trusted = true;
                     if let Some(ip) = maybe_ip {
@@ -253,6 +513,14 @@ impl ServerDataManager {
                 }
                 // else: never happens. The function must be called with AddIfMissing{} first
             }
+            ClientListAction::AddIp(ip) => {
+                if let Entry::Occupied(mut entry) = maybe_client_entry {
+                    let client_connection_ref = entry.get_mut();
+                    if client_connection_ref.trusted {
+                        updated = client_connection_ref.manual_ips.insert(ip);
+                    }
+                }
+            }
             ClientListAction::RemoveIpOrEntry(maybe_ip) => {
                 if let Entry::Occupied(mut entry) = maybe_client_entry {
                     if let Some(ip) = maybe_ip {
@@ -264,12 +532,23 @@ impl ServerDataManager {
                     updated = true;
                 }
             }
+            ClientListAction::SetProfile(profile) => {
+                if let Entry::Occupied(mut entry) = maybe_client_entry {
+                    let client_connection_ref = entry.get_mut();
+                    if client_connection_ref.profile != profile {
+                        client_connection_ref.profile = profile;
+
+                        updated = true;
+                    }
+                }
+            }
         }
 
         if updated {
             self.session.client_connections = client_connections;
+            self.session_revision += 1;
 
-            save_session(&self.session, &self.session_path).unwrap();
+            save_session(&self.session, &self.session_path, &self.session_journal).unwrap();
             alvr_events::send_event(EventType::SessionUpdated); // deprecated
             alvr_events::send_event(EventType::Session(Box::new(self.session.clone())));
 
@@ -278,4 +557,152 @@ impl ServerDataManager {
             }
         }
     }
+
+    pub fn add_pairing_request(
+        &mut self,
+        pairing_code: String,
+        hostname: String,
+        certificate_fingerprint: String,
+    ) {
+        self.remove_expired_pairing_requests();
+
+        let pairing_requests_count = self.pairing_requests.len();
+        match self.pairing_requests.entry(pairing_code) {
+            Entry::Occupied(mut entry) => {
+                let request = entry.get_mut();
+                if request.hostname != hostname
+                    || request.certificate_fingerprint != certificate_fingerprint
+                {
+                    request.conflicting = true;
+                }
+                request.last_announce = Instant::now();
+            }
+            Entry::Vacant(entry) => {
+                if pairing_requests_count >= MAX_PAIRING_REQUESTS {
+                    warn!("Too many clients are waiting for pairing. Ignoring {hostname}");
+                    return;
+                }
+
+                entry.insert(PairingRequest {
+                    hostname,
+                    certificate_fingerprint,
+                    last_announce: Instant::now(),
+                    conflicting: false,
+                });
+            }
+        }
+    }
+
+    fn remove_expired_pairing_requests(&mut self) {
+        self.pairing_requests
+            .retain(|_, request| request.last_announce.elapsed() < PAIRING_REQUEST_TIMEOUT);
+    }
+
+    // Trust the client that announced the pairing code and pin its certificate. The code can be
+    // used only once.
+    pub fn pair_client(
+        &mut self,
+        pairing_code: &str,
+        update_notifier: Option<&Notify>,
+    ) -> StrResult {
+        self.remove_expired_pairing_requests();
+
+        let request = self
+            .pairing_requests
+            .remove(pairing_code)
+            .ok_or_else(|| format!("No client is waiting for pairing code {pairing_code}"))?;
+
+        if request.conflicting {
+            return fmt_e!("Pairing code {pairing_code} was announced by more than one client");
+        }
+        if !self
+            .session
+            .client_connections
+            .contains_key(&request.hostname)
+        {
+            return fmt_e!("Client {} has been removed", request.hostname);
+        }
+
+        self.update_client_list(
+            request.hostname.clone(),
+            ClientListAction::PinCertificate(request.certificate_fingerprint),
+            None,
+        );
+        self.update_client_list(
+            request.hostname,
+            ClientListAction::TrustAndMaybeAddIp(None),
+            update_notifier,
+        );
+
+        Ok(())
+    }
+
+    // Only the values contained in the preset are changed
+    pub fn apply_preset(&mut self, preset: &Preset) -> StrResult {
+        let session_settings = preset.apply(&self.session.session_settings)?;
+        self.session_mut().session_settings = session_settings;
+
+        Ok(())
+    }
+
+    // Store a copy of the current session_settings as a named profile
+    pub fn save_profile(&mut self, name: String) {
+        let session_settings = self.session.session_settings.clone();
+        self.session_mut().profiles.insert(name, session_settings);
+    }
+
+    // Replace session_settings with the content of the profile
+    pub fn load_profile(&mut self, name: &str) -> StrResult {
+        let profile = self
+            .session
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("Profile {name} not found"))?;
+        self.session_mut().session_settings = profile;
+
+        Ok(())
+    }
+
+    // Clients that use the profile go back to session_settings
+    pub fn remove_profile(&mut self, name: &str) -> StrResult {
+        if !self.session.profiles.contains_key(name) {
+            return fmt_e!("Profile {name} not found");
+        }
+
+        let mut session = self.session_mut();
+        session.profiles.remove(name);
+        for client in session.client_connections.values_mut() {
+            if client.profile.as_deref() == Some(name) {
+                client.profile = None;
+            }
+        }
+
+        Ok(())
+    }
+
+    // The new profile is used starting from the next connection of the client
+    pub fn set_client_profile(
+        &mut self,
+        hostname: String,
+        profile: Option<String>,
+        update_notifier: Option<&Notify>,
+    ) -> StrResult {
+        if !self.session.client_connections.contains_key(&hostname) {
+            return fmt_e!("Client {hostname} not found");
+        }
+        if let Some(name) = &profile {
+            if !self.session.profiles.contains_key(name) {
+                return fmt_e!("Profile {name} not found");
+            }
+        }
+
+        self.update_client_list(
+            hostname,
+            ClientListAction::SetProfile(profile),
+            update_notifier,
+        );
+
+        Ok(())
+    }
 }
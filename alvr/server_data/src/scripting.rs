@@ -0,0 +1,89 @@
+use crate::parse_session_path;
+use alvr_common::{parking_lot::Mutex, prelude::*};
+use alvr_sockets::PathSegment;
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, AST};
+use serde_json as json;
+use std::sync::Arc;
+
+// Limits the time a script can hold the session lock
+const MAX_SCRIPT_OPERATIONS: u64 = 100_000;
+// Limit the memory a script can allocate and the recursion depth
+const MAX_STRING_SIZE: usize = 64 * 1024;
+const MAX_ARRAY_SIZE: usize = 10_000;
+const MAX_MAP_SIZE: usize = 10_000;
+const MAX_CALL_LEVELS: usize = 32;
+
+pub struct ScriptCallback {
+    pub event_id: String,
+    pub function: FnPtr,
+    // The callback can use functions and closures defined in the script
+    pub ast: Arc<AST>,
+}
+
+// Shared between the functions registered in the engine and ServerDataManager. Scripts cannot
+// access the session directly, changes are collected and applied after the script returns.
+#[derive(Default)]
+pub struct ScriptContext {
+    // Snapshot of the session, refreshed before running a script
+    pub session_json: json::Value,
+    pub new_callbacks: Vec<(String, FnPtr)>,
+    pub settings_changes: Vec<(String, json::Value)>,
+}
+
+fn get_setting(session_json: &json::Value, path: &str) -> Result<Dynamic, Box<EvalAltResult>> {
+    let mut value_ref = session_json;
+    for segment in parse_session_path(path) {
+        let maybe_value = match segment {
+            PathSegment::Name(name) => value_ref.get(name),
+            PathSegment::Index(index) => value_ref.get(index),
+        };
+        value_ref = maybe_value.ok_or_else(|| format!("Setting {path} not found"))?;
+    }
+
+    rhai::serde::to_dynamic(value_ref)
+}
+
+// Functions available to the scripts:
+// on(event_id, callback): call the callback every time the event is sent, with the event data
+// get_setting(path): value of the session, like get_setting("session_settings.video.preferred_fps")
+// set_setting(path, value): change a value of session_settings, after validation
+pub fn create_engine(context: Arc<Mutex<ScriptContext>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_SCRIPT_OPERATIONS);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.set_max_array_size(MAX_ARRAY_SIZE);
+    engine.set_max_map_size(MAX_MAP_SIZE);
+    engine.set_max_call_levels(MAX_CALL_LEVELS);
+    engine.on_print(|text| info!("[script] {text}"));
+    engine.on_debug(|text, _, _| debug!("[script] {text}"));
+
+    {
+        let context = Arc::clone(&context);
+        engine.register_fn("on", move |event_id: &str, callback: FnPtr| {
+            context
+                .lock()
+                .new_callbacks
+                .push((event_id.to_owned(), callback));
+        });
+    }
+    {
+        let context = Arc::clone(&context);
+        engine.register_fn("get_setting", move |path: &str| {
+            get_setting(&context.lock().session_json, path)
+        });
+    }
+    engine.register_fn(
+        "set_setting",
+        move |path: &str, value: Dynamic| -> Result<(), Box<EvalAltResult>> {
+            let value = rhai::serde::from_dynamic(&value)?;
+            context
+                .lock()
+                .settings_changes
+                .push((path.to_owned(), value));
+
+            Ok(())
+        },
+    );
+
+    engine
+}
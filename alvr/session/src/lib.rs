@@ -1,6 +1,12 @@
+mod migration;
+mod presets;
 mod settings;
+mod validation;
 
+pub use migration::*;
+pub use presets::*;
 pub use settings::*;
+pub use validation::*;
 
 use alvr_common::{prelude::*, semver::Version, ALVR_VERSION};
 use serde::{Deserialize, Serialize};
@@ -17,12 +23,11 @@ pub type SessionSettings = settings::SettingsDefault;
 
 // This structure is used to store the minimum configuration data that ALVR driver needs to
 // initialize OpenVR before having the chance to communicate with a client. When a client is
-// connected, a new OpenvrConfig instance is generated, then the connection is accepted only if that
-// instance is equivalent to the one stored in the session, otherwise SteamVR is restarted.
+// connected, a new OpenvrConfig instance is generated, then the connection is accepted only if the
+// fields that cannot change at runtime are equal to the ones stored in the session, otherwise
+// SteamVR is restarted.
 // Other components (like the encoder, audio recorder) don't need this treatment and are initialized
 // dynamically.
-// todo: properties that can be set after the OpenVR initialization should be removed and set with
-// UpdateForStream.
 #[derive(Serialize, Deserialize, PartialEq, Default, Clone, Debug)]
 pub struct OpenvrConfig {
     pub universe_id: u64,
@@ -48,17 +53,6 @@ pub struct OpenvrConfig {
     pub use_10bit_encoder: bool,
     pub force_sw_encoding: bool,
     pub sw_thread_count: u32,
-    pub encode_bitrate_mbs: u64,
-    pub enable_adaptive_bitrate: bool,
-    pub bitrate_maximum: u64,
-    pub latency_target: u64,
-    pub latency_use_frametime: bool,
-    pub latency_target_maximum: u64,
-    pub latency_target_offset: i32,
-    pub latency_threshold: u64,
-    pub bitrate_up_rate: u64,
-    pub bitrate_down_rate: u64,
-    pub bitrate_light_load_threshold: f32,
     pub controllers_tracking_system_name: String,
     pub controllers_manufacturer_name: String,
     pub controllers_model_number: String,
@@ -72,33 +66,99 @@ pub struct OpenvrConfig {
     pub controllers_mode_idx: i32,
     pub controllers_enabled: bool,
     pub position_offset: [f32; 3],
-    pub steamvr_hmd_prediction_multiplier: f32,
-    pub steamvr_ctrl_prediction_multiplier: f32,
     pub linear_velocity_cutoff: f32,
     pub angular_velocity_cutoff: f32,
     pub position_offset_left: [f32; 3],
     pub rotation_offset_left: [f32; 3],
+    pub use_headset_tracking_system: bool,
+    // The foveation and color correction passes are created only if enabled at startup
+    pub enable_foveated_rendering: bool,
+    pub enable_color_correction: bool,
+    pub linux_async_reprojection: bool,
+    // Flattened, so the driver reads all the fields from the same JSON object
+    #[serde(flatten)]
+    pub live: OpenvrLiveConfig,
+}
+
+// Properties that are pushed to the running stream, without restarting SteamVR
+#[derive(Serialize, Deserialize, PartialEq, Default, Clone, Debug)]
+pub struct OpenvrLiveConfig {
+    pub encode_bitrate_mbs: u64,
+    pub enable_adaptive_bitrate: bool,
+    pub bitrate_maximum: u64,
+    pub latency_target: u64,
+    pub latency_use_frametime: bool,
+    pub latency_target_maximum: u64,
+    pub latency_target_offset: i32,
+    pub latency_threshold: u64,
+    pub bitrate_up_rate: u64,
+    pub bitrate_down_rate: u64,
+    pub bitrate_light_load_threshold: f32,
+    pub bitrate_use_network_estimate: bool,
+    pub steamvr_hmd_prediction_multiplier: f32,
+    pub steamvr_ctrl_prediction_multiplier: f32,
     pub haptics_intensity: f32,
     pub haptics_amplitude_curve: f32,
     pub haptics_min_duration: f32,
     pub haptics_low_duration_amplitude_multiplier: f32,
     pub haptics_low_duration_range: f32,
-    pub use_headset_tracking_system: bool,
-    pub enable_foveated_rendering: bool,
     pub foveation_center_size_x: f32,
     pub foveation_center_size_y: f32,
     pub foveation_center_shift_x: f32,
     pub foveation_center_shift_y: f32,
     pub foveation_edge_ratio_x: f32,
     pub foveation_edge_ratio_y: f32,
-    pub enable_color_correction: bool,
     pub brightness: f32,
     pub contrast: f32,
     pub saturation: f32,
     pub gamma: f32,
     pub sharpening: f32,
-    pub enable_fec: bool,
-    pub linux_async_reprojection: bool,
+}
+
+impl OpenvrConfig {
+    // Size of the encoded frame for one eye. It must match the calculation done by the foveated
+    // rendering pass of the driver and of the client.
+    pub fn encoded_eye_resolution(&self) -> (u32, u32) {
+        if !self.enable_foveated_rendering {
+            return (self.eye_resolution_width, self.eye_resolution_height);
+        }
+
+        let foveated_size = |eye_size: u32, center_size: f32, edge_ratio: f32| {
+            let eye_size = eye_size as f32;
+            let edge_size = eye_size - center_size * eye_size;
+            let center_size_aligned =
+                1. - (edge_size / (edge_ratio * 2.)).ceil() * (edge_ratio * 2.) / eye_size;
+            let foveation_scale = center_size_aligned + (1. - center_size_aligned) / edge_ratio;
+
+            // rounded to a multiple of 32 for the encoder
+            ((foveation_scale * eye_size) / 32.).ceil() as u32 * 32
+        };
+
+        (
+            foveated_size(
+                self.eye_resolution_width,
+                self.live.foveation_center_size_x,
+                self.live.foveation_edge_ratio_x,
+            ),
+            foveated_size(
+                self.eye_resolution_height,
+                self.live.foveation_center_size_y,
+                self.live.foveation_edge_ratio_y,
+            ),
+        )
+    }
+
+    // Live fields can change without restarting SteamVR, unless the new foveation parameters
+    // change the size of the encoded frames
+    pub fn requires_restart(&self, new_config: &OpenvrConfig) -> bool {
+        let config_with_new_live_fields = OpenvrConfig {
+            live: new_config.live.clone(),
+            ..self.clone()
+        };
+
+        config_with_new_live_fields != *new_config
+            || self.encoded_eye_resolution() != new_config.encoded_eye_resolution()
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -107,6 +167,12 @@ pub struct ClientConnectionDesc {
     pub display_name: String,
     pub manual_ips: HashSet<IpAddr>,
     pub trusted: bool,
+    // SHA-256 of the client certificate. Once the client is trusted it cannot change anymore.
+    #[serde(default)]
+    pub certificate_fingerprint: Option<String>,
+    // Name of an entry of SessionDesc::profiles. If None or missing, session_settings is used
+    #[serde(default)]
+    pub profile: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -121,6 +187,9 @@ pub struct SessionDesc {
     // The hashmap key is the hostname
     pub client_connections: HashMap<String, ClientConnectionDesc>,
     pub session_settings: SessionSettings,
+    // Named alternatives to session_settings, that can be assigned to single clients
+    #[serde(default)]
+    pub profiles: HashMap<String, SessionSettings>,
     pub advanced: bool,
 }
 
@@ -156,6 +225,7 @@ impl Default for SessionDesc {
             },
             client_connections: HashMap::new(),
             session_settings: settings::session_settings_default(),
+            profiles: HashMap::new(),
             advanced: false,
         }
     }
@@ -171,6 +241,9 @@ impl SessionDesc {
     // settings schema.
     pub fn merge_from_json(&mut self, json_value: &json::Value) -> StrResult {
         const SESSION_SETTINGS_STR: &str = "sessionSettings";
+        const PROFILES_STR: &str = "profiles";
+
+        let json_value = &migrate_session_json(json_value, SESSION_MIGRATIONS);
 
         if let Ok(session_desc) = json::from_value(json_value.clone()) {
             *self = session_desc;
@@ -180,6 +253,10 @@ impl SessionDesc {
         let old_session_json = json::to_value(&self).map_err(err!())?;
         let old_session_fields = old_session_json.as_object().ok_or_else(enone!())?;
 
+        let schema = settings::settings_schema(settings::session_settings_default());
+        let default_session_settings_json =
+            json::to_value(settings::session_settings_default()).unwrap();
+
         let maybe_session_settings_json =
             json_value
                 .get(SESSION_SETTINGS_STR)
@@ -187,15 +264,42 @@ impl SessionDesc {
                     extrapolate_session_settings_from_session_settings(
                         &old_session_json[SESSION_SETTINGS_STR],
                         new_session_settings_json,
-                        &settings::settings_schema(settings::session_settings_default()),
+                        &schema,
                     )
                 });
 
+        // Profiles are extrapolated one by one, like session_settings. Profiles that cannot be
+        // recovered are dropped.
+        let maybe_profiles = json_value
+            .get(PROFILES_STR)
+            .and_then(|profiles_json| profiles_json.as_object())
+            .map(|profiles_json| {
+                profiles_json
+                    .iter()
+                    .filter_map(|(name, new_profile_json)| {
+                        let old_profile_json = old_session_json[PROFILES_STR]
+                            .get(name)
+                            .unwrap_or(&default_session_settings_json);
+                        let profile_json = extrapolate_session_settings_from_session_settings(
+                            old_profile_json,
+                            new_profile_json,
+                            &schema,
+                        );
+
+                        json::from_value(profile_json)
+                            .ok()
+                            .map(|profile| (name.clone(), profile))
+                    })
+                    .collect::<HashMap<_, _>>()
+            });
+
         let new_fields = old_session_fields
             .iter()
             .map(|(name, json_field_value)| {
                 let new_json_field_value = if name == SESSION_SETTINGS_STR {
-                    json::to_value(settings::session_settings_default()).unwrap()
+                    default_session_settings_json.clone()
+                } else if name == PROFILES_STR {
+                    json_field_value.clone()
                 } else {
                     json_value.get(name).unwrap_or(json_field_value).clone()
                 };
@@ -205,6 +309,9 @@ impl SessionDesc {
         // Failure to extrapolate other session_desc fields is not notified.
         let mut session_desc_mut =
             json::from_value::<SessionDesc>(json::Value::Object(new_fields)).unwrap_or_default();
+        if let Some(profiles) = maybe_profiles {
+            session_desc_mut.profiles = profiles;
+        }
 
         match json::from_value::<SessionSettings>(maybe_session_settings_json.ok_or_else(enone!())?)
         {
@@ -221,71 +328,360 @@ impl SessionDesc {
         }
     }
 
-    // This function requires that settings enums with data have tag = "type" and content = "content", and
-    // enums without data do not have tag and content set.
+    // Invalid settings are replaced with the default ones instead of panicking. Use validate() to
+    // find out what is wrong
     pub fn to_settings(&self) -> Settings {
-        let session_settings_json = json::to_value(&self.session_settings).unwrap();
-        let schema = settings::settings_schema(settings::session_settings_default());
+        session_settings_to_settings(&self.session_settings)
+    }
+
+    // Settings of the profile assigned to the client. Falls back to session_settings if the client
+    // has no profile or the profile does not exist anymore.
+    pub fn client_session_settings(&self, hostname: &str) -> &SessionSettings {
+        self.client_connections
+            .get(hostname)
+            .and_then(|client| client.profile.as_ref())
+            .and_then(|profile| self.profiles.get(profile))
+            .unwrap_or(&self.session_settings)
+    }
 
-        if let Err(e) = json::from_value::<Settings>(json_session_settings_to_settings(
-            &session_settings_json,
-            &schema,
-        )) {
-            dbg!(e);
+    pub fn to_client_settings(&self, hostname: &str) -> Settings {
+        session_settings_to_settings(self.client_session_settings(hostname))
+    }
+
+    // Diagnostics for session_settings and all the profiles
+    pub fn validate(&self) -> Vec<SettingsDiagnostic> {
+        let mut diagnostics = validate_session_settings_json(
+            &json::to_value(&self.session_settings).unwrap_or_default(),
+            "sessionSettings",
+        );
+
+        let mut profile_names = self.profiles.keys().collect::<Vec<_>>();
+        profile_names.sort();
+        for name in profile_names {
+            diagnostics.extend(validate_session_settings_json(
+                &json::to_value(&self.profiles[name]).unwrap_or_default(),
+                &format!("profiles.{name}"),
+            ));
         }
-        json::from_value(json_session_settings_to_settings(
-            &session_settings_json,
-            &schema,
-        ))
-        .unwrap()
+
+        diagnostics
     }
 }
 
-// Current data extrapolation strategy: match both field name and value type exactly.
-// Integer bounds are not validated, if they do not match the schema, deserialization will fail and
-// all data is lost.
-// Future strategies: check if value respects schema constraints, fuzzy field name matching, accept
-// integer to float and float to integer, tree traversal.
+// This function requires that settings enums with data have tag = "type" and content = "content", and
+// enums without data do not have tag and content set.
+fn try_session_settings_to_settings(session_settings: &SessionSettings) -> StrResult<Settings> {
+    let session_settings_json = json::to_value(session_settings).map_err(err!())?;
+    let schema = settings::settings_schema(settings::session_settings_default());
+
+    json::from_value(json_session_settings_to_settings(
+        &session_settings_json,
+        &schema,
+    ))
+    .map_err(err!())
+}
+
+fn session_settings_to_settings(session_settings: &SessionSettings) -> Settings {
+    try_session_settings_to_settings(session_settings).unwrap_or_else(|e| {
+        error!("Invalid session settings, using the default settings: {e}");
+
+        // The default settings are checked by test_session_to_settings
+        try_session_settings_to_settings(&settings::session_settings_default()).unwrap()
+    })
+}
+
+// Data extrapolation strategies:
+// * Fields are matched by name. If the name is not found, a field with the same name written with
+//   a different case or separators, or with a small typo, is used. Then a field with the same name
+//   in another section is used, if it is unique (the setting has been moved).
+// * The value type must match, except for integers and floats that are converted into each other.
+//   Numbers are clamped into the schema bounds.
+// * Values that cannot be recovered keep the old value.
+// Renames and moves that cannot be detected this way must be listed in SESSION_MIGRATIONS.
 fn extrapolate_session_settings_from_session_settings(
     old_session_settings: &json::Value,
     new_session_settings: &json::Value,
     schema: &SchemaNode,
 ) -> json::Value {
-    match schema {
-        SchemaNode::Section { entries } => json::Value::Object(
-            entries
+    let mut orphan_values = HashMap::new();
+    collect_orphan_values(new_session_settings, schema, &mut orphan_values);
+
+    extrapolate_node(
+        old_session_settings,
+        new_session_settings,
+        schema,
+        &orphan_values,
+    )
+}
+
+// "encode_bitrate_mbs" and "encodeBitrateMbs" -> "encodebitratembs"
+fn normalize_field_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let value = (row[j + 1] + 1)
+                .min(row[j] + 1)
+                .min(diagonal + usize::from(a_char != *b_char));
+            diagonal = row[j + 1];
+            row[j + 1] = value;
+        }
+    }
+
+    row[b.len()]
+}
+
+// Short names are not matched by edit distance, they would match unrelated fields
+const MAX_FIELD_NAME_TYPOS: usize = 2;
+const MIN_FUZZY_FIELD_NAME_LENGTH: usize = 6;
+
+#[derive(Clone, Copy)]
+enum FieldNameMatch {
+    Exact,
+    // Different case or separators
+    Normalized,
+    Typos,
+}
+
+fn find_field_index(
+    fields: &[(&str, &json::Value)],
+    entry_name: &str,
+    strategy: FieldNameMatch,
+) -> Option<usize> {
+    let normalized_entry_name = normalize_field_name(entry_name);
+
+    match strategy {
+        FieldNameMatch::Exact => fields.iter().position(|(name, _)| *name == entry_name),
+        FieldNameMatch::Normalized => fields
+            .iter()
+            .position(|(name, _)| normalize_field_name(name) == normalized_entry_name),
+        FieldNameMatch::Typos => {
+            if entry_name.len() < MIN_FUZZY_FIELD_NAME_LENGTH {
+                return None;
+            }
+
+            let distances = fields
                 .iter()
-                .filter_map(|(field_name, maybe_data)| {
-                    maybe_data.as_ref().map(|data_schema| {
-                        let value_json =
-                            if let Some(new_value_json) = new_session_settings.get(field_name) {
-                                extrapolate_session_settings_from_session_settings(
+                .map(|(name, _)| edit_distance(&normalize_field_name(name), &normalized_entry_name))
+                .collect::<Vec<_>>();
+            let min_distance = *distances.iter().min()?;
+
+            // ambiguous matches are ignored
+            if min_distance <= MAX_FIELD_NAME_TYPOS
+                && distances.iter().filter(|d| **d == min_distance).count() == 1
+            {
+                distances.iter().position(|d| *d == min_distance)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+// Matches the schema entries of a section with the fields of the JSON object. Returns the matched
+// values and the fields that do not belong to any entry.
+fn match_section_fields<'a>(
+    json_object: &'a json::Value,
+    entry_names: &[&str],
+) -> (
+    HashMap<String, &'a json::Value>,
+    Vec<(&'a str, &'a json::Value)>,
+) {
+    let mut unmatched_fields = json_object
+        .as_object()
+        .map(|fields| {
+            fields
+                .iter()
+                .map(|(name, value)| (name.as_str(), value))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let mut matched_values = HashMap::new();
+
+    // Each strategy is applied to all entries before trying the next, less strict, one
+    for strategy in [
+        FieldNameMatch::Exact,
+        FieldNameMatch::Normalized,
+        FieldNameMatch::Typos,
+    ] {
+        for entry_name in entry_names {
+            if matched_values.contains_key(*entry_name) {
+                continue;
+            }
+
+            if let Some(idx) = find_field_index(&unmatched_fields, entry_name, strategy) {
+                let (_, value) = unmatched_fields.remove(idx);
+                matched_values.insert((*entry_name).to_owned(), value);
+            }
+        }
+    }
+
+    (matched_values, unmatched_fields)
+}
+
+fn section_entry_names<T>(entries: &[(String, Option<T>)]) -> Vec<&str> {
+    entries
+        .iter()
+        .filter(|(_, maybe_data)| maybe_data.is_some())
+        .map(|(name, _)| name.as_str())
+        .collect()
+}
+
+// Fields of new_session_settings that do not match any field of the schema, by normalized name
+fn collect_orphan_values(
+    new_session_settings: &json::Value,
+    schema: &SchemaNode,
+    orphan_values: &mut HashMap<String, Vec<json::Value>>,
+) {
+    match schema {
+        SchemaNode::Section { entries } => {
+            let (matched_values, unmatched_fields) =
+                match_section_fields(new_session_settings, &section_entry_names(entries));
+
+            for (field_name, maybe_data) in entries {
+                if let (Some(data_schema), Some(value)) =
+                    (maybe_data, matched_values.get(field_name))
+                {
+                    collect_orphan_values(value, &data_schema.content, orphan_values);
+                }
+            }
+            for (name, value) in unmatched_fields {
+                orphan_values
+                    .entry(normalize_field_name(name))
+                    .or_default()
+                    .push(value.clone());
+            }
+        }
+        SchemaNode::Choice { variants, .. } => {
+            for (variant_name, maybe_data) in variants {
+                if let (Some(data_schema), Some(value)) =
+                    (maybe_data, new_session_settings.get(variant_name))
+                {
+                    collect_orphan_values(value, &data_schema.content, orphan_values);
+                }
+            }
+        }
+        SchemaNode::Optional { content, .. } | SchemaNode::Switch { content, .. } => {
+            if let Some(value) = new_session_settings.get("content") {
+                collect_orphan_values(value, content, orphan_values);
+            }
+        }
+        SchemaNode::Array(array_schema) => {
+            for (idx, element_schema) in array_schema.iter().enumerate() {
+                if let Some(value) = new_session_settings.get(idx) {
+                    collect_orphan_values(value, element_schema, orphan_values);
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+// Returns None if the number cannot be represented with the schema type
+fn coerce_number(value: &json::Value, schema: &SchemaNode) -> Option<json::Value> {
+    // The bounds have different types for integers and floats, the JSON representation is shared
+    let schema_json = json::to_value(schema).ok()?;
+    let min = schema_json["content"]["min"].as_f64();
+    let max = schema_json["content"]["max"].as_f64();
+
+    let clamp = |number: f64| {
+        let number = min.map_or(number, |min| number.max(min));
+        max.map_or(number, |max| number.min(max))
+    };
+
+    match schema {
+        SchemaNode::Integer { .. } => {
+            if value.is_i64() || value.is_u64() {
+                // clamping through f64 would lose precision on big integers
+                let number = value.as_f64()?;
+                if clamp(number) == number {
+                    Some(value.clone())
+                } else {
+                    Some(json::json!(clamp(number).round() as i64))
+                }
+            } else {
+                let number = value.as_f64().filter(|number| number.is_finite())?;
+                Some(json::json!(clamp(number.round()) as i64))
+            }
+        }
+        SchemaNode::Float { .. } => {
+            let number = value.as_f64()?;
+            if clamp(number) == number {
+                Some(value.clone())
+            } else {
+                Some(json::json!(clamp(number)))
+            }
+        }
+        _ => None,
+    }
+}
+
+fn extrapolate_node(
+    old_session_settings: &json::Value,
+    new_session_settings: &json::Value,
+    schema: &SchemaNode,
+    orphan_values: &HashMap<String, Vec<json::Value>>,
+) -> json::Value {
+    match schema {
+        SchemaNode::Section { entries } => {
+            let (matched_values, _) =
+                match_section_fields(new_session_settings, &section_entry_names(entries));
+
+            json::Value::Object(
+                entries
+                    .iter()
+                    .filter_map(|(field_name, maybe_data)| {
+                        maybe_data.as_ref().map(|data_schema| {
+                            // Fields moved to another section are recognized by their name
+                            let maybe_new_value_json =
+                                matched_values.get(field_name).copied().or_else(|| {
+                                    match orphan_values
+                                        .get(&normalize_field_name(field_name))
+                                        .map(|values| values.as_slice())
+                                    {
+                                        Some([value]) => Some(value),
+                                        _ => None,
+                                    }
+                                });
+
+                            let value_json = if let Some(new_value_json) = maybe_new_value_json {
+                                extrapolate_node(
                                     &old_session_settings[field_name],
                                     new_value_json,
                                     &data_schema.content,
+                                    orphan_values,
                                 )
                             } else {
                                 old_session_settings[field_name].clone()
                             };
-                        (field_name.clone(), value_json)
+                            (field_name.clone(), value_json)
+                        })
                     })
-                })
-                .collect(),
-        ),
+                    .collect(),
+            )
+        }
 
         SchemaNode::Choice { variants, .. } => {
+            // variant names are compared ignoring the case
             let variant_json = new_session_settings
                 .get("variant")
-                .cloned()
-                .filter(|new_variant_json| {
-                    new_variant_json
-                        .as_str()
-                        .map(|variant_str| {
-                            variants
-                                .iter()
-                                .any(|(variant_name, _)| variant_str == variant_name)
+                .and_then(|new_variant_json| new_variant_json.as_str())
+                .and_then(|variant_str| {
+                    variants
+                        .iter()
+                        .find(|(variant_name, _)| {
+                            normalize_field_name(variant_name) == normalize_field_name(variant_str)
                         })
-                        .is_some()
+                        .map(|(variant_name, _)| json::Value::String(variant_name.clone()))
                 })
                 .unwrap_or_else(|| old_session_settings["variant"].clone());
 
@@ -295,10 +691,11 @@ fn extrapolate_session_settings_from_session_settings(
                     maybe_data.as_ref().map(|data_schema| {
                         let value_json =
                             if let Some(new_value_json) = new_session_settings.get(variant_name) {
-                                extrapolate_session_settings_from_session_settings(
+                                extrapolate_node(
                                     &old_session_settings[variant_name],
                                     new_value_json,
                                     &data_schema.content,
+                                    orphan_values,
                                 )
                             } else {
                                 old_session_settings[variant_name].clone()
@@ -322,10 +719,11 @@ fn extrapolate_session_settings_from_session_settings(
             let content_json = new_session_settings
                 .get("content")
                 .map(|new_content_json| {
-                    extrapolate_session_settings_from_session_settings(
+                    extrapolate_node(
                         &old_session_settings["content"],
                         new_content_json,
                         content,
+                        orphan_values,
                     )
                 })
                 .unwrap_or_else(|| old_session_settings["content"].clone());
@@ -346,10 +744,11 @@ fn extrapolate_session_settings_from_session_settings(
             let content_json = new_session_settings
                 .get("content")
                 .map(|new_content_json| {
-                    extrapolate_session_settings_from_session_settings(
+                    extrapolate_node(
                         &old_session_settings["content"],
                         new_content_json,
                         content,
+                        orphan_values,
                     )
                 })
                 .unwrap_or_else(|| old_session_settings["content"].clone());
@@ -368,20 +767,9 @@ fn extrapolate_session_settings_from_session_settings(
             }
         }
 
-        SchemaNode::Integer { .. } => {
-            if new_session_settings.is_i64() {
-                new_session_settings.clone()
-            } else {
-                old_session_settings.clone()
-            }
-        }
-
-        SchemaNode::Float { .. } => {
-            if new_session_settings.is_number() {
-                new_session_settings.clone()
-            } else {
-                old_session_settings.clone()
-            }
+        SchemaNode::Integer { .. } | SchemaNode::Float { .. } => {
+            coerce_number(new_session_settings, schema)
+                .unwrap_or_else(|| old_session_settings.clone())
         }
 
         SchemaNode::Text { .. } => {
@@ -393,27 +781,35 @@ fn extrapolate_session_settings_from_session_settings(
         }
 
         SchemaNode::Array(array_schema) => {
-            let array_vec = (0..array_schema.len())
-                .map(|idx| {
-                    new_session_settings
-                        .get(idx)
-                        .cloned()
-                        .unwrap_or_else(|| old_session_settings[idx].clone())
+            let array_vec = array_schema
+                .iter()
+                .enumerate()
+                .map(|(idx, element_schema)| {
+                    if let Some(new_element_json) = new_session_settings.get(idx) {
+                        extrapolate_node(
+                            &old_session_settings[idx],
+                            new_element_json,
+                            element_schema,
+                            orphan_values,
+                        )
+                    } else {
+                        old_session_settings[idx].clone()
+                    }
                 })
                 .collect();
             json::Value::Array(array_vec)
         }
-
         SchemaNode::Vector {
             default_element, ..
         } => {
             let element_json = new_session_settings
                 .get("element")
                 .map(|new_element_json| {
-                    extrapolate_session_settings_from_session_settings(
+                    extrapolate_node(
                         &old_session_settings["element"],
                         new_element_json,
                         default_element,
+                        orphan_values,
                     )
                 })
                 .unwrap_or_else(|| old_session_settings["element"].clone());
@@ -443,10 +839,11 @@ fn extrapolate_session_settings_from_session_settings(
             let value_json = new_session_settings
                 .get("value")
                 .map(|new_value_json| {
-                    extrapolate_session_settings_from_session_settings(
+                    extrapolate_node(
                         &old_session_settings["value"],
                         new_value_json,
                         default_value,
+                        orphan_values,
                     )
                 })
                 .unwrap_or_else(|| old_session_settings["value"].clone());
@@ -592,4 +989,232 @@ mod tests {
             .merge_from_json(&json::from_str(input_json_string).unwrap())
             .unwrap();
     }
+
+    #[test]
+    fn test_session_extrapolation_fuzzy_names() {
+        let mut session = SessionDesc::default();
+        session
+            .merge_from_json(&json::json!({
+                "sessionSettings": {
+                    "video": {
+                        "encode_bitrate_mbs": 100,
+                        "preferedFps": 90.0
+                    }
+                }
+            }))
+            .unwrap();
+
+        assert_eq!(session.session_settings.video.encode_bitrate_mbs, 100);
+        assert_eq!(session.session_settings.video.preferred_fps, 90.);
+    }
+
+    #[test]
+    fn test_session_extrapolation_numeric_coercion() {
+        let mut session = SessionDesc::default();
+        session
+            .merge_from_json(&json::json!({
+                "sessionSettings": {
+                    "video": {
+                        "encodeBitrateMbs": 80.0,
+                        "preferredFps": 90
+                    },
+                    "connection": {
+                        "maxSpectators": 100
+                    }
+                }
+            }))
+            .unwrap();
+
+        assert_eq!(session.session_settings.video.encode_bitrate_mbs, 80);
+        assert_eq!(session.session_settings.video.preferred_fps, 90.);
+        // clamped to the schema bounds
+        assert_eq!(session.session_settings.connection.max_spectators, 8);
+    }
+
+    #[test]
+    fn test_session_extrapolation_moved_field() {
+        let mut session = SessionDesc::default();
+        session
+            .merge_from_json(&json::json!({
+                "sessionSettings": {
+                    "connection": {
+                        "encodeBitrateMbs": 120
+                    }
+                }
+            }))
+            .unwrap();
+
+        assert_eq!(session.session_settings.video.encode_bitrate_mbs, 120);
+    }
+
+    #[test]
+    fn test_session_migrations() {
+        let migrations = [SessionMigration {
+            version: "19.0.0",
+            from: "sessionSettings.video.bitrate",
+            to: "sessionSettings.video.encodeBitrateMbs",
+        }];
+        let session_json = |server_version: &str| {
+            json::json!({
+                "serverVersion": server_version,
+                "sessionSettings": { "video": { "bitrate": 120 } }
+            })
+        };
+
+        let migrated_json = migrate_session_json(&session_json("18.2.3"), &migrations);
+        assert_eq!(
+            migrated_json["sessionSettings"]["video"],
+            json::json!({ "encodeBitrateMbs": 120 })
+        );
+
+        // the session has been saved by a version that already uses the new path
+        let migrated_json = migrate_session_json(&session_json("19.1.0"), &migrations);
+        assert_eq!(
+            migrated_json["sessionSettings"]["video"],
+            json::json!({ "bitrate": 120 })
+        );
+
+        let mut versions = SESSION_MIGRATIONS
+            .iter()
+            .map(|migration| Version::parse(migration.version).unwrap());
+        if let Some(mut previous_version) = versions.next() {
+            for version in versions {
+                assert!(version >= previous_version);
+                previous_version = version;
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_session_settings_value() {
+        let path = ["connection".to_owned(), "maxSpectators".to_owned()];
+
+        validate_session_settings_value(&path, &json::json!(2)).unwrap();
+        assert!(validate_session_settings_value(&path, &json::json!(9)).is_err());
+    }
+
+    #[test]
+    fn test_validate_session() {
+        let mut session = SessionDesc::default();
+        assert!(!session
+            .validate()
+            .iter()
+            .any(|diagnostic| diagnostic.severity == DiagnosticSeverity::Error));
+
+        session.session_settings.video.encode_bitrate_mbs = 300;
+        session.session_settings.video.adaptive_bitrate.enabled = true;
+        session
+            .session_settings
+            .video
+            .adaptive_bitrate
+            .content
+            .bitrate_maximum = 200;
+        assert!(session.validate().iter().any(|diagnostic| {
+            diagnostic.severity == DiagnosticSeverity::Warning
+                && diagnostic.path == "sessionSettings.video.encodeBitrateMbs"
+        }));
+    }
+
+    #[test]
+    fn test_apply_preset() {
+        let preset = Preset {
+            id: "test".into(),
+            name: "Test".into(),
+            description: "".into(),
+            session_settings: json::json!({
+                "video": {
+                    "encodeBitrateMbs": 100,
+                    "foveatedRendering": { "enabled": false }
+                }
+            }),
+        };
+
+        let session_settings = preset.apply(&settings::session_settings_default()).unwrap();
+
+        assert_eq!(session_settings.video.encode_bitrate_mbs, 100);
+        assert!(!session_settings.video.foveated_rendering.enabled);
+        // values missing in the preset are not changed
+        assert_eq!(session_settings.video.preferred_fps, 72.);
+    }
+
+    #[test]
+    fn test_bundled_presets() {
+        let presets_dir =
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../xtask/resources/presets");
+
+        let presets = load_presets(&presets_dir);
+        assert_eq!(presets.len(), 3);
+        for preset in presets {
+            preset.apply(&settings::session_settings_default()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_client_profile_settings() {
+        let mut session = SessionDesc::default();
+
+        let mut profile = settings::session_settings_default();
+        profile.video.encode_bitrate_mbs = 150;
+        session.profiles.insert("quest_pro".into(), profile);
+
+        session.client_connections.insert(
+            "client.alvr".into(),
+            ClientConnectionDesc {
+                display_name: "Quest Pro".into(),
+                manual_ips: HashSet::new(),
+                trusted: true,
+                certificate_fingerprint: None,
+                profile: Some("quest_pro".into()),
+            },
+        );
+
+        assert_eq!(
+            session
+                .to_client_settings("client.alvr")
+                .video
+                .encode_bitrate_mbs,
+            150
+        );
+        // clients without a profile use session_settings
+        assert_eq!(
+            session
+                .to_client_settings("other.client.alvr")
+                .video
+                .encode_bitrate_mbs,
+            session.to_settings().video.encode_bitrate_mbs
+        );
+    }
+
+    #[test]
+    fn test_openvr_config_restart() {
+        let config = OpenvrConfig {
+            eye_resolution_width: 1440,
+            eye_resolution_height: 1600,
+            enable_foveated_rendering: true,
+            live: OpenvrLiveConfig {
+                foveation_center_size_x: 0.4,
+                foveation_center_size_y: 0.35,
+                foveation_edge_ratio_x: 4.,
+                foveation_edge_ratio_y: 5.,
+                ..Default::default()
+            },
+            ..SessionDesc::default().openvr_config
+        };
+
+        // the driver reads the live fields together with the others
+        assert!(json::to_value(&config).unwrap()["brightness"].is_number());
+
+        let mut new_config = config.clone();
+        new_config.live.brightness = 0.5;
+        new_config.live.haptics_intensity = 2.;
+        new_config.live.foveation_center_shift_x = 0.2;
+        assert!(!config.requires_restart(&new_config));
+
+        new_config.live.foveation_center_size_x = 0.8;
+        assert!(config.requires_restart(&new_config));
+
+        let mut new_config = config.clone();
+        new_config.refresh_rate = 120;
+        assert!(config.requires_restart(&new_config));
+    }
 }
@@ -0,0 +1,80 @@
+// Settings that have been renamed or moved are listed in SESSION_MIGRATIONS, so their values are
+// not lost when upgrading. Renames that only change the case or fix a typo, and moves that keep
+// the same unique name, are already recovered by the session extrapolation.
+
+use alvr_common::semver::Version;
+use serde_json as json;
+
+pub struct SessionMigration {
+    // First server version that uses the new path
+    pub version: &'static str,
+    // Paths in the session JSON, like "sessionSettings.video.encodeBitrateMbs"
+    pub from: &'static str,
+    pub to: &'static str,
+}
+
+// Sorted by version. New entries are added at the end.
+pub const SESSION_MIGRATIONS: &[SessionMigration] = &[];
+
+fn take_value(json_value: &mut json::Value, path: &[&str]) -> Option<json::Value> {
+    let (last, parents) = path.split_last()?;
+
+    let mut parent = json_value;
+    for segment in parents {
+        parent = parent.get_mut(segment)?;
+    }
+
+    parent.as_object_mut()?.remove(*last)
+}
+
+// Missing parents are created. Existing values are not replaced.
+fn insert_value(json_value: &mut json::Value, path: &[&str], value: json::Value) {
+    let (last, parents) = match path.split_last() {
+        Some(split) => split,
+        None => return,
+    };
+
+    let mut parent = json_value;
+    for segment in parents {
+        let fields = match parent.as_object_mut() {
+            Some(fields) => fields,
+            None => return,
+        };
+        parent = fields
+            .entry(*segment)
+            .or_insert_with(|| json::Value::Object(json::Map::new()));
+    }
+
+    if let Some(fields) = parent.as_object_mut() {
+        fields.entry(*last).or_insert(value);
+    }
+}
+
+// Applies the migrations introduced after the server version that saved the session. If the
+// version is missing, all migrations are applied.
+pub fn migrate_session_json(
+    session_json: &json::Value,
+    migrations: &[SessionMigration],
+) -> json::Value {
+    let maybe_session_version = session_json["serverVersion"]
+        .as_str()
+        .and_then(|version| Version::parse(version).ok());
+
+    let mut session_json = session_json.clone();
+    for migration in migrations {
+        let is_newer = match (&maybe_session_version, Version::parse(migration.version)) {
+            (Some(session_version), Ok(version)) => version > *session_version,
+            _ => true,
+        };
+
+        if is_newer {
+            let from = migration.from.split('.').collect::<Vec<_>>();
+            if let Some(value) = take_value(&mut session_json, &from) {
+                let to = migration.to.split('.').collect::<Vec<_>>();
+                insert_value(&mut session_json, &to, value);
+            }
+        }
+    }
+
+    session_json
+}
@@ -0,0 +1,80 @@
+// Presets are JSON files in the presets folder that contain a subset of the session settings.
+// Applying a preset overwrites only the values it contains, using the same logic used to
+// extrapolate the session after a version upgrade.
+//
+// File layout: {"name": ..., "description": ..., "sessionSettings": {...}}
+// The file name without extension is the preset ID.
+
+use crate::{extrapolate_session_settings_from_session_settings, settings, SessionSettings};
+use alvr_common::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use std::{fs, path::Path};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Preset {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    // Same layout as SessionDesc::session_settings, but any field can be omitted
+    pub session_settings: json::Value,
+}
+
+impl Preset {
+    pub fn apply(&self, session_settings: &SessionSettings) -> StrResult<SessionSettings> {
+        let session_settings_json = extrapolate_session_settings_from_session_settings(
+            &json::to_value(session_settings).map_err(err!())?,
+            &self.session_settings,
+            &settings::settings_schema(settings::session_settings_default()),
+        );
+
+        json::from_value(session_settings_json).map_err(err!())
+    }
+}
+
+fn load_preset_file(path: &Path) -> StrResult<Preset> {
+    let mut preset: Preset =
+        json::from_str(&fs::read_to_string(path).map_err(err!())?).map_err(err!())?;
+    preset.id = path
+        .file_stem()
+        .ok_or_else(enone!())?
+        .to_string_lossy()
+        .into_owned();
+
+    Ok(preset)
+}
+
+// Presets sorted by ID. Files that cannot be parsed are skipped. A missing folder means there are
+// no presets.
+pub fn load_presets(presets_dir: &Path) -> Vec<Preset> {
+    let entries = match fs::read_dir(presets_dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut presets = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("json"))
+        .filter_map(|path| match load_preset_file(&path) {
+            Ok(preset) => Some(preset),
+            Err(e) => {
+                warn!("Invalid preset {}: {e}", path.display());
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    presets.sort_by(|a, b| a.id.cmp(&b.id));
+
+    presets
+}
+
+// The ID is looked up among the loaded presets, so it cannot be used to read other files
+pub fn load_preset(presets_dir: &Path, id: &str) -> StrResult<Preset> {
+    load_presets(presets_dir)
+        .into_iter()
+        .find(|preset| preset.id == id)
+        .ok_or_else(|| format!("Preset {id} not found"))
+}
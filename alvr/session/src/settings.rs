@@ -66,6 +66,9 @@ pub struct AdaptiveBitrateDesc {
 
     #[schema(advanced, min = 0., max = 1., step = 0.01)]
     pub bitrate_light_load_threshold: f32,
+
+    #[schema(advanced)]
+    pub use_network_estimate: bool,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Copy, Clone)]
@@ -423,28 +426,29 @@ pub enum SocketProtocol {
     },
 
     Tcp,
-}
 
-#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct DiscoveryConfig {
-    #[schema(advanced)]
-    pub auto_trust_clients: bool,
+    Quic,
 }
 
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ConnectionDesc {
-    pub client_discovery: Switch<DiscoveryConfig>,
+    pub client_discovery: bool,
 
     #[schema(advanced, min = 1024, max = 65535)]
     pub web_server_port: u16,
 
+    #[schema(advanced)]
+    pub web_server_loopback_only: bool,
+
     pub stream_protocol: SocketProtocol,
 
     #[schema(advanced)]
     pub stream_port: u16,
 
+    #[schema(min = 0, max = 8, step = 1)]
+    pub max_spectators: u32,
+
     #[schema(advanced)]
     pub aggressive_keyframe_resend: bool,
 
@@ -457,6 +461,11 @@ pub struct ConnectionDesc {
     #[schema(advanced)]
     pub enable_fec: bool,
 
+    // Maximum size of the stream packets, bigger messages are split. It must leave room for the
+    // IP and UDP headers within the network MTU
+    #[schema(advanced, min = 500, max = 65000)]
+    pub max_packet_size: u64,
+
     #[schema(advanced)]
     pub statistics_history_size: u64,
 }
@@ -486,6 +495,29 @@ pub enum LogLevel {
     Debug,
 }
 
+#[derive(SettingsSchema, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", tag = "type", content = "content")]
+pub enum LogFormat {
+    Text,
+    // One JSON object per line, with level, target module and connection ID
+    JsonLines,
+}
+
+#[derive(SettingsSchema, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LogFilesDesc {
+    pub format: LogFormat,
+    // A new file is started when the current one reaches this size
+    #[schema(min = 1, max = 1000, step = 1)]
+    pub max_file_size_mb: u64,
+    // A new file is started when the current one is older than this
+    #[schema(min = 1, max = 168, step = 1)]
+    pub max_file_age_hours: u64,
+    // Number of previous server sessions whose logs are kept
+    #[schema(min = 0, max = 100, step = 1)]
+    pub kept_sessions: u64,
+}
+
 #[derive(SettingsSchema, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ExtraDesc {
@@ -495,8 +527,13 @@ pub struct ExtraDesc {
     pub prompt_before_update: bool,
     pub update_channel: UpdateChannel,
     pub log_to_disk: bool,
+    #[schema(advanced)]
+    pub log_files: LogFilesDesc,
 
     pub log_button_presses: bool,
+    // Save the streams of each connection for offline debugging
+    #[schema(advanced)]
+    pub record_streams: bool,
     #[schema(advanced)]
     pub notification_level: LogLevel,
     #[schema(advanced)]
@@ -585,6 +622,7 @@ pub fn session_settings_default() -> SettingsDefault {
                     bitrate_up_rate: 1,
                     bitrate_down_rate: 3,
                     bitrate_light_load_threshold: 0.7,
+                    use_network_estimate: false,
                 },
             },
             seconds_from_vsync_to_photons: 0.005,
@@ -701,13 +739,9 @@ pub fn session_settings_default() -> SettingsDefault {
             extra_latency_mode: false,
         },
         connection: ConnectionDescDefault {
-            client_discovery: SwitchDefault {
-                enabled: true,
-                content: DiscoveryConfigDefault {
-                    auto_trust_clients: cfg!(debug_assertions),
-                },
-            },
+            client_discovery: true,
             web_server_port: 8082,
+            web_server_loopback_only: false,
             stream_protocol: SocketProtocolDefault {
                 variant: if !cfg!(target_os = "linux") {
                     SocketProtocolDefaultVariant::Udp
@@ -719,10 +753,12 @@ pub fn session_settings_default() -> SettingsDefault {
                 },
             },
             stream_port: 9944,
+            max_spectators: 0,
             aggressive_keyframe_resend: false,
             on_connect_script: "".into(),
             on_disconnect_script: "".into(),
             enable_fec: true,
+            max_packet_size: 1400,
             statistics_history_size: 1024,
         },
         extra: ExtraDescDefault {
@@ -742,7 +778,16 @@ pub fn session_settings_default() -> SettingsDefault {
                 },
             },
             log_to_disk: cfg!(debug_assertions),
+            log_files: LogFilesDescDefault {
+                format: LogFormatDefault {
+                    variant: LogFormatDefaultVariant::Text,
+                },
+                max_file_size_mb: 50,
+                max_file_age_hours: 24,
+                kept_sessions: 10,
+            },
             log_button_presses: false,
+            record_streams: false,
             notification_level: LogLevelDefault {
                 variant: if cfg!(debug_assertions) {
                     LogLevelDefaultVariant::Info
@@ -0,0 +1,326 @@
+// Validation of session settings in their JSON form. It catches the errors that deserialization
+// cannot catch (bounds, unknown variants) and the constraints between different settings.
+// Diagnostics are addressed with the path of the value inside the session JSON, like
+// "sessionSettings.video.encodeBitrateMbs".
+
+use crate::settings;
+use alvr_common::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json as json;
+use settings_schema::SchemaNode;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticSeverity {
+    // The settings cannot be used
+    Error,
+    // The settings can be used but are probably not what the user wants
+    Warning,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SettingsDiagnostic {
+    pub severity: DiagnosticSeverity,
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct Validator {
+    diagnostics: Vec<SettingsDiagnostic>,
+}
+
+impl Validator {
+    fn report(&mut self, severity: DiagnosticSeverity, path: &str, message: String) {
+        self.diagnostics.push(SettingsDiagnostic {
+            severity,
+            path: path.to_owned(),
+            message,
+        });
+    }
+
+    fn error(&mut self, path: &str, message: String) {
+        self.report(DiagnosticSeverity::Error, path, message)
+    }
+
+    fn warning(&mut self, path: &str, message: String) {
+        self.report(DiagnosticSeverity::Warning, path, message)
+    }
+
+    // Fields missing from the JSON are not reported, the value can be a partial session_settings
+    fn check_node(&mut self, value: &json::Value, schema: &SchemaNode, path: &str) {
+        match schema {
+            SchemaNode::Section { entries } => {
+                for (field_name, maybe_data) in entries {
+                    if let (Some(data_schema), Some(field_value)) =
+                        (maybe_data, value.get(field_name))
+                    {
+                        self.check_node(
+                            field_value,
+                            &data_schema.content,
+                            &format!("{path}.{field_name}"),
+                        );
+                    }
+                }
+            }
+            SchemaNode::Choice { variants, .. } => {
+                if let Some(variant_json) = value.get("variant") {
+                    let known_variant = variants.iter().any(|(variant_name, _)| {
+                        Some(variant_name.as_str()) == variant_json.as_str()
+                    });
+                    if !known_variant {
+                        let variant_names = variants
+                            .iter()
+                            .map(|(variant_name, _)| variant_name.as_str())
+                            .collect::<Vec<_>>();
+                        self.error(
+                            &format!("{path}.variant"),
+                            format!(
+                                "Unknown variant {variant_json}, expected one of {variant_names:?}"
+                            ),
+                        );
+                    }
+                }
+
+                for (variant_name, maybe_data) in variants {
+                    if let (Some(data_schema), Some(variant_value)) =
+                        (maybe_data, value.get(variant_name))
+                    {
+                        self.check_node(
+                            variant_value,
+                            &data_schema.content,
+                            &format!("{path}.{variant_name}"),
+                        );
+                    }
+                }
+            }
+            SchemaNode::Optional { content, .. } => {
+                self.check_flag(value, "set", path);
+                if let Some(content_value) = value.get("content") {
+                    self.check_node(content_value, content, &format!("{path}.content"));
+                }
+            }
+            SchemaNode::Switch { content, .. } => {
+                self.check_flag(value, "enabled", path);
+                if let Some(content_value) = value.get("content") {
+                    self.check_node(content_value, content, &format!("{path}.content"));
+                }
+            }
+            SchemaNode::Boolean { .. } => {
+                if !value.is_boolean() {
+                    self.error(path, format!("Expected a boolean, found {value}"));
+                }
+            }
+            SchemaNode::Integer { .. } => {
+                if value.is_i64() || value.is_u64() {
+                    self.check_number(value, schema, path);
+                } else {
+                    self.error(path, format!("Expected an integer, found {value}"));
+                }
+            }
+            SchemaNode::Float { .. } => {
+                if value.is_number() {
+                    self.check_number(value, schema, path);
+                } else {
+                    self.error(path, format!("Expected a number, found {value}"));
+                }
+            }
+            SchemaNode::Text { .. } => {
+                if !value.is_string() {
+                    self.error(path, format!("Expected a string, found {value}"));
+                }
+            }
+            SchemaNode::Array(array_schema) => {
+                for (idx, element_schema) in array_schema.iter().enumerate() {
+                    if let Some(element_value) = value.get(idx) {
+                        self.check_node(element_value, element_schema, &format!("{path}.{idx}"));
+                    }
+                }
+            }
+            // The content of vectors and dictionaries uses the Settings layout and it is checked
+            // during deserialization
+            SchemaNode::Vector { .. } | SchemaNode::Dictionary { .. } => {
+                if let Some(content_value) = value.get("content") {
+                    if !content_value.is_array() {
+                        self.error(
+                            &format!("{path}.content"),
+                            format!("Expected an array, found {content_value}"),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_flag(&mut self, value: &json::Value, flag_name: &str, path: &str) {
+        if let Some(flag_value) = value.get(flag_name) {
+            if !flag_value.is_boolean() {
+                self.error(
+                    &format!("{path}.{flag_name}"),
+                    format!("Expected a boolean, found {flag_value}"),
+                );
+            }
+        }
+    }
+
+    fn check_number(&mut self, value: &json::Value, schema: &SchemaNode, path: &str) {
+        let number = value.as_f64().unwrap_or_default();
+
+        // The bounds have different types for integers and floats, the JSON representation is
+        // shared
+        let schema_json = json::to_value(schema).unwrap_or_default();
+        let min = schema_json["content"]["min"].as_f64();
+        let max = schema_json["content"]["max"].as_f64();
+        let step = schema_json["content"]["step"].as_f64();
+
+        if let Some(min) = min {
+            if number < min {
+                self.error(path, format!("Must be at least {min}"));
+            }
+        }
+        if let Some(max) = max {
+            if number > max {
+                self.error(path, format!("Must be at most {max}"));
+            }
+        }
+        // The step is a hint for the UI, other values still work
+        if let Some(step) = step.filter(|step| *step > 0.) {
+            let steps = (number - min.unwrap_or(0.)) / step;
+            if (steps - steps.round()).abs() > 1e-3 {
+                self.warning(path, format!("Should be a multiple of {step}"));
+            }
+        }
+    }
+
+    // Constraints between different settings. They are checked only if all the involved values are
+    // present.
+    fn check_constraints(&mut self, session_settings: &json::Value, path: &str) {
+        let get = |pointer: &str| session_settings.pointer(pointer);
+
+        // The check is skipped on Linux like in the connection handshake, where audio devices
+        // are resolved differently
+        if cfg!(not(target_os = "linux"))
+            && get("/audio/gameAudio/enabled") == Some(&json::Value::Bool(true))
+            && get("/audio/microphone/enabled") == Some(&json::Value::Bool(true))
+        {
+            if let (Some(game_audio_device), Some(microphone_device)) = (
+                get("/audio/gameAudio/content/deviceId"),
+                get("/audio/microphone/content/inputDeviceId"),
+            ) {
+                if same_audio_device(game_audio_device, microphone_device) {
+                    self.error(
+                        &format!("{path}.audio.microphone.content.inputDeviceId"),
+                        "Game audio and microphone cannot point to the same device".into(),
+                    );
+                }
+            }
+        }
+
+        if get("/video/adaptiveBitrate/enabled") == Some(&json::Value::Bool(true)) {
+            if let (Some(bitrate), Some(bitrate_maximum)) = (
+                get("/video/encodeBitrateMbs").and_then(|v| v.as_u64()),
+                get("/video/adaptiveBitrate/content/bitrateMaximum").and_then(|v| v.as_u64()),
+            ) {
+                if bitrate > bitrate_maximum {
+                    self.warning(
+                        &format!("{path}.video.encodeBitrateMbs"),
+                        format!(
+                            "The initial bitrate is higher than the adaptive bitrate maximum \
+                            ({bitrate_maximum} Mbps)"
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+// Only devices selected by name or index can be compared, the default device depends on the
+// device type
+fn same_audio_device(device1: &json::Value, device2: &json::Value) -> bool {
+    let variant = device1["variant"].as_str();
+    match variant {
+        Some(variant @ ("name" | "index")) => {
+            device2["variant"].as_str() == Some(variant) && device1[variant] == device2[variant]
+        }
+        _ => false,
+    }
+}
+
+// Diagnostics for a (possibly partial) session_settings JSON. Paths start with root_path, like
+// "sessionSettings" or "profiles.<name>".
+pub fn validate_session_settings_json(
+    session_settings: &json::Value,
+    root_path: &str,
+) -> Vec<SettingsDiagnostic> {
+    let schema = settings::settings_schema(settings::session_settings_default());
+
+    let mut validator = Validator::default();
+    validator.check_node(session_settings, &schema, root_path);
+    validator.check_constraints(session_settings, root_path);
+
+    validator.diagnostics
+}
+
+// Returns None if the path points to a field without its own schema node, like the "variant" field
+// of choices or the content of vectors
+fn session_settings_schema_at<'a>(
+    schema: &'a SchemaNode,
+    path: &[String],
+) -> Option<&'a SchemaNode> {
+    let (segment, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return Some(schema),
+    };
+
+    let child_schema: &SchemaNode = match schema {
+        SchemaNode::Section { entries } => {
+            &entries
+                .iter()
+                .find(|(field_name, _)| field_name == segment)?
+                .1
+                .as_ref()?
+                .content
+        }
+        SchemaNode::Choice { variants, .. } => {
+            &variants
+                .iter()
+                .find(|(variant_name, _)| variant_name == segment)?
+                .1
+                .as_ref()?
+                .content
+        }
+        SchemaNode::Optional { content, .. } if segment == "content" => content,
+        SchemaNode::Switch { content, .. } if segment == "content" => content,
+        SchemaNode::Array(array_schema) => array_schema.get(segment.parse::<usize>().ok()?)?,
+        _ => return None,
+    };
+
+    session_settings_schema_at(child_schema, rest)
+}
+
+// Checks a value before it is stored at the given path of session_settings. The path uses the JSON
+// field names, array indices are written as numbers. Warnings are ignored, constraints between
+// different settings are not checked.
+pub fn validate_session_settings_value(path: &[String], value: &json::Value) -> StrResult {
+    let schema = settings::settings_schema(settings::session_settings_default());
+
+    if let Some(value_schema) = session_settings_schema_at(&schema, path) {
+        let path = path.join(".");
+
+        let mut validator = Validator::default();
+        validator.check_node(value, value_schema, &path);
+
+        let errors = validator
+            .diagnostics
+            .into_iter()
+            .filter(|diagnostic| diagnostic.severity == DiagnosticSeverity::Error)
+            .map(|diagnostic| format!("{}: {}", diagnostic.path, diagnostic.message))
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return fmt_e!("{}", errors.join("; "));
+        }
+    }
+
+    Ok(())
+}
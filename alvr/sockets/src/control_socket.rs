@@ -1,17 +1,24 @@
-use super::{Ldc, CONTROL_PORT, LOCAL_IP};
+use super::{
+    certificate_fingerprint, security::PinnedCertificateVerifier, ClientIdentity, Ldc, StreamKey,
+    CERTIFICATE_SERVER_NAME, CONTROL_PORT, LOCAL_IP,
+};
 use alvr_common::prelude::*;
 use bytes::Bytes;
 use futures::{
     stream::{SplitSink, SplitStream},
     SinkExt, StreamExt,
 };
+use rustls::ServerName;
 use serde::{de::DeserializeOwned, Serialize};
-use std::{marker::PhantomData, net::IpAddr};
+use std::{marker::PhantomData, net::IpAddr, sync::Arc};
 use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::{TlsAcceptor, TlsConnector, TlsStream};
 use tokio_util::codec::Framed;
 
+type ControlStream = Framed<TlsStream<TcpStream>, Ldc>;
+
 pub struct ControlSocketSender<T> {
-    inner: SplitSink<Framed<TcpStream, Ldc>, Bytes>,
+    inner: SplitSink<ControlStream, Bytes>,
     _phantom: PhantomData<T>,
 }
 
@@ -23,7 +30,7 @@ impl<S: Serialize> ControlSocketSender<S> {
 }
 
 pub struct ControlSocketReceiver<T> {
-    inner: SplitStream<Framed<TcpStream, Ldc>>,
+    inner: SplitStream<ControlStream>,
     _phantom: PhantomData<T>,
 }
 
@@ -42,27 +49,31 @@ impl<R: DeserializeOwned> ControlSocketReceiver<R> {
 // Proto-control-socket that can send and receive any packet. After the split, only the packets of
 // the specified types can be exchanged
 pub struct ProtoControlSocket {
-    inner: Framed<TcpStream, Ldc>,
+    inner: ControlStream,
+    client_fingerprint: String,
+    stream_key: StreamKey,
 }
 
+// The client acts as the TLS server, presenting its own certificate. The server checks the
+// certificate against the fingerprint pinned for the IP it connected to.
 pub enum PeerType {
-    AnyClient(Vec<IpAddr>),
-    Server,
+    AnyClient(Vec<(IpAddr, Option<String>)>),
+    Server(ClientIdentity),
 }
 
 impl ProtoControlSocket {
     pub async fn connect_to(peer: PeerType) -> StrResult<(Self, IpAddr)> {
-        let socket = match peer {
-            PeerType::AnyClient(ips) => {
-                let client_addresses = ips
+        let socket = match &peer {
+            PeerType::AnyClient(clients) => {
+                let client_addresses = clients
                     .iter()
-                    .map(|&ip| (ip, CONTROL_PORT).into())
+                    .map(|&(ip, _)| (ip, CONTROL_PORT).into())
                     .collect::<Vec<_>>();
                 TcpStream::connect(client_addresses.as_slice())
                     .await
                     .map_err(err!())?
             }
-            PeerType::Server => {
+            PeerType::Server(_) => {
                 let listener = TcpListener::bind((LOCAL_IP, CONTROL_PORT))
                     .await
                     .map_err(err!())?;
@@ -73,9 +84,78 @@ impl ProtoControlSocket {
 
         socket.set_nodelay(true).map_err(err!())?;
         let peer_ip = socket.peer_addr().map_err(err!())?.ip();
-        let socket = Framed::new(socket, Ldc::new());
 
-        Ok((Self { inner: socket }, peer_ip))
+        let (socket, client_fingerprint, stream_key) = match peer {
+            PeerType::AnyClient(clients) => {
+                let pinned_fingerprint = clients
+                    .into_iter()
+                    .find(|(ip, _)| *ip == peer_ip)
+                    .and_then(|(_, fingerprint)| fingerprint);
+
+                let config = rustls::ClientConfig::builder()
+                    .with_safe_defaults()
+                    .with_custom_certificate_verifier(Arc::new(PinnedCertificateVerifier(
+                        pinned_fingerprint,
+                    )))
+                    .with_no_client_auth();
+                let server_name = ServerName::try_from(CERTIFICATE_SERVER_NAME).map_err(err!())?;
+                let socket = TlsConnector::from(Arc::new(config))
+                    .connect(server_name, socket)
+                    .await
+                    .map_err(err!())?;
+
+                let connection = socket.get_ref().1;
+                let client_fingerprint = certificate_fingerprint(
+                    connection
+                        .peer_certificates()
+                        .and_then(|certificates| certificates.first())
+                        .ok_or_else(enone!())?,
+                );
+                let stream_key = StreamKey::export(|output, label| {
+                    connection.export_keying_material(output, label, None)
+                })?;
+
+                (TlsStream::from(socket), client_fingerprint, stream_key)
+            }
+            PeerType::Server(identity) => {
+                let config = rustls::ServerConfig::builder()
+                    .with_safe_defaults()
+                    .with_no_client_auth()
+                    .with_single_cert(vec![identity.certificate()?], identity.private_key()?)
+                    .map_err(err!())?;
+                let socket = TlsAcceptor::from(Arc::new(config))
+                    .accept(socket)
+                    .await
+                    .map_err(err!())?;
+
+                let stream_key = StreamKey::export(|output, label| {
+                    socket
+                        .get_ref()
+                        .1
+                        .export_keying_material(output, label, None)
+                })?;
+
+                (TlsStream::from(socket), identity.fingerprint()?, stream_key)
+            }
+        };
+
+        Ok((
+            Self {
+                inner: Framed::new(socket, Ldc::new()),
+                client_fingerprint,
+                stream_key,
+            },
+            peer_ip,
+        ))
+    }
+
+    pub fn client_fingerprint(&self) -> &str {
+        &self.client_fingerprint
+    }
+
+    // Must be passed to the StreamSocketBuilder to encrypt the stream socket
+    pub fn stream_key(&self) -> StreamKey {
+        self.stream_key.clone()
     }
 
     pub async fn send<S: Serialize>(&mut self, packet: &S) -> StrResult {
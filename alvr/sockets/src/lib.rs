@@ -1,11 +1,15 @@
 mod control_socket;
+mod network_estimate;
 mod packets;
+mod security;
 mod stream_socket;
 
 use std::net::{IpAddr, Ipv4Addr};
 
 pub use control_socket::*;
+pub use network_estimate::*;
 pub use packets::*;
+pub use security::{certificate_fingerprint, ClientIdentity, StreamKey, CERTIFICATE_SERVER_NAME};
 pub use stream_socket::*;
 
 pub const LOCAL_IP: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
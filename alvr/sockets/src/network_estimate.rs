@@ -0,0 +1,292 @@
+// Active measurement of the network while streaming. The server sends a Ping with a timestamp on the
+// control socket and the client echoes it back with a Pong, to measure the round-trip time. The
+// server also sends probe trains on the PROBE stream: bursts of full-size packets sent
+// back-to-back. The client measures how much each train spread out on arrival, which depends on the
+// bandwidth of the slowest link, and counts the lost packets. The server combines the measurements
+// into a NetworkEstimate.
+
+use super::{ProbePacketHeader, ProbeReport, StreamReceiver, StreamSender};
+use alvr_common::prelude::*;
+use bytes::BufMut;
+use std::time::{Duration, Instant};
+use tokio::time;
+
+pub const PROBE_INTERVAL: Duration = Duration::from_secs(1);
+const PROBE_TRAIN_LENGTH: u16 = 16;
+// The client reports a train when no more packets arrive
+const PROBE_TRAIN_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Weight of the new samples
+const RTT_SMOOTHING: f64 = 0.125;
+const RTT_VARIATION_SMOOTHING: f64 = 0.25;
+const BANDWIDTH_SMOOTHING: f64 = 0.25;
+const LOSS_SMOOTHING: f64 = 0.1;
+
+fn smooth(old: f64, new: f64, weight: f64) -> f64 {
+    old * (1. - weight) + new * weight
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct NetworkEstimate {
+    // Smoothed round-trip time, None until the first Pong
+    pub rtt: Option<Duration>,
+    // Smoothed deviation of the round-trip time samples
+    pub rtt_variation: Duration,
+    // Bits per second, None until the first probe train has been measured
+    pub bandwidth_bps: Option<f64>,
+    // Fraction of the probe packets that have been lost
+    pub loss_rate: f32,
+}
+
+// Server side
+pub struct NetworkEstimator {
+    start: Instant,
+    estimate: NetworkEstimate,
+}
+
+impl Default for NetworkEstimator {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            estimate: NetworkEstimate::default(),
+        }
+    }
+}
+
+impl NetworkEstimator {
+    // To be sent with ServerControlPacket::Ping
+    pub fn ping_timestamp(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    // `timestamp` is the one echoed back with ClientControlPacket::Pong
+    pub fn report_pong(&mut self, timestamp: Duration) {
+        let rtt = self.start.elapsed().saturating_sub(timestamp).as_secs_f64();
+
+        // Same smoothing as the TCP retransmission timer
+        let estimate = &mut self.estimate;
+        if let Some(smoothed_rtt) = estimate.rtt {
+            let smoothed_rtt = smoothed_rtt.as_secs_f64();
+            estimate.rtt_variation = Duration::from_secs_f64(smooth(
+                estimate.rtt_variation.as_secs_f64(),
+                (rtt - smoothed_rtt).abs(),
+                RTT_VARIATION_SMOOTHING,
+            ));
+            estimate.rtt = Some(Duration::from_secs_f64(smooth(
+                smoothed_rtt,
+                rtt,
+                RTT_SMOOTHING,
+            )));
+        } else {
+            estimate.rtt = Some(Duration::from_secs_f64(rtt));
+            estimate.rtt_variation = Duration::from_secs_f64(rtt / 2.);
+        }
+    }
+
+    pub fn report_probe_train(&mut self, report: &ProbeReport) {
+        if report.packets_count == 0 || report.packets_received > report.packets_count {
+            return;
+        }
+
+        let loss_rate = 1. - report.packets_received as f64 / report.packets_count as f64;
+        self.estimate.loss_rate =
+            smooth(self.estimate.loss_rate as _, loss_rate, LOSS_SMOOTHING) as _;
+
+        // The dispersion of a single packet cannot be measured
+        if report.packets_received >= 2 && report.dispersion > Duration::ZERO {
+            let bandwidth_bps =
+                report.dispersed_bytes as f64 * 8. / report.dispersion.as_secs_f64();
+            self.estimate.bandwidth_bps = Some(match self.estimate.bandwidth_bps {
+                Some(old) => smooth(old, bandwidth_bps, BANDWIDTH_SMOOTHING),
+                None => bandwidth_bps,
+            });
+        }
+    }
+
+    pub fn estimate(&self) -> NetworkEstimate {
+        self.estimate
+    }
+}
+
+// Server side. The packets are as big as possible without being fragmented
+pub async fn send_probe_train(
+    sender: &mut StreamSender<ProbePacketHeader>,
+    train_index: u32,
+) -> StrResult {
+    for packet_index in 0..PROBE_TRAIN_LENGTH {
+        let header = ProbePacketHeader {
+            train_index,
+            packet_index,
+            packets_count: PROBE_TRAIN_LENGTH,
+        };
+        let header_size = bincode::serialized_size(&header).map_err(err!())? as usize;
+        let padding_size = sender.max_fragment_size().saturating_sub(header_size);
+
+        let mut buffer = sender.new_buffer(&header, padding_size)?;
+        buffer.get_mut().put_bytes(0, padding_size);
+        sender.send_buffer(buffer).await?;
+    }
+
+    Ok(())
+}
+
+struct TrainMeasurement {
+    train_index: u32,
+    packets_count: u16,
+    packets_received: u16,
+    first_arrival: Instant,
+    last_arrival: Instant,
+    dispersed_bytes: u64,
+}
+
+impl TrainMeasurement {
+    fn report(&self) -> ProbeReport {
+        ProbeReport {
+            train_index: self.train_index,
+            packets_count: self.packets_count,
+            packets_received: self.packets_received,
+            dispersion: self.last_arrival - self.first_arrival,
+            dispersed_bytes: self.dispersed_bytes,
+        }
+    }
+}
+
+// Client side. Measures the probe trains and passes a report to `report_train` for each of them
+pub async fn probe_receive_loop(
+    mut receiver: StreamReceiver<ProbePacketHeader>,
+    mut report_train: impl FnMut(ProbeReport),
+) -> StrResult {
+    let mut measurement: Option<TrainMeasurement> = None;
+    loop {
+        let packet = if measurement.is_some() {
+            match time::timeout(PROBE_TRAIN_TIMEOUT, receiver.recv()).await {
+                Ok(res) => res?,
+                Err(_) => {
+                    if let Some(measurement) = measurement.take() {
+                        report_train(measurement.report());
+                    }
+                    continue;
+                }
+            }
+        } else {
+            receiver.recv().await?
+        };
+        let arrival = Instant::now();
+        let header = packet.header;
+        // The header is not included, it has the same size for every packet
+        let bytes_count = packet.buffer.len() as u64;
+
+        match &mut measurement {
+            Some(measurement) if measurement.train_index == header.train_index => {
+                measurement.packets_received = measurement.packets_received.saturating_add(1);
+                measurement.last_arrival = arrival;
+                measurement.dispersed_bytes += bytes_count;
+            }
+            _ => {
+                if let Some(measurement) = measurement.take() {
+                    report_train(measurement.report());
+                }
+
+                measurement = Some(TrainMeasurement {
+                    train_index: header.train_index,
+                    packets_count: header.packets_count,
+                    packets_received: 1,
+                    first_arrival: arrival,
+                    last_arrival: arrival,
+                    dispersed_bytes: 0,
+                });
+            }
+        }
+
+        // Do not wait for the timeout if the last packet arrived
+        if header.packet_index as u32 + 1 == header.packets_count as u32 {
+            if let Some(measurement) = measurement.take() {
+                report_train(measurement.report());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(value: f64, expected: f64) {
+        assert!(
+            (value - expected).abs() < expected * 0.01,
+            "{value} != {expected}"
+        );
+    }
+
+    // The pong arrives `rtt` after the ping
+    fn report_pong(estimator: &mut NetworkEstimator, rtt: Duration) {
+        let timestamp = estimator.ping_timestamp();
+        estimator.start -= rtt;
+        estimator.report_pong(timestamp);
+    }
+
+    fn probe_report(packets_received: u16, dispersion: Duration) -> ProbeReport {
+        ProbeReport {
+            train_index: 0,
+            packets_count: 16,
+            packets_received,
+            dispersion,
+            dispersed_bytes: packets_received.saturating_sub(1) as u64 * 1000,
+        }
+    }
+
+    #[test]
+    fn rtt_is_smoothed() {
+        let mut estimator = NetworkEstimator::default();
+        assert!(estimator.estimate().rtt.is_none());
+
+        report_pong(&mut estimator, Duration::from_millis(100));
+        let estimate = estimator.estimate();
+        assert_near(estimate.rtt.unwrap().as_secs_f64(), 0.1);
+        assert_near(estimate.rtt_variation.as_secs_f64(), 0.05);
+
+        report_pong(&mut estimator, Duration::from_millis(200));
+        let estimate = estimator.estimate();
+        assert_near(estimate.rtt.unwrap().as_secs_f64(), 0.1125);
+        assert_near(estimate.rtt_variation.as_secs_f64(), 0.0625);
+    }
+
+    #[test]
+    fn bandwidth_is_smoothed() {
+        let mut estimator = NetworkEstimator::default();
+        assert!(estimator.estimate().bandwidth_bps.is_none());
+
+        // 15000 bytes in 10ms
+        estimator.report_probe_train(&probe_report(16, Duration::from_millis(10)));
+        assert_near(estimator.estimate().bandwidth_bps.unwrap(), 12e6);
+
+        estimator.report_probe_train(&probe_report(16, Duration::from_millis(20)));
+        assert_near(estimator.estimate().bandwidth_bps.unwrap(), 10.5e6);
+
+        // The dispersion of a single packet cannot be measured
+        estimator.report_probe_train(&probe_report(1, Duration::ZERO));
+        assert_near(estimator.estimate().bandwidth_bps.unwrap(), 10.5e6);
+    }
+
+    #[test]
+    fn loss_rate_is_smoothed() {
+        let mut estimator = NetworkEstimator::default();
+
+        estimator.report_probe_train(&probe_report(16, Duration::from_millis(10)));
+        assert_eq!(estimator.estimate().loss_rate, 0.);
+
+        estimator.report_probe_train(&probe_report(8, Duration::from_millis(10)));
+        assert_near(estimator.estimate().loss_rate as _, 0.05);
+
+        estimator.report_probe_train(&probe_report(16, Duration::from_millis(10)));
+        assert_near(estimator.estimate().loss_rate as _, 0.045);
+
+        // Invalid reports are ignored
+        estimator.report_probe_train(&ProbeReport {
+            packets_count: 0,
+            ..probe_report(0, Duration::ZERO)
+        });
+        estimator.report_probe_train(&probe_report(17, Duration::from_millis(10)));
+        assert_near(estimator.estimate().loss_rate as _, 0.045);
+    }
+}
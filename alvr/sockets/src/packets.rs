@@ -12,6 +12,7 @@ pub const HAPTICS: u16 = 1;
 pub const AUDIO: u16 = 2;
 pub const VIDEO: u16 = 3;
 pub const STATISTICS: u16 = 4;
+pub const PROBE: u16 = 5;
 
 // Field of view in radians
 #[derive(Serialize, Deserialize, PartialEq, Default, Clone, Copy)]
@@ -89,6 +90,20 @@ pub enum ServerControlPacket {
     KeepAlive,
     Reserved(String),
     ReservedBuffer(Vec<u8>),
+    // Sent while streaming, only if the size of the encoded frames does not change
+    FoveationConfig(FoveationConfig),
+    // Echoed back with ClientControlPacket::Pong to measure the round-trip time
+    Ping(Duration),
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct FoveationConfig {
+    pub center_size_x: f32,
+    pub center_size_y: f32,
+    pub center_shift_x: f32,
+    pub center_shift_y: f32,
+    pub edge_ratio_x: f32,
+    pub edge_ratio_y: f32,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -118,6 +133,27 @@ pub enum ClientControlPacket {
     ActiveInteractionProfile { device_id: u64, profile_id: u64 },
     Reserved(String),
     ReservedBuffer(Vec<u8>),
+    Pong(Duration),
+    ProbeReport(ProbeReport),
+}
+
+// Header of the packets of a probe train. The buffer is only padding
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ProbePacketHeader {
+    pub train_index: u32,
+    pub packet_index: u16,
+    pub packets_count: u16,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ProbeReport {
+    pub train_index: u32,
+    pub packets_count: u16,
+    pub packets_received: u16,
+    // Between the arrival of the first and the last received packets
+    pub dispersion: Duration,
+    // Size of the received packets, except the first one
+    pub dispersed_bytes: u64,
 }
 
 // legacy video packet
@@ -128,8 +164,6 @@ pub struct VideoFrameHeaderPacket {
     pub video_frame_index: u64,
     pub sent_time: u64,
     pub frame_byte_size: u32,
-    pub fec_index: u32,
-    pub fec_percentage: u16,
 }
 
 #[derive(Serialize, Deserialize, Clone, Default, Debug)]
@@ -175,9 +209,18 @@ pub enum PathSegment {
 }
 
 pub enum ClientListAction {
-    AddIfMissing { display_name: String },
+    AddIfMissing {
+        display_name: String,
+        certificate_fingerprint: Option<String>,
+    },
+    // Ignored if the client is already trusted with another certificate
+    PinCertificate(String),
     TrustAndMaybeAddIp(Option<IpAddr>),
+    // Ignored if the client is not trusted
+    AddIp(IpAddr),
     RemoveIpOrEntry(Option<IpAddr>),
+    // None resets the client to the default session settings
+    SetProfile(Option<String>),
 }
 
 #[derive(Serialize, Deserialize, Default, Clone)]
@@ -192,4 +235,9 @@ pub struct ClientStatistics {
     // Note: This is used for the controller prediction.
     // NB: This contains also the tracking packet send latency so it might lead to overprediction
     pub average_total_pipeline_latency: Duration,
+
+    // Totals of the video stream, see StreamReceiverStatistics
+    pub video_packets_reordered: u64,
+    pub video_packets_late: u64,
+    pub video_packets_duplicated: u64,
 }
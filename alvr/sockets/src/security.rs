@@ -0,0 +1,350 @@
+// The client (headset) owns a long-lived self-signed certificate. The server pins its fingerprint
+// when the client is trusted and checks it every time the control socket connects. The control
+// socket is TLS, and the stream socket packets are encrypted with keys exported from the TLS session,
+// so both channels are only readable by the two peers.
+
+use alvr_common::{parking_lot::Mutex, prelude::*};
+use bytes::{BufMut, BytesMut};
+use chacha20poly1305::{aead::AeadInPlace, ChaCha20Poly1305, Key, KeyInit, Nonce, Tag};
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    Certificate, PrivateKey, ServerName,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+pub const CERTIFICATE_SERVER_NAME: &str = "alvr.client";
+
+const STREAM_KEY_LABEL: &[u8] = b"EXPORTER-alvr-stream-key";
+const NONCE_SIZE: usize = 8;
+const TAG_SIZE: usize = 16;
+// Nonces older than the highest one received on a stream that are still accepted. The nonces of a
+// stream are not contiguous, they are shared with the other streams
+const REPLAY_WINDOW_SIZE: u64 = 1024;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ClientIdentity {
+    pub certificate_pem: String,
+    pub private_key_pem: String,
+}
+
+impl ClientIdentity {
+    pub fn generate() -> StrResult<Self> {
+        let certificate = rcgen::generate_simple_self_signed(vec![CERTIFICATE_SERVER_NAME.into()])
+            .map_err(err!())?;
+
+        Ok(Self {
+            certificate_pem: certificate.serialize_pem().map_err(err!())?,
+            private_key_pem: certificate.serialize_private_key_pem(),
+        })
+    }
+
+    pub fn certificate(&self) -> StrResult<Certificate> {
+        Ok(Certificate(
+            pem::parse(&self.certificate_pem).map_err(err!())?.contents,
+        ))
+    }
+
+    pub fn private_key(&self) -> StrResult<PrivateKey> {
+        Ok(PrivateKey(
+            pem::parse(&self.private_key_pem).map_err(err!())?.contents,
+        ))
+    }
+
+    pub fn fingerprint(&self) -> StrResult<String> {
+        Ok(certificate_fingerprint(&self.certificate()?))
+    }
+}
+
+// Hex encoded SHA-256 of the DER certificate
+pub fn certificate_fingerprint(certificate: &Certificate) -> String {
+    Sha256::digest(&certificate.0)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+// Accepts the client certificate only if it matches the pinned fingerprint. If no fingerprint has
+// been pinned yet, any certificate is accepted and the caller is responsible of pinning it.
+pub(crate) struct PinnedCertificateVerifier(pub Option<String>);
+
+impl ServerCertVerifier for PinnedCertificateVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _: &[Certificate],
+        _: &ServerName,
+        _: &mut dyn Iterator<Item = &[u8]>,
+        _: &[u8],
+        _: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match &self.0 {
+            Some(fingerprint) if *fingerprint != certificate_fingerprint(end_entity) => Err(
+                rustls::Error::General("Client certificate does not match".into()),
+            ),
+            _ => Ok(ServerCertVerified::assertion()),
+        }
+    }
+}
+
+// Key material exported from the control socket TLS session. Each direction uses its own key.
+#[derive(Clone)]
+pub struct StreamKey {
+    pub(crate) server_to_client: [u8; 32],
+    pub(crate) client_to_server: [u8; 32],
+}
+
+impl StreamKey {
+    pub(crate) fn export(
+        exporter: impl FnOnce(&mut [u8], &[u8]) -> Result<(), rustls::Error>,
+    ) -> StrResult<Self> {
+        let mut material = [0; 64];
+        exporter(&mut material, STREAM_KEY_LABEL).map_err(err!())?;
+
+        let mut server_to_client = [0; 32];
+        let mut client_to_server = [0; 32];
+        server_to_client.copy_from_slice(&material[..32]);
+        client_to_server.copy_from_slice(&material[32..]);
+
+        Ok(Self {
+            server_to_client,
+            client_to_server,
+        })
+    }
+}
+
+// Nonces received on a stream, so that captured packets cannot be injected again. Each nonce is
+// accepted once, and only if it is not older than the window.
+#[derive(Default)]
+struct ReplayWindow {
+    highest_nonce: Option<u64>,
+    // Bit n % REPLAY_WINDOW_SIZE is set if the nonce n has been received
+    bitmap: [u64; REPLAY_WINDOW_SIZE as usize / 64],
+}
+
+impl ReplayWindow {
+    fn bit(nonce: u64) -> (usize, u64) {
+        let position = nonce % REPLAY_WINDOW_SIZE;
+
+        ((position / 64) as usize, 1 << (position % 64))
+    }
+
+    fn accept(&mut self, nonce: u64) -> bool {
+        match self.highest_nonce {
+            Some(highest_nonce) if nonce <= highest_nonce => {
+                let (word, mask) = Self::bit(nonce);
+                if highest_nonce - nonce >= REPLAY_WINDOW_SIZE || self.bitmap[word] & mask != 0 {
+                    return false;
+                }
+            }
+            Some(highest_nonce) => {
+                // The bits of the nonces that move out of the window are reused
+                let skipped = (nonce - highest_nonce).min(REPLAY_WINDOW_SIZE);
+                for old_nonce in nonce - skipped + 1..=nonce {
+                    let (word, mask) = Self::bit(old_nonce);
+                    self.bitmap[word] &= !mask;
+                }
+                self.highest_nonce = Some(nonce);
+            }
+            None => self.highest_nonce = Some(nonce),
+        }
+
+        let (word, mask) = Self::bit(nonce);
+        self.bitmap[word] |= mask;
+
+        true
+    }
+}
+
+// Encrypted packet layout: [nonce (u64)][ciphertext][tag]. The stream ID is authenticated as
+// associated data. The nonce counter is shared by all streams of a socket so it is never reused.
+pub(crate) struct StreamCipher {
+    send_cipher: ChaCha20Poly1305,
+    receive_cipher: ChaCha20Poly1305,
+    next_nonce: AtomicU64,
+    // By stream ID. Each stream is received in order of arrival, so packets reordered by the other
+    // streams do not fall out of the window
+    replay_windows: Mutex<HashMap<u16, ReplayWindow>>,
+}
+
+impl StreamCipher {
+    pub fn new(key: &StreamKey, is_server: bool) -> Self {
+        let (send_key, receive_key) = if is_server {
+            (&key.server_to_client, &key.client_to_server)
+        } else {
+            (&key.client_to_server, &key.server_to_client)
+        };
+
+        Self {
+            send_cipher: ChaCha20Poly1305::new(Key::from_slice(send_key)),
+            receive_cipher: ChaCha20Poly1305::new(Key::from_slice(receive_key)),
+            next_nonce: AtomicU64::new(0),
+            replay_windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Space that must be reserved after the stream ID
+    pub const HEADER_SIZE: usize = NONCE_SIZE;
+    // Space appended to the packet by encrypt()
+    pub const TRAILER_SIZE: usize = TAG_SIZE;
+
+    // `packet` must start with the stream ID followed by `HEADER_SIZE` reserved bytes
+    pub fn encrypt(&self, packet: &mut BytesMut) -> StrResult {
+        let nonce = self.next_nonce.fetch_add(1, Ordering::Relaxed);
+        packet[2..2 + NONCE_SIZE].copy_from_slice(&nonce.to_be_bytes());
+
+        let (associated_data, payload) = packet.split_at_mut(2 + NONCE_SIZE);
+        let tag = self
+            .send_cipher
+            .encrypt_in_place_detached(
+                &nonce_from_bytes(&associated_data[2..]),
+                &associated_data[..2],
+                payload,
+            )
+            .map_err(err!())?;
+        packet.put_slice(&tag);
+
+        Ok(())
+    }
+
+    // `packet` must not include the stream ID anymore. On success the nonce and tag are removed.
+    // Packets already received and packets older than the replay window are rejected.
+    pub fn decrypt(&self, stream_id: u16, packet: &mut BytesMut) -> StrResult {
+        if packet.len() < NONCE_SIZE + TAG_SIZE {
+            return fmt_e!("Packet too small");
+        }
+
+        let tag = Tag::clone_from_slice(&packet.split_off(packet.len() - TAG_SIZE));
+        let nonce_bytes = packet.split_to(NONCE_SIZE);
+        let nonce = nonce_from_bytes(&nonce_bytes);
+
+        self.receive_cipher
+            .decrypt_in_place_detached(&nonce, &stream_id.to_be_bytes(), packet, &tag)
+            .map_err(err!())?;
+
+        // Checked only after authentication, forged nonces must not move the window
+        let mut nonce_counter = [0; NONCE_SIZE];
+        nonce_counter.copy_from_slice(&nonce_bytes);
+        if !self
+            .replay_windows
+            .lock()
+            .entry(stream_id)
+            .or_default()
+            .accept(u64::from_be_bytes(nonce_counter))
+        {
+            return fmt_e!("Replayed packet");
+        }
+
+        Ok(())
+    }
+}
+
+fn nonce_from_bytes(counter: &[u8]) -> Nonce {
+    let mut nonce = [0; 12];
+    nonce[4..].copy_from_slice(counter);
+
+    *Nonce::from_slice(&nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher_pair() -> (StreamCipher, StreamCipher) {
+        let key = StreamKey {
+            server_to_client: [1; 32],
+            client_to_server: [2; 32],
+        };
+
+        (
+            StreamCipher::new(&key, true),
+            StreamCipher::new(&key, false),
+        )
+    }
+
+    fn encrypted_packet(cipher: &StreamCipher, stream_id: u16) -> BytesMut {
+        let mut packet = BytesMut::new();
+        packet.put_u16(stream_id);
+        packet.put_bytes(0, StreamCipher::HEADER_SIZE);
+        packet.put_slice(b"payload");
+        cipher.encrypt(&mut packet).unwrap();
+
+        // The receive loop removes the stream ID
+        packet.split_off(2)
+    }
+
+    #[test]
+    fn replayed_packets_are_rejected() {
+        let (server, client) = cipher_pair();
+
+        let packet = encrypted_packet(&server, 3);
+
+        let mut received = packet.clone();
+        client.decrypt(3, &mut received).unwrap();
+        assert_eq!(&received[..], b"payload");
+
+        assert!(client.decrypt(3, &mut packet.clone()).is_err());
+    }
+
+    #[test]
+    fn reordered_packets_are_accepted_once() {
+        let (server, client) = cipher_pair();
+
+        let packets = (0..10)
+            .map(|_| encrypted_packet(&server, 3))
+            .collect::<Vec<_>>();
+
+        for packet in packets.iter().rev() {
+            client.decrypt(3, &mut packet.clone()).unwrap();
+        }
+        for packet in &packets {
+            assert!(client.decrypt(3, &mut packet.clone()).is_err());
+        }
+    }
+
+    #[test]
+    fn packets_older_than_the_window_are_rejected() {
+        let (server, client) = cipher_pair();
+
+        let old_packet = encrypted_packet(&server, 3);
+        for _ in 0..REPLAY_WINDOW_SIZE {
+            client
+                .decrypt(3, &mut encrypted_packet(&server, 3))
+                .unwrap();
+        }
+
+        assert!(client.decrypt(3, &mut old_packet.clone()).is_err());
+    }
+
+    #[test]
+    fn streams_have_separate_windows() {
+        let (server, client) = cipher_pair();
+
+        let video_packet = encrypted_packet(&server, 3);
+        for _ in 0..2 * REPLAY_WINDOW_SIZE {
+            client
+                .decrypt(0, &mut encrypted_packet(&server, 0))
+                .unwrap();
+        }
+
+        client.decrypt(3, &mut video_packet.clone()).unwrap();
+    }
+
+    #[test]
+    fn forged_packets_do_not_move_the_window() {
+        let (server, client) = cipher_pair();
+
+        let packet = encrypted_packet(&server, 3);
+
+        let mut forged_packet = encrypted_packet(&server, 3);
+        forged_packet[..NONCE_SIZE].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(client.decrypt(3, &mut forged_packet).is_err());
+
+        client.decrypt(3, &mut packet.clone()).unwrap();
+    }
+}
@@ -0,0 +1,676 @@
+// Forward error correction for the streams that opt in with a FecConfig. Data packets are grouped
+// and the Reed-Solomon parity packets of a group are sent right after its last data packet. When a
+// data packet is lost, the receiver holds the following packets of the group until the lost one is
+// recovered, so packets are still delivered in order.
+//
+// Groups can be closed before they are full, for example at the end of a fragmented message. Their
+// data packets declare the planned size of the group, the parity packets declare the actual one.
+//
+// Once per second the receiver reports the shard loss rate back to the sender, on the stream ID
+// with FEC_FEEDBACK_FLAG set. The sender adapts the number of parity packets of the next groups.
+//
+// Data and parity packets start with [group (u32)][shard (u8)][data shards (u8)][parity shards (u8)].
+// The Reed-Solomon shard of a data packet is [packet size (u32)][packet][zero padding], where the
+// packet starts at the packet index. Parity packets contain only the parity shard.
+
+use super::{queue::PacketQueue, StreamCipher};
+use alvr_common::prelude::*;
+use bytes::{Buf, BufMut, BytesMut};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+pub const FEC_FEEDBACK_FLAG: u16 = 0x8000;
+pub const SHARD_HEADER_SIZE: usize = 7;
+
+// Held packets are released after this time even if the lost packets could not be recovered
+pub const MAX_HOLD_TIME: Duration = Duration::from_millis(50);
+
+const FEEDBACK_INTERVAL: Duration = Duration::from_secs(1);
+// Weight of the last reported loss rate in the smoothed one
+const LOSS_RATE_SMOOTHING: f32 = 0.3;
+// Redundancy added for each unit of loss rate, it leaves a margin for loss bursts
+const LOSS_REDUNDANCY_FACTOR: f32 = 2.;
+
+#[derive(Clone, Copy, Debug)]
+pub struct FecConfig {
+    // Number of data packets protected by the same parity packets. With bigger groups less
+    // redundancy is needed, but packets are held for longer after a loss.
+    pub group_size: u8,
+    // Parity packets per data packet. The redundancy grows with the loss rate reported by the
+    // receiver, up to max_redundancy.
+    pub min_redundancy: f32,
+    pub max_redundancy: f32,
+}
+
+// Suited for streams of small packets sent at a regular rate, like audio
+impl Default for FecConfig {
+    fn default() -> Self {
+        Self {
+            group_size: 4,
+            min_redundancy: 0.25,
+            max_redundancy: 1.,
+        }
+    }
+}
+
+impl FecConfig {
+    pub fn validate(&self) -> StrResult {
+        if self.group_size == 0 {
+            return fmt_e!("The FEC group size must be at least 1");
+        }
+        if !(self.min_redundancy >= 0. && self.min_redundancy <= self.max_redundancy) {
+            return fmt_e!(
+                "Invalid FEC redundancy range {}..{}",
+                self.min_redundancy,
+                self.max_redundancy
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ShardHeader {
+    pub group: u32,
+    pub shard: u8,
+    pub data_shards: u8,
+    pub parity_shards: u8,
+}
+
+impl ShardHeader {
+    pub fn write(&self, mut buffer: impl BufMut) {
+        buffer.put_u32(self.group);
+        buffer.put_u8(self.shard);
+        buffer.put_u8(self.data_shards);
+        buffer.put_u8(self.parity_shards);
+    }
+
+    pub fn read(buffer: &mut impl Buf) -> StrResult<Self> {
+        if buffer.remaining() < SHARD_HEADER_SIZE {
+            return fmt_e!("FEC packet too small");
+        }
+
+        let header = Self {
+            group: buffer.get_u32(),
+            shard: buffer.get_u8(),
+            data_shards: buffer.get_u8(),
+            parity_shards: buffer.get_u8(),
+        };
+        if header.data_shards == 0
+            || header.shard as usize >= header.data_shards as usize + header.parity_shards as usize
+        {
+            return fmt_e!("Invalid FEC shard {header:?}");
+        }
+
+        Ok(header)
+    }
+}
+
+fn parity_shards_count(data_shards: u8, redundancy: f32) -> u8 {
+    let parity_shards = (data_shards as f32 * redundancy).ceil() as usize;
+
+    // Reed-Solomon over GF(2^8) supports up to 256 shards, the shard index must fit in a u8
+    parity_shards.min(u8::MAX as usize - data_shards as usize) as u8
+}
+
+pub struct FecEncoder {
+    config: FecConfig,
+    feedback_queue: Arc<PacketQueue<BytesMut>>,
+    loss_rate: f32,
+    group: u32,
+    parity_shards: u8,
+    // Reed-Solomon shards of the data packets of the current group, without padding
+    data_shards: Vec<Vec<u8>>,
+}
+
+impl FecEncoder {
+    pub fn new(config: FecConfig, feedback_queue: Arc<PacketQueue<BytesMut>>) -> Self {
+        Self {
+            config,
+            feedback_queue,
+            loss_rate: 0.,
+            group: 0,
+            parity_shards: 0,
+            data_shards: vec![],
+        }
+    }
+
+    pub fn redundancy(&self) -> f32 {
+        (self.config.min_redundancy + self.loss_rate * LOSS_REDUNDANCY_FACTOR)
+            .min(self.config.max_redundancy)
+    }
+
+    // Updates the loss rate with the reports received since the last call
+    pub fn process_feedback(&mut self, cipher: &StreamCipher, stream_id: u16) {
+        while let Some(mut bytes) = self.feedback_queue.try_pop() {
+            if let Err(e) = cipher.decrypt(stream_id | FEC_FEEDBACK_FLAG, &mut bytes) {
+                debug!("Dropped invalid FEC feedback: {e}");
+                continue;
+            }
+            if bytes.remaining() < 8 {
+                continue;
+            }
+
+            let shards = bytes.get_u32();
+            let lost_shards = bytes.get_u32();
+            if shards > 0 {
+                let loss_rate = (lost_shards as f32 / shards as f32).min(1.);
+                self.loss_rate += (loss_rate - self.loss_rate) * LOSS_RATE_SMOOTHING;
+            }
+        }
+    }
+
+    // `packet` starts at the packet index. Returns the header to write in the packet and, if the
+    // packet completes the group, the parity packets to send after it.
+    pub fn add_packet(&mut self, packet: &[u8]) -> StrResult<(ShardHeader, Vec<BytesMut>)> {
+        // The redundancy is decided at the start of the group, all its packets declare it
+        if self.data_shards.is_empty() {
+            self.parity_shards = parity_shards_count(self.config.group_size, self.redundancy());
+        }
+
+        let header = ShardHeader {
+            group: self.group,
+            shard: self.data_shards.len() as u8,
+            data_shards: self.config.group_size,
+            parity_shards: self.parity_shards,
+        };
+
+        let mut shard = Vec::with_capacity(4 + packet.len());
+        shard.put_u32(packet.len() as u32);
+        shard.extend_from_slice(packet);
+        self.data_shards.push(shard);
+
+        if self.data_shards.len() < self.config.group_size as usize {
+            return Ok((header, vec![]));
+        }
+
+        let parity_packets = self.close_group()?;
+
+        Ok((header, parity_packets))
+    }
+
+    // Returns the parity packets of the current group, if it contains any packet
+    pub fn close_group(&mut self) -> StrResult<Vec<BytesMut>> {
+        if self.data_shards.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut shards = std::mem::take(&mut self.data_shards);
+        let group = self.group;
+        self.group = self.group.wrapping_add(1);
+
+        // A smaller group needs less parity packets for the same redundancy
+        let data_shards = shards.len() as u8;
+        let parity_shards = self
+            .parity_shards
+            .min(parity_shards_count(data_shards, self.redundancy()));
+        if parity_shards == 0 {
+            return Ok(vec![]);
+        }
+
+        let shard_size = shards.iter().map(|shard| shard.len()).max().unwrap_or(0);
+        for shard in &mut shards {
+            shard.resize(shard_size, 0);
+        }
+        shards.resize(
+            data_shards as usize + parity_shards as usize,
+            vec![0; shard_size],
+        );
+
+        ReedSolomon::new(data_shards as _, parity_shards as _)
+            .map_err(err!())?
+            .encode(&mut shards)
+            .map_err(err!())?;
+
+        let parity_packets = shards
+            .into_iter()
+            .enumerate()
+            .skip(data_shards as _)
+            .map(|(index, shard)| {
+                let mut packet = BytesMut::with_capacity(SHARD_HEADER_SIZE + shard.len());
+                ShardHeader {
+                    group,
+                    shard: index as u8,
+                    data_shards,
+                    parity_shards,
+                }
+                .write(&mut packet);
+                packet.put_slice(&shard);
+
+                packet
+            })
+            .collect();
+
+        Ok(parity_packets)
+    }
+}
+
+struct FecGroup {
+    id: u32,
+    // Declared by the data packets until a parity packet is received
+    data_shards: usize,
+    parity_shards: usize,
+    // Reed-Solomon shards. Data shards are stored without padding
+    shards: Vec<Option<Vec<u8>>>,
+    received_shards: usize,
+    last_received_shard: usize,
+    // Data packets before this one have been delivered or skipped
+    next_shard: usize,
+}
+
+impl FecGroup {
+    fn new(header: &ShardHeader) -> Self {
+        let shards_count = header.data_shards as usize + header.parity_shards as usize;
+
+        Self {
+            id: header.group,
+            data_shards: header.data_shards as _,
+            parity_shards: header.parity_shards as _,
+            shards: vec![None; shards_count],
+            received_shards: 0,
+            last_received_shard: 0,
+            next_shard: 0,
+        }
+    }
+
+    // The parity packets declare the actual size of groups closed before they were full
+    fn resize(&mut self, header: &ShardHeader) -> StrResult {
+        let data_shards = header.data_shards as usize;
+        let parity_shards = header.parity_shards as usize;
+        if (data_shards, parity_shards) == (self.data_shards, self.parity_shards) {
+            return Ok(());
+        }
+        if data_shards > self.data_shards
+            || self.shards[data_shards..]
+                .iter()
+                .any(|shard| shard.is_some())
+        {
+            return fmt_e!("Invalid FEC shard {header:?}");
+        }
+
+        self.shards.truncate(data_shards);
+        self.shards.resize(data_shards + parity_shards, None);
+        self.data_shards = data_shards;
+        self.parity_shards = parity_shards;
+
+        Ok(())
+    }
+
+    // Data packets are held if one of the packets before them is missing
+    fn is_holding(&self) -> bool {
+        self.shards[self.next_shard.min(self.data_shards)..self.data_shards]
+            .iter()
+            .any(|shard| shard.is_some())
+    }
+
+    // Shards are sent in order, so a missing shard before the last received one has been lost
+    fn is_recoverable(&self) -> bool {
+        let lost_shards = self.shards[..self.last_received_shard]
+            .iter()
+            .filter(|shard| shard.is_none())
+            .count();
+
+        lost_shards <= self.parity_shards
+    }
+}
+
+#[derive(Default)]
+pub struct FecStatistics {
+    pub packets_recovered: u64,
+    pub parity_packets_received: u64,
+}
+
+pub struct FecDecoder {
+    group: Option<FecGroup>,
+    // Packets ready to be delivered, in order. They start at the packet index
+    ready_packets: VecDeque<BytesMut>,
+    hold_start: Option<Instant>,
+    window_shards: u32,
+    window_lost_shards: u32,
+    last_feedback: Instant,
+    pub statistics: FecStatistics,
+}
+
+impl FecDecoder {
+    pub fn new() -> Self {
+        Self {
+            group: None,
+            ready_packets: VecDeque::new(),
+            hold_start: None,
+            window_shards: 0,
+            window_lost_shards: 0,
+            last_feedback: Instant::now(),
+            statistics: FecStatistics::default(),
+        }
+    }
+
+    pub fn pop_packet(&mut self) -> Option<BytesMut> {
+        self.ready_packets.pop_front()
+    }
+
+    // Time after which the held packets must be released with release_held()
+    pub fn hold_deadline(&self) -> Option<Instant> {
+        self.hold_start.map(|start| start + MAX_HOLD_TIME)
+    }
+
+    // `bytes` starts after the packet kind
+    pub fn push_shard(&mut self, is_parity: bool, mut bytes: BytesMut) -> StrResult {
+        let header = ShardHeader::read(&mut bytes)?;
+        if is_parity != (header.shard >= header.data_shards) {
+            return fmt_e!("Invalid FEC shard {header:?}");
+        }
+
+        match &self.group {
+            // Packets of older groups arrived too late
+            Some(group) if (header.group.wrapping_sub(group.id) as i32) < 0 => return Ok(()),
+            Some(group) if header.group == group.id => (),
+            _ => {
+                self.finish_group();
+                self.group = Some(FecGroup::new(&header));
+            }
+        }
+        let group = self.group.as_mut().unwrap();
+        if is_parity {
+            group.resize(&header)?;
+        }
+
+        let shard_index = header.shard as usize;
+        if shard_index >= group.shards.len()
+            || is_parity != (shard_index >= group.data_shards)
+            || group.shards[shard_index].is_some()
+        {
+            return Ok(());
+        }
+
+        if is_parity {
+            group.shards[shard_index] = Some(bytes.to_vec());
+            self.statistics.parity_packets_received += 1;
+        } else {
+            if bytes.remaining() < 4 {
+                return fmt_e!("FEC packet too small");
+            }
+
+            let mut shard = Vec::with_capacity(4 + bytes.len());
+            shard.put_u32(bytes.len() as u32);
+            shard.extend_from_slice(&bytes);
+            group.shards[shard_index] = Some(shard);
+
+            // Packets that have been skipped are not delivered anymore
+            if shard_index == group.next_shard {
+                self.ready_packets.push_back(bytes);
+                group.next_shard += 1;
+            }
+        }
+        group.received_shards += 1;
+        group.last_received_shard = group.last_received_shard.max(shard_index);
+
+        self.deliver_in_order();
+
+        let group = self.group.as_ref().unwrap();
+        if group.next_shard < group.data_shards && group.received_shards >= group.data_shards {
+            self.recover()?;
+        }
+
+        let group = self.group.as_ref().unwrap();
+        if !group.is_holding() {
+            self.hold_start = None;
+        } else if !group.is_recoverable() {
+            self.release_held();
+        } else if self.hold_start.is_none() {
+            self.hold_start = Some(Instant::now());
+        }
+
+        Ok(())
+    }
+
+    fn deliver_in_order(&mut self) {
+        if let Some(group) = &mut self.group {
+            while group.next_shard < group.data_shards {
+                match &group.shards[group.next_shard] {
+                    Some(shard) => {
+                        self.ready_packets.push_back(BytesMut::from(&shard[4..]));
+                        group.next_shard += 1;
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+
+    // Delivers the held packets, skipping the missing ones
+    pub fn release_held(&mut self) {
+        while let Some(group) = self.group.as_mut().filter(|group| group.is_holding()) {
+            // skip the missing packet
+            group.next_shard += 1;
+            self.deliver_in_order();
+        }
+        self.hold_start = None;
+    }
+
+    fn recover(&mut self) -> StrResult {
+        let group = self.group.as_mut().unwrap();
+
+        // All parity shards have the size of the padded data shards
+        let shard_size = match group.shards[group.data_shards..].iter().flatten().next() {
+            Some(shard) => shard.len(),
+            None => return Ok(()),
+        };
+
+        let mut shards = group.shards.clone();
+        for shard in shards[..group.data_shards].iter_mut().flatten() {
+            if shard.len() > shard_size {
+                return fmt_e!("FEC shard too big");
+            }
+            shard.resize(shard_size, 0);
+        }
+
+        ReedSolomon::new(group.data_shards, group.parity_shards)
+            .map_err(err!())?
+            .reconstruct_data(&mut shards)
+            .map_err(err!())?;
+
+        let missing_shards = group
+            .shards
+            .iter_mut()
+            .zip(shards)
+            .take(group.data_shards)
+            .skip(group.next_shard)
+            .filter(|(shard, _)| shard.is_none());
+        for (shard, recovered_shard) in missing_shards {
+            if let Some(mut recovered_shard) = recovered_shard {
+                let packet_size = (&recovered_shard[..4]).get_u32() as usize;
+                if 4 + packet_size > recovered_shard.len() {
+                    return fmt_e!("Invalid recovered FEC shard");
+                }
+                recovered_shard.truncate(4 + packet_size);
+
+                *shard = Some(recovered_shard);
+                self.statistics.packets_recovered += 1;
+            }
+        }
+
+        self.deliver_in_order();
+
+        Ok(())
+    }
+
+    // Called when the packets of a new group start arriving
+    fn finish_group(&mut self) {
+        self.release_held();
+
+        if let Some(group) = &self.group {
+            // Without parity packets the actual size of the group is unknown, only the shards up
+            // to the last received one are counted
+            let shards_count = if group.shards[group.data_shards..]
+                .iter()
+                .any(|s| s.is_some())
+            {
+                group.shards.len()
+            } else {
+                group.last_received_shard + 1
+            };
+            self.window_shards += shards_count as u32;
+            self.window_lost_shards += (shards_count - group.received_shards) as u32;
+        }
+    }
+
+    // Loss report for the sender: [shards (u32)][lost shards (u32)]
+    pub fn take_feedback(&mut self) -> Option<BytesMut> {
+        if self.last_feedback.elapsed() < FEEDBACK_INTERVAL || self.window_shards == 0 {
+            return None;
+        }
+
+        let mut feedback = BytesMut::with_capacity(8);
+        feedback.put_u32(self.window_shards);
+        feedback.put_u32(self.window_lost_shards);
+
+        self.window_shards = 0;
+        self.window_lost_shards = 0;
+        self.last_feedback = Instant::now();
+
+        Some(feedback)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream_socket::QueueConfig;
+
+    const CONFIG: FecConfig = FecConfig {
+        group_size: 4,
+        min_redundancy: 0.5,
+        max_redundancy: 1.,
+    };
+
+    // Starts with the packet index, followed by a payload of variable size
+    fn test_packet(index: u32) -> Vec<u8> {
+        let mut packet = index.to_be_bytes().to_vec();
+        packet.resize(4 + (index as usize * 37) % 200, index as u8);
+
+        packet
+    }
+
+    fn encoder(config: FecConfig) -> FecEncoder {
+        let feedback_queue = PacketQueue::new(QueueConfig::default_for_stream(0), Arc::default());
+
+        FecEncoder::new(config, Arc::new(feedback_queue))
+    }
+
+    // Returns the shards in send order. The group is closed early after the packets in `close_after`
+    fn encode(config: FecConfig, packets_count: u32, close_after: &[u32]) -> Vec<(bool, BytesMut)> {
+        let mut encoder = encoder(config);
+
+        let mut shards = vec![];
+        for index in 0..packets_count {
+            let packet = test_packet(index);
+            let (header, parity_packets) = encoder.add_packet(&packet).unwrap();
+
+            let mut bytes = BytesMut::new();
+            header.write(&mut bytes);
+            bytes.extend_from_slice(&packet);
+            shards.push((false, bytes));
+            shards.extend(parity_packets.into_iter().map(|bytes| (true, bytes)));
+
+            if close_after.contains(&index) {
+                let parity_packets = encoder.close_group().unwrap();
+                shards.extend(parity_packets.into_iter().map(|bytes| (true, bytes)));
+            }
+        }
+
+        shards
+    }
+
+    // Returns the indices of the packets delivered in order
+    fn decode(shards: Vec<(bool, BytesMut)>, lost_shards: &[usize]) -> (Vec<u32>, FecDecoder) {
+        let mut decoder = FecDecoder::new();
+
+        let mut indices = vec![];
+        for (shard_index, (is_parity, bytes)) in shards.into_iter().enumerate() {
+            if lost_shards.contains(&shard_index) {
+                continue;
+            }
+
+            decoder.push_shard(is_parity, bytes).unwrap();
+            while let Some(packet) = decoder.pop_packet() {
+                let index = u32::from_be_bytes(packet[..4].try_into().unwrap());
+                assert_eq!(&packet[..], test_packet(index));
+                indices.push(index);
+            }
+        }
+
+        decoder.release_held();
+        while let Some(packet) = decoder.pop_packet() {
+            indices.push(u32::from_be_bytes(packet[..4].try_into().unwrap()));
+        }
+
+        (indices, decoder)
+    }
+
+    #[test]
+    fn lost_packets_are_recovered() {
+        // Each group is sent as 4 data shards followed by 2 parity shards. Up to 2 shards per
+        // group can be lost
+        let shards = encode(CONFIG, 12, &[]);
+        assert_eq!(shards.iter().filter(|(is_parity, _)| *is_parity).count(), 6);
+
+        let (indices, decoder) = decode(shards, &[1, 6, 7, 14]);
+
+        assert_eq!(indices, (0..12).collect::<Vec<_>>());
+        assert_eq!(decoder.statistics.packets_recovered, 4);
+        assert_eq!(decoder.statistics.parity_packets_received, 6);
+    }
+
+    #[test]
+    fn group_with_too_many_losses_is_skipped() {
+        let (indices, decoder) = decode(encode(CONFIG, 8, &[]), &[0, 1, 2]);
+
+        assert_eq!(indices, vec![3, 4, 5, 6, 7]);
+        assert_eq!(decoder.statistics.packets_recovered, 0);
+    }
+
+    #[test]
+    fn packets_are_not_recovered_without_redundancy() {
+        let config = FecConfig {
+            min_redundancy: 0.,
+            max_redundancy: 0.,
+            ..CONFIG
+        };
+
+        let (indices, _) = decode(encode(config, 8, &[]), &[1]);
+
+        assert_eq!(indices, vec![0, 2, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn closed_groups_are_recovered() {
+        // Shards: 0 1 P | 2 3 4 5 P P
+        let (indices, decoder) = decode(encode(CONFIG, 6, &[1]), &[1]);
+        assert_eq!(indices, (0..6).collect::<Vec<_>>());
+        assert_eq!(decoder.statistics.packets_recovered, 1);
+
+        let (indices, decoder) = decode(encode(CONFIG, 6, &[1]), &[0, 4]);
+        assert_eq!(indices, (0..6).collect::<Vec<_>>());
+        assert_eq!(decoder.statistics.packets_recovered, 2);
+    }
+
+    #[test]
+    fn redundancy_follows_loss_rate() {
+        let mut encoder = encoder(FecConfig::default());
+        assert_eq!(parity_shards_count(4, encoder.redundancy()), 1);
+
+        encoder.loss_rate = 0.2;
+        assert_eq!(parity_shards_count(4, encoder.redundancy()), 3);
+
+        encoder.loss_rate = 1.;
+        assert_eq!(parity_shards_count(4, encoder.redundancy()), 4);
+
+        // The shard index must fit in a u8
+        assert_eq!(parity_shards_count(200, 1.), 55);
+    }
+}
@@ -0,0 +1,271 @@
+// Messages bigger than the maximum packet size are split into fragments, sent as consecutive
+// packets. After the packet index, each packet contains [fragment (u16)][fragments count (u16)]
+// followed by its part of the message. The receiver concatenates the fragments of a message once
+// all of them arrived. Incomplete messages are dropped when a newer message is delivered or when
+// they are not completed in time.
+
+use alvr_common::prelude::*;
+use bytes::{Buf, BufMut, BytesMut};
+use std::time::{Duration, Instant};
+
+pub const FRAGMENT_HEADER_SIZE: usize = 4;
+
+const MAX_REASSEMBLY_TIME: Duration = Duration::from_millis(500);
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FragmentHeader {
+    pub fragment: u16,
+    pub fragments_count: u16,
+}
+
+impl FragmentHeader {
+    pub fn write(&self, mut buffer: impl BufMut) {
+        buffer.put_u16(self.fragment);
+        buffer.put_u16(self.fragments_count);
+    }
+
+    pub fn read(buffer: &mut impl Buf) -> StrResult<Self> {
+        if buffer.remaining() < FRAGMENT_HEADER_SIZE {
+            return fmt_e!("Packet too small");
+        }
+
+        let header = Self {
+            fragment: buffer.get_u16(),
+            fragments_count: buffer.get_u16(),
+        };
+        if header.fragment >= header.fragments_count {
+            return fmt_e!("Invalid fragment {header:?}");
+        }
+
+        Ok(header)
+    }
+}
+
+// The packets of a message have consecutive indices, starting from first_index
+pub struct Message {
+    pub first_index: u32,
+    pub fragments_count: u16,
+    pub bytes: BytesMut,
+}
+
+struct PartialMessage {
+    first_index: u32,
+    fragments: Vec<Option<BytesMut>>,
+    received_fragments: usize,
+    start: Instant,
+}
+
+#[derive(Default)]
+pub struct Reassembler {
+    partial_messages: Vec<PartialMessage>,
+    // Index after the last delivered message
+    next_index: Option<u32>,
+    pub messages_dropped: u64,
+}
+
+impl Reassembler {
+    // `bytes` starts after the fragment header. Returns the message once it is complete
+    pub fn push_fragment(
+        &mut self,
+        packet_index: u32,
+        header: FragmentHeader,
+        bytes: BytesMut,
+    ) -> StrResult<Option<Message>> {
+        let first_index = packet_index.wrapping_sub(header.fragment as u32);
+
+        // Messages that fit in a single packet are delivered without copies
+        if header.fragments_count == 1 {
+            self.drop_older(first_index);
+            self.next_index = Some(first_index.wrapping_add(1));
+
+            return Ok(Some(Message {
+                first_index,
+                fragments_count: 1,
+                bytes,
+            }));
+        }
+
+        // Fragments of messages that have been delivered or dropped arrived too late
+        if matches!(self.next_index, Some(index) if (first_index.wrapping_sub(index) as i32) < 0) {
+            return Ok(None);
+        }
+
+        let partial_messages_count = self.partial_messages.len();
+        self.partial_messages
+            .retain(|message| message.start.elapsed() < MAX_REASSEMBLY_TIME);
+        self.messages_dropped += (partial_messages_count - self.partial_messages.len()) as u64;
+
+        let message_index = match self
+            .partial_messages
+            .iter()
+            .position(|message| message.first_index == first_index)
+        {
+            Some(index) => index,
+            None => {
+                self.partial_messages.push(PartialMessage {
+                    first_index,
+                    fragments: vec![None; header.fragments_count as usize],
+                    received_fragments: 0,
+                    start: Instant::now(),
+                });
+
+                self.partial_messages.len() - 1
+            }
+        };
+
+        let message = &mut self.partial_messages[message_index];
+        if message.fragments.len() != header.fragments_count as usize {
+            return fmt_e!("Invalid fragment {header:?}");
+        }
+
+        let fragment = &mut message.fragments[header.fragment as usize];
+        if fragment.is_some() {
+            return Ok(None);
+        }
+        *fragment = Some(bytes);
+        message.received_fragments += 1;
+
+        if message.received_fragments < message.fragments.len() {
+            return Ok(None);
+        }
+
+        let message = self.partial_messages.remove(message_index);
+        let fragments = message.fragments.into_iter().flatten().collect::<Vec<_>>();
+
+        let mut bytes = BytesMut::with_capacity(fragments.iter().map(|f| f.len()).sum());
+        for fragment in fragments {
+            bytes.put(fragment);
+        }
+
+        self.drop_older(first_index);
+        self.next_index = Some(first_index.wrapping_add(header.fragments_count as u32));
+
+        Ok(Some(Message {
+            first_index,
+            fragments_count: header.fragments_count,
+            bytes,
+        }))
+    }
+
+    // Messages are delivered in order, the fragments of older messages are not needed anymore
+    fn drop_older(&mut self, first_index: u32) {
+        let partial_messages_count = self.partial_messages.len();
+        self.partial_messages
+            .retain(|message| (message.first_index.wrapping_sub(first_index) as i32) > 0);
+        self.messages_dropped += (partial_messages_count - self.partial_messages.len()) as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push(
+        reassembler: &mut Reassembler,
+        packet_index: u32,
+        fragment: u16,
+        fragments_count: u16,
+        bytes: &[u8],
+    ) -> Option<Message> {
+        let header = FragmentHeader {
+            fragment,
+            fragments_count,
+        };
+
+        reassembler
+            .push_fragment(packet_index, header, BytesMut::from(bytes))
+            .unwrap()
+    }
+
+    #[test]
+    fn fragments_in_order_are_reassembled() {
+        let mut reassembler = Reassembler::default();
+
+        assert!(push(&mut reassembler, 10, 0, 3, b"ab").is_none());
+        assert!(push(&mut reassembler, 11, 1, 3, b"cd").is_none());
+        let message = push(&mut reassembler, 12, 2, 3, b"ef").unwrap();
+
+        assert_eq!(message.first_index, 10);
+        assert_eq!(message.fragments_count, 3);
+        assert_eq!(&message.bytes[..], b"abcdef");
+
+        let message = push(&mut reassembler, 13, 0, 1, b"gh").unwrap();
+        assert_eq!(message.first_index, 13);
+        assert_eq!(&message.bytes[..], b"gh");
+    }
+
+    #[test]
+    fn fragments_out_of_order_are_reassembled() {
+        let mut reassembler = Reassembler::default();
+
+        assert!(push(&mut reassembler, 12, 2, 3, b"ef").is_none());
+        assert!(push(&mut reassembler, 10, 0, 3, b"ab").is_none());
+        let message = push(&mut reassembler, 11, 1, 3, b"cd").unwrap();
+
+        assert_eq!(message.first_index, 10);
+        assert_eq!(&message.bytes[..], b"abcdef");
+        assert!(reassembler.partial_messages.is_empty());
+    }
+
+    #[test]
+    fn duplicate_fragments_are_ignored() {
+        let mut reassembler = Reassembler::default();
+
+        assert!(push(&mut reassembler, 10, 0, 2, b"ab").is_none());
+        assert!(push(&mut reassembler, 10, 0, 2, b"xx").is_none());
+        let message = push(&mut reassembler, 11, 1, 2, b"cd").unwrap();
+        assert_eq!(&message.bytes[..], b"abcd");
+
+        // The message has already been delivered
+        assert!(push(&mut reassembler, 11, 1, 2, b"cd").is_none());
+        assert!(reassembler.partial_messages.is_empty());
+        assert_eq!(reassembler.messages_dropped, 0);
+    }
+
+    #[test]
+    fn incomplete_messages_are_dropped() {
+        let mut reassembler = Reassembler::default();
+
+        // Dropped when a newer message is delivered
+        assert!(push(&mut reassembler, 10, 0, 2, b"ab").is_none());
+        assert!(push(&mut reassembler, 12, 0, 1, b"cd").is_some());
+        assert_eq!(reassembler.messages_dropped, 1);
+
+        // Its last fragment arrives too late
+        assert!(push(&mut reassembler, 11, 1, 2, b"ef").is_none());
+        assert!(reassembler.partial_messages.is_empty());
+
+        // Dropped when not completed in time
+        assert!(push(&mut reassembler, 13, 0, 2, b"ab").is_none());
+        reassembler.partial_messages[0].start -= MAX_REASSEMBLY_TIME;
+        assert!(push(&mut reassembler, 15, 0, 2, b"cd").is_none());
+        assert_eq!(reassembler.messages_dropped, 2);
+
+        // A late fragment of the expired message is held until a newer message is delivered
+        assert!(push(&mut reassembler, 14, 1, 2, b"ef").is_none());
+        assert_eq!(reassembler.partial_messages.len(), 2);
+        assert!(push(&mut reassembler, 16, 1, 2, b"gh").is_some());
+        assert_eq!(reassembler.messages_dropped, 3);
+    }
+
+    #[test]
+    fn messages_are_reassembled_across_index_wraparound() {
+        let mut reassembler = Reassembler::default();
+
+        assert!(push(&mut reassembler, u32::MAX - 1, 0, 4, b"ab").is_none());
+        assert!(push(&mut reassembler, 1, 3, 4, b"gh").is_none());
+        assert!(push(&mut reassembler, u32::MAX, 1, 4, b"cd").is_none());
+        let message = push(&mut reassembler, 0, 2, 4, b"ef").unwrap();
+
+        assert_eq!(message.first_index, u32::MAX - 1);
+        assert_eq!(&message.bytes[..], b"abcdefgh");
+
+        // Messages before the wraparound are older
+        assert!(push(&mut reassembler, u32::MAX - 3, 1, 2, b"ab").is_none());
+        assert!(reassembler.partial_messages.is_empty());
+
+        let message = push(&mut reassembler, 2, 0, 1, b"ij").unwrap();
+        assert_eq!(message.first_index, 2);
+        assert_eq!(reassembler.messages_dropped, 0);
+    }
+}
@@ -1,42 +1,107 @@
 // Note: for StreamSocket, the client uses a server socket, the server uses a client socket.
 // This is because of certificate management. The server needs to trust a client and its certificate
 //
+// Packets are encrypted and authenticated with the StreamKey obtained from the control socket, for
+// every protocol.
+//
+// Packets are not written to the socket directly. They are queued per stream and sent by
+// StreamSocket::send_loop() in priority order, see QueueConfig. Received packets that arrive out of
+// order are held for a short time to be delivered in order, see ReorderBuffer.
+//
+// A StreamRecorder can be attached to the socket to save the decrypted packets to disk. Recordings
+// are played back by a StreamSocket created with StreamPlayer::open().
+//
 // StreamSender and StreamReceiver endpoints allow for convenient conversion of the header to/from
 // bytes while still handling the additional byte buffer with zero copies and extra allocations.
-
+//
+// Decrypted packet layout:
+// [kind (u8)][FEC shard header, for FEC packets][u32 index][fragment header][header][buffer]
+// Parity packets contain the parity shard in place of the index, fragment header, header and
+// buffer. Header and buffer are split into fragments if they do not fit in max_packet_size.
+
+mod fec;
+mod fragmentation;
+mod queue;
+mod quic;
+mod recording;
+mod reorder;
 mod tcp;
 mod throttled_udp;
 mod udp;
 
-use alvr_common::prelude::*;
+use crate::{security::StreamCipher, StreamKey};
+use alvr_common::{parking_lot, prelude::*};
 use alvr_session::SocketProtocol;
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use fec::{FecDecoder, FecEncoder, FEC_FEEDBACK_FLAG, SHARD_HEADER_SIZE};
+use fragmentation::{FragmentHeader, Reassembler, FRAGMENT_HEADER_SIZE};
 use futures::SinkExt;
+use queue::PacketQueue;
+use quic::{QuicStreamReceiveSocket, QuicStreamSendSocket};
+use reorder::{default_reorder_window, ReorderBuffer};
 use serde::{de::DeserializeOwned, Serialize};
 use std::{
     collections::HashMap,
     marker::PhantomData,
     net::IpAddr,
     ops::{Deref, DerefMut},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
 };
 use tcp::{TcpStreamReceiveSocket, TcpStreamSendSocket};
 use throttled_udp::{ThrottledUdpStreamReceiveSocket, ThrottledUdpStreamSendSocket};
 use tokio::net;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{Mutex, Notify};
+use tokio::time;
 use udp::{UdpStreamReceiveSocket, UdpStreamSendSocket};
 
+pub use fec::FecConfig;
+pub use queue::{DropPolicy, QueueConfig, StreamPriority};
+pub use recording::{RecordedDirection, StreamPlayer, StreamRecorder};
+
+const PACKET_KIND_PLAIN: u8 = 0;
+const PACKET_KIND_FEC_DATA: u8 = 1;
+const PACKET_KIND_FEC_PARITY: u8 = 2;
+
+type PacketQueues = Arc<Mutex<HashMap<u16, Arc<PacketQueue<BytesMut>>>>>;
+
 #[derive(Clone)]
 enum StreamSendSocket {
     Udp(UdpStreamSendSocket),
     ThrottledUdp(ThrottledUdpStreamSendSocket),
     Tcp(TcpStreamSendSocket),
+    Quic(QuicStreamSendSocket),
+    // packets sent during playback are discarded
+    Replay,
+}
+
+impl StreamSendSocket {
+    async fn send(&self, packet: Bytes) -> StrResult {
+        match self {
+            StreamSendSocket::Udp(socket) => socket
+                .inner
+                .lock()
+                .await
+                .send((packet, socket.peer_addr))
+                .await
+                .map_err(err!()),
+            StreamSendSocket::Tcp(socket) => socket.lock().await.send(packet).await.map_err(err!()),
+            StreamSendSocket::ThrottledUdp(socket) => socket.send(packet).await.map_err(err!()),
+            StreamSendSocket::Quic(socket) => socket.send(packet).await,
+            StreamSendSocket::Replay => Ok(()),
+        }
+    }
 }
 
 enum StreamReceiveSocket {
     Udp(UdpStreamReceiveSocket),
     ThrottledUdp(ThrottledUdpStreamReceiveSocket),
     Tcp(TcpStreamReceiveSocket),
+    Quic(QuicStreamReceiveSocket),
+    Replay(StreamPlayer),
 }
 
 pub struct SendBufferLock<'a> {
@@ -85,37 +150,168 @@ impl<T> SenderBuffer<T> {
 #[derive(Clone)]
 pub struct StreamSender<T> {
     stream_id: u16,
-    socket: StreamSendSocket,
-    // if the packet index overflows the worst that happens is a false positive packet loss
-    next_packet_index: u32,
+    // shared by the clones of the sender
+    queue: Arc<PacketQueue<Bytes>>,
+    cipher: Arc<StreamCipher>,
+    recorder: Option<Arc<StreamRecorder>>,
+    max_packet_size: usize,
+    // if the packet index overflows the worst that happens is a false positive packet loss.
+    // Shared by the clones of the sender, otherwise the receiver would drop the packets of a new
+    // clone as late
+    next_packet_index: Arc<AtomicU32>,
+    // shared by the clones of the sender, so they fill the same groups
+    fec_encoder: Option<Arc<parking_lot::Mutex<FecEncoder>>>,
     _phantom: PhantomData<T>,
 }
 
 impl<T> StreamSender<T> {
+    // Offset of the packet index, after the stream ID, the encryption header and the packet kind
+    fn index_offset(&self) -> usize {
+        let kind_offset = 2 + StreamCipher::HEADER_SIZE;
+        if self.fec_encoder.is_some() {
+            kind_offset + 1 + SHARD_HEADER_SIZE
+        } else {
+            kind_offset + 1
+        }
+    }
+
+    // Offset of the message, after the packet index and the fragment header
+    fn message_offset(&self) -> usize {
+        self.index_offset() + 4 + FRAGMENT_HEADER_SIZE
+    }
+
+    // Messages are split in parts of this size, so packets are not bigger than max_packet_size
+    pub(crate) fn max_fragment_size(&self) -> usize {
+        let mut overhead = self.message_offset() + StreamCipher::TRAILER_SIZE;
+        // parity shards also contain the size of the data packet
+        if self.fec_encoder.is_some() {
+            overhead += 4;
+        }
+
+        self.max_packet_size.saturating_sub(overhead).max(1)
+    }
+
+    // Packets dropped because the send queue was full
+    pub fn packets_dropped(&self) -> u64 {
+        self.queue.packets_dropped()
+    }
+
+    // Parity packets per data packet, None if the stream does not use FEC
+    pub fn fec_redundancy(&self) -> Option<f32> {
+        self.fec_encoder
+            .as_ref()
+            .map(|encoder| encoder.lock().redundancy())
+    }
+
+    async fn send_packet(&self, mut packet: BytesMut) -> StrResult {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(
+                RecordedDirection::Sent,
+                self.stream_id,
+                &packet[2 + StreamCipher::HEADER_SIZE..],
+            );
+        }
+
+        self.cipher.encrypt(&mut packet)?;
+
+        self.queue.push_wait(packet.freeze()).await
+    }
+
+    async fn send_parity_packets(&self, parity_packets: Vec<BytesMut>) -> StrResult {
+        for parity_packet in parity_packets {
+            let mut packet = BytesMut::with_capacity(
+                2 + StreamCipher::HEADER_SIZE
+                    + 1
+                    + parity_packet.len()
+                    + StreamCipher::TRAILER_SIZE,
+            );
+            packet.put_u16(self.stream_id);
+            packet.put_bytes(0, StreamCipher::HEADER_SIZE);
+            packet.put_u8(PACKET_KIND_FEC_PARITY);
+            packet.put_slice(&parity_packet);
+
+            self.send_packet(packet).await?;
+        }
+
+        Ok(())
+    }
+
+    // `packet` has space for the packet index and the FEC shard header, they are filled here
+    async fn send_fragment(&self, mut packet: BytesMut, packet_index: u32) -> StrResult {
+        let index_offset = self.index_offset();
+        packet[index_offset..index_offset + 4].copy_from_slice(&packet_index.to_be_bytes());
+
+        let parity_packets = if let Some(encoder) = &self.fec_encoder {
+            let mut encoder = encoder.lock();
+            encoder.process_feedback(&self.cipher, self.stream_id);
+
+            let (shard_header, parity_packets) = encoder.add_packet(&packet[index_offset..])?;
+            shard_header.write(&mut packet[index_offset - SHARD_HEADER_SIZE..index_offset]);
+
+            parity_packets
+        } else {
+            vec![]
+        };
+
+        self.send_packet(packet).await?;
+
+        self.send_parity_packets(parity_packets).await
+    }
+
     // The buffer is moved into the method. There is no way of reusing the same buffer twice without
-    // extra copies/allocations
+    // extra copies/allocations. Buffers bigger than max_packet_size are copied into fragments.
     pub async fn send_buffer(&mut self, mut buffer: SenderBuffer<T>) -> StrResult {
-        buffer.inner[2..6].copy_from_slice(&self.next_packet_index.to_be_bytes());
-        self.next_packet_index += 1;
+        let message_offset = self.message_offset();
+        let max_fragment_size = self.max_fragment_size();
+        let message_size = buffer.inner.len() - message_offset;
+
+        if message_size <= max_fragment_size {
+            FragmentHeader {
+                fragment: 0,
+                fragments_count: 1,
+            }
+            .write(&mut buffer.inner[message_offset - FRAGMENT_HEADER_SIZE..message_offset]);
 
-        match &self.socket {
-            StreamSendSocket::Udp(socket) => socket
-                .inner
-                .lock()
-                .await
-                .send((buffer.inner.freeze(), socket.peer_addr))
-                .await
-                .map_err(err!()),
-            StreamSendSocket::Tcp(socket) => socket
-                .lock()
-                .await
-                .send(buffer.inner.freeze())
-                .await
-                .map_err(err!()),
-            StreamSendSocket::ThrottledUdp(socket) => {
-                socket.send(buffer.inner.freeze()).await.map_err(err!())
+            let packet_index = self.next_packet_index.fetch_add(1, Ordering::Relaxed);
+            return self.send_fragment(buffer.inner, packet_index).await;
+        }
+
+        let fragments_count = (message_size + max_fragment_size - 1) / max_fragment_size;
+        if fragments_count > u16::MAX as usize {
+            return fmt_e!("Message too big ({message_size} bytes)");
+        }
+
+        // The fragments need consecutive indices, even if a clone of the sender sends at the same
+        // time
+        let first_index = self
+            .next_packet_index
+            .fetch_add(fragments_count as u32, Ordering::Relaxed);
+
+        let fragments = buffer.inner[message_offset..].chunks(max_fragment_size);
+        for (fragment, fragment_bytes) in fragments.enumerate() {
+            let mut packet = BytesMut::with_capacity(
+                message_offset + fragment_bytes.len() + StreamCipher::TRAILER_SIZE,
+            );
+            // stream ID, encryption header, packet kind and space for the FEC and index fields
+            packet.put_slice(&buffer.inner[..message_offset - FRAGMENT_HEADER_SIZE]);
+            FragmentHeader {
+                fragment: fragment as u16,
+                fragments_count: fragments_count as u16,
             }
+            .write(&mut packet);
+            packet.put_slice(fragment_bytes);
+
+            self.send_fragment(packet, first_index.wrapping_add(fragment as u32))
+                .await?;
         }
+
+        // The parity packets of the last fragments are sent right away, instead of after the
+        // next messages
+        let parity_packets = match &self.fec_encoder {
+            Some(encoder) => encoder.lock().close_group()?,
+            None => vec![],
+        };
+        self.send_parity_packets(parity_packets).await
     }
 }
 
@@ -127,14 +323,28 @@ impl<T: Serialize> StreamSender<T> {
     ) -> StrResult<SenderBuffer<T>> {
         let header_size = bincode::serialized_size(header).map_err(err!())?;
         // the first two bytes are for the stream ID
-        let offset = 2 + 4 + header_size as usize;
+        let offset = self.message_offset() + header_size as usize;
 
-        let mut buffer = BytesMut::with_capacity(offset + preferred_max_buffer_size);
+        let mut buffer = BytesMut::with_capacity(
+            offset + preferred_max_buffer_size + StreamCipher::TRAILER_SIZE,
+        );
 
         buffer.put_u16(self.stream_id);
 
-        // make space for the packet index
+        // make space for the encryption header
+        buffer.put_bytes(0, StreamCipher::HEADER_SIZE);
+
+        // the FEC shard header is filled when the packet is sent
+        if self.fec_encoder.is_some() {
+            buffer.put_u8(PACKET_KIND_FEC_DATA);
+            buffer.put_bytes(0, SHARD_HEADER_SIZE);
+        } else {
+            buffer.put_u8(PACKET_KIND_PLAIN);
+        }
+
+        // make space for the packet index and the fragment header
         buffer.put_u32(0);
+        buffer.put_bytes(0, FRAGMENT_HEADER_SIZE);
 
         let mut buffer_writer = buffer.writer();
         bincode::serialize_into(&mut buffer_writer, header).map_err(err!())?;
@@ -153,8 +363,7 @@ impl<T: Serialize> StreamSender<T> {
 }
 
 enum StreamReceiverType {
-    Queue(mpsc::UnboundedReceiver<BytesMut>),
-    // QuicReliable(...)
+    Queue(Arc<PacketQueue<BytesMut>>),
 }
 
 pub struct ReceivedPacket<T> {
@@ -163,23 +372,204 @@ pub struct ReceivedPacket<T> {
     pub had_packet_loss: bool,
 }
 
+// Counters since the stream has been subscribed
+#[derive(Clone, Copy, Default, Debug)]
+pub struct StreamReceiverStatistics {
+    // Packets of the delivered messages, including the recovered ones
+    pub packets_received: u64,
+    // Packets recovered with FEC
+    pub packets_recovered: u64,
+    // Packets of the messages that have never been delivered
+    pub packets_lost: u64,
+    pub parity_packets_received: u64,
+    // Packets dropped because the receive queue was full
+    pub packets_dropped: u64,
+    // Packets that arrived out of order but in time to be delivered in order
+    pub packets_reordered: u64,
+    // Packets dropped because they arrived after the following ones had been delivered. They are
+    // included in packets_lost
+    pub packets_late: u64,
+    pub packets_duplicated: u64,
+    // Fragmented messages dropped because some fragments did not arrive in time
+    pub messages_dropped: u64,
+}
+
 pub struct StreamReceiver<T> {
+    stream_id: u16,
     receiver: StreamReceiverType,
+    cipher: Arc<StreamCipher>,
+    recorder: Option<Arc<StreamRecorder>>,
+    // used to send the FEC feedback
+    feedback_queue: Arc<PacketQueue<Bytes>>,
+    fec_decoder: FecDecoder,
+    reorder_buffer: ReorderBuffer,
+    reassembler: Reassembler,
     next_packet_index: u32,
+    packets_received: u64,
+    packets_lost: u64,
     _phantom: PhantomData<T>,
 }
 
+impl<T> StreamReceiver<T> {
+    pub fn statistics(&self) -> StreamReceiverStatistics {
+        StreamReceiverStatistics {
+            packets_received: self.packets_received,
+            packets_recovered: self.fec_decoder.statistics.packets_recovered,
+            packets_lost: self.packets_lost,
+            parity_packets_received: self.fec_decoder.statistics.parity_packets_received,
+            packets_dropped: match &self.receiver {
+                StreamReceiverType::Queue(queue) => queue.packets_dropped(),
+            },
+            packets_reordered: self.reorder_buffer.statistics.packets_reordered,
+            packets_late: self.reorder_buffer.statistics.packets_late,
+            packets_duplicated: self.reorder_buffer.statistics.packets_duplicated,
+            messages_dropped: self.reassembler.messages_dropped,
+        }
+    }
+
+    // Waits for the next authenticated packet. It starts with the packet kind
+    async fn recv_packet(&mut self) -> StrResult<BytesMut> {
+        loop {
+            let mut bytes = match &mut self.receiver {
+                StreamReceiverType::Queue(queue) => queue.pop().await.ok_or_else(enone!())?,
+            };
+
+            // Packets that fail authentication are dropped, they must not interrupt the stream
+            match self.cipher.decrypt(self.stream_id, &mut bytes) {
+                Ok(()) => {
+                    if let Some(recorder) = &self.recorder {
+                        recorder.record(RecordedDirection::Received, self.stream_id, &bytes);
+                    }
+
+                    return Ok(bytes);
+                }
+                Err(e) => debug!("Dropped invalid packet: {e}"),
+            }
+        }
+    }
+
+    fn send_fec_feedback(&self, feedback: BytesMut) -> StrResult {
+        let mut packet = BytesMut::with_capacity(
+            2 + StreamCipher::HEADER_SIZE + feedback.len() + StreamCipher::TRAILER_SIZE,
+        );
+        packet.put_u16(self.stream_id | FEC_FEEDBACK_FLAG);
+        packet.put_bytes(0, StreamCipher::HEADER_SIZE);
+        packet.put_slice(&feedback);
+
+        self.cipher.encrypt(&mut packet)?;
+
+        self.feedback_queue.push(packet.freeze())
+    }
+
+    // Waits for the next data packet, in order if the stream uses FEC. It starts with the index
+    async fn recv_data_packet(&mut self) -> StrResult<BytesMut> {
+        loop {
+            if let Some(bytes) = self.fec_decoder.pop_packet() {
+                return Ok(bytes);
+            }
+
+            // Packets held by FEC are released if the lost ones are not recovered in time
+            let mut bytes = if let Some(deadline) = self.fec_decoder.hold_deadline() {
+                match time::timeout_at(deadline.into(), self.recv_packet()).await {
+                    Ok(res) => res?,
+                    Err(_) => {
+                        self.fec_decoder.release_held();
+                        continue;
+                    }
+                }
+            } else {
+                self.recv_packet().await?
+            };
+
+            if !bytes.has_remaining() {
+                continue;
+            }
+            match bytes.get_u8() {
+                PACKET_KIND_PLAIN => return Ok(bytes),
+                kind @ (PACKET_KIND_FEC_DATA | PACKET_KIND_FEC_PARITY) => {
+                    if let Err(e) = self
+                        .fec_decoder
+                        .push_shard(kind == PACKET_KIND_FEC_PARITY, bytes)
+                    {
+                        debug!("Dropped invalid FEC packet: {e}");
+                    }
+
+                    if let Some(feedback) = self.fec_decoder.take_feedback() {
+                        self.send_fec_feedback(feedback).ok();
+                    }
+                }
+                kind => debug!("Dropped packet of unknown kind {kind}"),
+            }
+        }
+    }
+
+    // Waits for the next data packet in order of index. Packets that arrive early are held until
+    // the missing ones arrive or the reorder window elapses
+    async fn recv_ordered_packet(&mut self) -> StrResult<BytesMut> {
+        loop {
+            if let Some(bytes) = self.reorder_buffer.pop_packet() {
+                return Ok(bytes);
+            }
+
+            let bytes = if let Some(deadline) = self.reorder_buffer.hold_deadline() {
+                match time::timeout_at(deadline.into(), self.recv_data_packet()).await {
+                    Ok(res) => res?,
+                    Err(_) => {
+                        self.reorder_buffer.release_held();
+                        continue;
+                    }
+                }
+            } else {
+                self.recv_data_packet().await?
+            };
+
+            if bytes.len() < 4 {
+                return fmt_e!("Packet too small");
+            }
+            let packet_index = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+            self.reorder_buffer.push_packet(packet_index, bytes);
+        }
+    }
+}
+
+// The receive loop fails when it receives packets for a dropped receiver
+impl<T> Drop for StreamReceiver<T> {
+    fn drop(&mut self) {
+        match &self.receiver {
+            StreamReceiverType::Queue(queue) => queue.close(),
+        }
+    }
+}
+
 impl<T: DeserializeOwned> StreamReceiver<T> {
     pub async fn recv(&mut self) -> StrResult<ReceivedPacket<T>> {
-        let mut bytes = match &mut self.receiver {
-            StreamReceiverType::Queue(receiver) => receiver.recv().await.ok_or_else(enone!())?,
+        let message = loop {
+            let mut bytes = self.recv_ordered_packet().await?;
+
+            let packet_index = bytes.get_u32();
+            let fragment_header = FragmentHeader::read(&mut bytes)?;
+
+            if let Some(message) =
+                self.reassembler
+                    .push_fragment(packet_index, fragment_header, bytes)?
+            {
+                break message;
+            }
         };
 
-        let packet_index = bytes.get_u32();
-        let had_packet_loss = packet_index != self.next_packet_index;
-        self.next_packet_index = packet_index + 1;
+        let had_packet_loss = message.first_index != self.next_packet_index;
+        // Packets recovered after being skipped are not delivered, so the index never goes back
+        let lost_packets = message.first_index.wrapping_sub(self.next_packet_index);
+        if lost_packets < u32::MAX / 2 {
+            self.packets_lost += lost_packets as u64;
+        }
+        self.next_packet_index = message
+            .first_index
+            .wrapping_add(message.fragments_count as u32);
+        self.packets_received += message.fragments_count as u64;
 
-        let mut bytes_reader = bytes.reader();
+        let mut bytes_reader = message.bytes.reader();
         let header = bincode::deserialize_from(&mut bytes_reader).map_err(err!())?;
         let buffer = bytes_reader.into_inner();
 
@@ -196,6 +586,7 @@ pub enum StreamSocketBuilder {
     Tcp(net::TcpListener),
     Udp(net::UdpSocket),
     ThrottledUdp(net::UdpSocket),
+    Quic(quinn::Endpoint, quinn::Incoming),
 }
 
 impl StreamSocketBuilder {
@@ -209,10 +600,20 @@ impl StreamSocketBuilder {
             SocketProtocol::ThrottledUdp { .. } => {
                 StreamSocketBuilder::ThrottledUdp(throttled_udp::listen_for_server(port).await?)
             }
+            SocketProtocol::Quic => {
+                let (endpoint, incoming) = quic::listen_for_server(port).await?;
+                StreamSocketBuilder::Quic(endpoint, incoming)
+            }
         })
     }
 
-    pub async fn accept_from_server(self, server_ip: IpAddr, port: u16) -> StrResult<StreamSocket> {
+    pub async fn accept_from_server(
+        self,
+        server_ip: IpAddr,
+        port: u16,
+        stream_key: StreamKey,
+        max_packet_size: usize,
+    ) -> StrResult<StreamSocket> {
         let (send_socket, receive_socket) = match self {
             StreamSocketBuilder::Udp(socket) => {
                 let (send_socket, receive_socket) = udp::connect(socket, server_ip, port).await?;
@@ -237,12 +638,27 @@ impl StreamSocketBuilder {
                     StreamReceiveSocket::ThrottledUdp(receive_socket),
                 )
             }
+            StreamSocketBuilder::Quic(endpoint, incoming) => {
+                let (send_socket, receive_socket) =
+                    quic::accept_from_server(endpoint, incoming, server_ip).await?;
+                (
+                    StreamSendSocket::Quic(send_socket),
+                    StreamReceiveSocket::Quic(receive_socket),
+                )
+            }
         };
 
         Ok(StreamSocket {
             send_socket,
             receive_socket: Arc::new(Mutex::new(Some(receive_socket))),
             packet_queues: Arc::new(Mutex::new(HashMap::new())),
+            send_queues: Arc::new(parking_lot::Mutex::new(vec![])),
+            send_notifier: Arc::new(Notify::new()),
+            queue_configs: HashMap::new(),
+            reorder_windows: HashMap::new(),
+            cipher: Arc::new(StreamCipher::new(&stream_key, false)),
+            recorder: None,
+            max_packet_size,
         })
     }
 
@@ -251,6 +667,8 @@ impl StreamSocketBuilder {
         port: u16,
         protocol: SocketProtocol,
         video_byterate: u32,
+        stream_key: StreamKey,
+        max_packet_size: usize,
     ) -> StrResult<StreamSocket> {
         let (send_socket, receive_socket) = match protocol {
             SocketProtocol::Udp => {
@@ -281,12 +699,27 @@ impl StreamSocketBuilder {
                     StreamReceiveSocket::ThrottledUdp(receive_socket),
                 )
             }
+            SocketProtocol::Quic => {
+                let (send_socket, receive_socket) =
+                    quic::connect_to_client(client_ip, port).await?;
+                (
+                    StreamSendSocket::Quic(send_socket),
+                    StreamReceiveSocket::Quic(receive_socket),
+                )
+            }
         };
 
         Ok(StreamSocket {
             send_socket,
             receive_socket: Arc::new(Mutex::new(Some(receive_socket))),
             packet_queues: Arc::new(Mutex::new(HashMap::new())),
+            send_queues: Arc::new(parking_lot::Mutex::new(vec![])),
+            send_notifier: Arc::new(Notify::new()),
+            queue_configs: HashMap::new(),
+            reorder_windows: HashMap::new(),
+            cipher: Arc::new(StreamCipher::new(&stream_key, true)),
+            recorder: None,
+            max_packet_size,
         })
     }
 }
@@ -294,30 +727,148 @@ impl StreamSocketBuilder {
 pub struct StreamSocket {
     send_socket: StreamSendSocket,
     receive_socket: Arc<Mutex<Option<StreamReceiveSocket>>>,
-    packet_queues: Arc<Mutex<HashMap<u16, mpsc::UnboundedSender<BytesMut>>>>,
+    packet_queues: PacketQueues,
+    // sorted by priority, from the highest
+    send_queues: Arc<parking_lot::Mutex<Vec<Arc<PacketQueue<Bytes>>>>>,
+    send_notifier: Arc<Notify>,
+    queue_configs: HashMap<u16, QueueConfig>,
+    reorder_windows: HashMap<u16, Duration>,
+    cipher: Arc<StreamCipher>,
+    recorder: Option<Arc<StreamRecorder>>,
+    // Bigger messages are split into fragments
+    max_packet_size: usize,
 }
 
 impl StreamSocket {
-    pub async fn request_stream<T>(&self, stream_id: u16) -> StrResult<StreamSender<T>> {
+    // Only the streams requested or subscribed after this call are recorded
+    pub fn set_recorder(&mut self, recorder: StreamRecorder) {
+        self.recorder = Some(Arc::new(recorder));
+    }
+
+    // Overrides QueueConfig::default_for_stream(). Only the streams requested or subscribed after
+    // this call use the new configuration
+    pub fn set_queue_config(&mut self, stream_id: u16, config: QueueConfig) {
+        self.queue_configs.insert(stream_id, config);
+    }
+
+    // Overrides the default reorder window of a stream, zero to never hold packets. Only the
+    // streams subscribed after this call use the new window
+    pub fn set_reorder_window(&mut self, stream_id: u16, window: Duration) {
+        self.reorder_windows.insert(stream_id, window);
+    }
+
+    fn queue_config(&self, stream_id: u16) -> QueueConfig {
+        self.queue_configs
+            .get(&stream_id)
+            .copied()
+            .unwrap_or_else(|| QueueConfig::default_for_stream(stream_id))
+    }
+
+    fn add_send_queue(&self, stream_id: u16) -> Arc<PacketQueue<Bytes>> {
+        let queue = Arc::new(PacketQueue::new(
+            self.queue_config(stream_id),
+            Arc::clone(&self.send_notifier),
+        ));
+
+        // Queues with the same priority are served in the order they have been added
+        let mut send_queues = self.send_queues.lock();
+        let index = send_queues
+            .iter()
+            .position(|other| other.priority() < queue.priority())
+            .unwrap_or(send_queues.len());
+        send_queues.insert(index, Arc::clone(&queue));
+
+        queue
+    }
+
+    async fn add_receive_queue(&self, stream_id: u16) -> Arc<PacketQueue<BytesMut>> {
+        let queue = Arc::new(PacketQueue::new(
+            self.queue_config(stream_id),
+            Arc::new(Notify::new()),
+        ));
+        self.packet_queues
+            .lock()
+            .await
+            .insert(stream_id, Arc::clone(&queue));
+
+        queue
+    }
+
+    // With FEC, parity packets are added to the stream and their number adapts to the loss rate
+    // measured by the receiver
+    pub async fn request_stream<T>(
+        &self,
+        stream_id: u16,
+        fec: Option<FecConfig>,
+    ) -> StrResult<StreamSender<T>> {
+        let fec_encoder = if let Some(config) = fec {
+            config.validate()?;
+
+            // the receiver reports the loss rate on the stream ID with the feedback flag set
+            let feedback_queue = self.add_receive_queue(stream_id | FEC_FEEDBACK_FLAG).await;
+
+            Some(Arc::new(parking_lot::Mutex::new(FecEncoder::new(
+                config,
+                feedback_queue,
+            ))))
+        } else {
+            None
+        };
+
         Ok(StreamSender {
             stream_id,
-            socket: self.send_socket.clone(),
-            next_packet_index: 0,
+            queue: self.add_send_queue(stream_id),
+            cipher: Arc::clone(&self.cipher),
+            recorder: self.recorder.clone(),
+            max_packet_size: self.max_packet_size,
+            next_packet_index: Arc::new(AtomicU32::new(0)),
+            fec_encoder,
             _phantom: PhantomData,
         })
     }
 
     pub async fn subscribe_to_stream<T>(&self, stream_id: u16) -> StrResult<StreamReceiver<T>> {
-        let (enqueuer, dequeuer) = mpsc::unbounded_channel();
-        self.packet_queues.lock().await.insert(stream_id, enqueuer);
+        let queue = self.add_receive_queue(stream_id).await;
 
         Ok(StreamReceiver {
-            receiver: StreamReceiverType::Queue(dequeuer),
+            stream_id,
+            receiver: StreamReceiverType::Queue(queue),
+            cipher: Arc::clone(&self.cipher),
+            recorder: self.recorder.clone(),
+            feedback_queue: self.add_send_queue(stream_id | FEC_FEEDBACK_FLAG),
+            fec_decoder: FecDecoder::new(),
+            reorder_buffer: ReorderBuffer::new(
+                self.reorder_windows
+                    .get(&stream_id)
+                    .copied()
+                    .unwrap_or_else(|| default_reorder_window(stream_id)),
+            ),
+            reassembler: Reassembler::default(),
             next_packet_index: 0,
+            packets_received: 0,
+            packets_lost: 0,
             _phantom: PhantomData,
         })
     }
 
+    // Sends the queued packets, starting from the streams with the highest priority. It must run
+    // together with receive_loop()
+    pub async fn send_loop(&self) -> StrResult {
+        loop {
+            let packet_notified = self.send_notifier.notified();
+
+            let maybe_packet = self
+                .send_queues
+                .lock()
+                .iter()
+                .find_map(|queue| queue.try_pop());
+            match maybe_packet {
+                Some(packet) => self.send_socket.send(packet).await?,
+                None => packet_notified.await,
+            }
+        }
+    }
+
     pub async fn receive_loop(&self) -> StrResult {
         match self.receive_socket.lock().await.take().unwrap() {
             StreamReceiveSocket::Udp(socket) => {
@@ -329,6 +880,77 @@ impl StreamSocket {
             StreamReceiveSocket::ThrottledUdp(socket) => {
                 throttled_udp::receive_loop(socket, Arc::clone(&self.packet_queues)).await
             }
+            StreamReceiveSocket::Quic(socket) => {
+                quic::receive_loop(socket, Arc::clone(&self.packet_queues)).await
+            }
+            StreamReceiveSocket::Replay(player) => {
+                recording::receive_loop(player, Arc::clone(&self.packet_queues)).await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AUDIO;
+
+    fn decrypted_packet_index(cipher: &StreamCipher, packet: &[u8]) -> u32 {
+        // The receive loop removes the stream ID
+        let mut packet = BytesMut::from(&packet[2..]);
+        cipher.decrypt(AUDIO, &mut packet).unwrap();
+        assert_eq!(packet.get_u8(), PACKET_KIND_PLAIN);
+
+        packet.get_u32()
+    }
+
+    #[tokio::test]
+    async fn sender_clones_share_packet_index() {
+        let key = StreamKey {
+            server_to_client: [1; 32],
+            client_to_server: [2; 32],
+        };
+        let client_cipher = StreamCipher::new(&key, false);
+
+        let mut sender = StreamSender::<u32> {
+            stream_id: AUDIO,
+            queue: Arc::new(PacketQueue::new(
+                QueueConfig {
+                    priority: StreamPriority::Medium,
+                    capacity: 64,
+                    drop_policy: DropPolicy::DropNewest,
+                },
+                Arc::default(),
+            )),
+            cipher: Arc::new(StreamCipher::new(&key, true)),
+            recorder: None,
+            max_packet_size: 100,
+            next_packet_index: Arc::new(AtomicU32::new(0)),
+            fec_encoder: None,
+            _phantom: PhantomData,
+        };
+        let mut sender_clone = sender.clone();
+
+        let buffer = sender.new_buffer(&0, 0).unwrap();
+        sender.send_buffer(buffer).await.unwrap();
+
+        // Split into 3 fragments
+        let mut buffer = sender_clone.new_buffer(&1, 150).unwrap();
+        buffer.get_mut().extend(vec![0; 150]);
+        sender_clone.send_buffer(buffer).await.unwrap();
+
+        let buffer = sender.new_buffer(&2, 0).unwrap();
+        sender.send_buffer(buffer).await.unwrap();
+
+        let mut reorder_buffer = ReorderBuffer::new(Duration::from_millis(10));
+        let mut packet_indices = vec![];
+        while let Some(packet) = sender.queue.try_pop() {
+            let packet_index = decrypted_packet_index(&client_cipher, &packet);
+            reorder_buffer.push_packet(packet_index, BytesMut::new());
+            packet_indices.push(packet_index);
         }
+
+        assert_eq!(packet_indices, (0..5).collect::<Vec<_>>());
+        assert_eq!(reorder_buffer.statistics.packets_late, 0);
     }
 }
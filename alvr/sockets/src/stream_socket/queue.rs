@@ -0,0 +1,305 @@
+// Bounded packet queues, one per stream and direction. Sent packets wait in the send queues until
+// the send loop picks them, starting from the streams with the highest priority, so small tracking
+// and haptics packets do not wait behind video frames. Received packets wait in the receive queues
+// until the stream consumer reads them. When a queue is full, its DropPolicy decides which packet
+// is discarded.
+
+use super::fec::FEC_FEEDBACK_FLAG;
+use crate::{AUDIO, HAPTICS, PROBE, STATISTICS, TRACKING, VIDEO};
+use alvr_common::{parking_lot::Mutex, prelude::*};
+use std::{collections::VecDeque, sync::Arc};
+use tokio::sync::Notify;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum StreamPriority {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DropPolicy {
+    // For streams where only the latest packets are useful, like video
+    DropOldest,
+    DropNewest,
+    // The sender waits until there is space in the send queue. The receive loop cannot wait, so the
+    // receive queue grows past its capacity.
+    Never,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct QueueConfig {
+    pub priority: StreamPriority,
+    // In packets. Video frames are split into many packets
+    pub capacity: usize,
+    pub drop_policy: DropPolicy,
+}
+
+impl QueueConfig {
+    // Used for the streams that have not been configured with StreamSocket::set_queue_config()
+    pub fn default_for_stream(stream_id: u16) -> Self {
+        // FEC feedback is needed to react quickly to the packet loss
+        if stream_id & FEC_FEEDBACK_FLAG != 0 {
+            return Self {
+                priority: StreamPriority::High,
+                capacity: 16,
+                drop_policy: DropPolicy::DropOldest,
+            };
+        }
+
+        match stream_id {
+            TRACKING | STATISTICS => Self {
+                priority: StreamPriority::High,
+                capacity: 64,
+                drop_policy: DropPolicy::DropOldest,
+            },
+            HAPTICS => Self {
+                priority: StreamPriority::High,
+                capacity: 64,
+                drop_policy: DropPolicy::Never,
+            },
+            // Probe trains must be sent back-to-back, ahead of the video packets
+            PROBE => Self {
+                priority: StreamPriority::High,
+                capacity: 64,
+                drop_policy: DropPolicy::Never,
+            },
+            AUDIO => Self {
+                priority: StreamPriority::Medium,
+                capacity: 256,
+                drop_policy: DropPolicy::DropOldest,
+            },
+            VIDEO => Self {
+                priority: StreamPriority::Low,
+                capacity: 2048,
+                drop_policy: DropPolicy::DropOldest,
+            },
+            _ => Self {
+                priority: StreamPriority::Medium,
+                capacity: 256,
+                drop_policy: DropPolicy::DropNewest,
+            },
+        }
+    }
+}
+
+struct QueueState<T> {
+    packets: VecDeque<T>,
+    packets_dropped: u64,
+    closed: bool,
+}
+
+pub struct PacketQueue<T> {
+    config: QueueConfig,
+    state: Mutex<QueueState<T>>,
+    // Shared by the send queues, so the send loop can wait on all of them
+    packet_notifier: Arc<Notify>,
+    space_notifier: Notify,
+}
+
+impl<T> PacketQueue<T> {
+    pub fn new(config: QueueConfig, packet_notifier: Arc<Notify>) -> Self {
+        Self {
+            config,
+            state: Mutex::new(QueueState {
+                packets: VecDeque::new(),
+                packets_dropped: 0,
+                closed: false,
+            }),
+            packet_notifier,
+            space_notifier: Notify::new(),
+        }
+    }
+
+    pub fn priority(&self) -> StreamPriority {
+        self.config.priority
+    }
+
+    pub fn packets_dropped(&self) -> u64 {
+        self.state.lock().packets_dropped
+    }
+
+    // Never waits. Fails if the consumer has closed the queue
+    pub fn push(&self, packet: T) -> StrResult {
+        let mut state = self.state.lock();
+        if state.closed {
+            return fmt_e!("Packet queue closed");
+        }
+
+        if state.packets.len() >= self.config.capacity {
+            match self.config.drop_policy {
+                DropPolicy::DropOldest => {
+                    state.packets.pop_front();
+                    state.packets_dropped += 1;
+                }
+                DropPolicy::DropNewest => {
+                    state.packets_dropped += 1;
+                    return Ok(());
+                }
+                DropPolicy::Never => (),
+            }
+        }
+        state.packets.push_back(packet);
+        drop(state);
+
+        self.packet_notifier.notify_one();
+
+        Ok(())
+    }
+
+    // With DropPolicy::Never, waits until the queue has space for the packet
+    pub async fn push_wait(&self, packet: T) -> StrResult {
+        if self.config.drop_policy == DropPolicy::Never {
+            loop {
+                let space_notified = self.space_notifier.notified();
+                {
+                    let state = self.state.lock();
+                    if state.closed || state.packets.len() < self.config.capacity {
+                        break;
+                    }
+                }
+                space_notified.await;
+            }
+        }
+
+        self.push(packet)
+    }
+
+    pub fn try_pop(&self) -> Option<T> {
+        let packet = self.state.lock().packets.pop_front();
+        if packet.is_some() {
+            self.space_notifier.notify_one();
+        }
+
+        packet
+    }
+
+    // Returns None once the queue is closed and empty
+    pub async fn pop(&self) -> Option<T> {
+        loop {
+            let packet_notified = self.packet_notifier.notified();
+            if let Some(packet) = self.try_pop() {
+                return Some(packet);
+            }
+            if self.state.lock().closed {
+                return None;
+            }
+            packet_notified.await;
+        }
+    }
+
+    pub fn close(&self) {
+        self.state.lock().closed = true;
+
+        self.packet_notifier.notify_one();
+        // notify_one() also wakes a sender that has not started waiting yet
+        self.space_notifier.notify_waiters();
+        self.space_notifier.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::{task, time};
+
+    fn test_queue(capacity: usize, drop_policy: DropPolicy) -> Arc<PacketQueue<u32>> {
+        let config = QueueConfig {
+            priority: StreamPriority::Medium,
+            capacity,
+            drop_policy,
+        };
+
+        Arc::new(PacketQueue::new(config, Arc::default()))
+    }
+
+    fn pop_all(queue: &PacketQueue<u32>) -> Vec<u32> {
+        std::iter::from_fn(|| queue.try_pop()).collect()
+    }
+
+    #[test]
+    fn drop_oldest_keeps_latest_packets() {
+        let queue = test_queue(3, DropPolicy::DropOldest);
+        for packet in 0..5 {
+            queue.push(packet).unwrap();
+        }
+
+        assert_eq!(pop_all(&queue), vec![2, 3, 4]);
+        assert_eq!(queue.packets_dropped(), 2);
+    }
+
+    #[test]
+    fn drop_newest_keeps_first_packets() {
+        let queue = test_queue(3, DropPolicy::DropNewest);
+        for packet in 0..5 {
+            queue.push(packet).unwrap();
+        }
+
+        assert_eq!(pop_all(&queue), vec![0, 1, 2]);
+        assert_eq!(queue.packets_dropped(), 2);
+    }
+
+    #[tokio::test]
+    async fn never_waits_for_space() {
+        let queue = test_queue(2, DropPolicy::Never);
+        queue.push_wait(0).await.unwrap();
+        queue.push_wait(1).await.unwrap();
+
+        let sender = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move { queue.push_wait(2).await }
+        });
+        task::yield_now().await;
+        assert!(!sender.is_finished());
+
+        assert_eq!(queue.pop().await, Some(0));
+        sender.await.unwrap().unwrap();
+        assert_eq!(pop_all(&queue), vec![1, 2]);
+
+        // push() does not wait, the queue grows past its capacity
+        for packet in 3..6 {
+            queue.push(packet).unwrap();
+        }
+        assert_eq!(pop_all(&queue), vec![3, 4, 5]);
+        assert_eq!(queue.packets_dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn close_wakes_waiters() {
+        let queue = test_queue(1, DropPolicy::Never);
+
+        let receiver = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move { queue.pop().await }
+        });
+        task::yield_now().await;
+        queue.close();
+        assert_eq!(
+            time::timeout(Duration::from_secs(1), receiver)
+                .await
+                .unwrap()
+                .unwrap(),
+            None
+        );
+
+        let queue = test_queue(1, DropPolicy::Never);
+        queue.push(0).unwrap();
+
+        let sender = tokio::spawn({
+            let queue = Arc::clone(&queue);
+            async move { queue.push_wait(1).await }
+        });
+        task::yield_now().await;
+        queue.close();
+        let result = time::timeout(Duration::from_secs(1), sender)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(result.is_err());
+
+        // Packets queued before closing can still be read
+        assert_eq!(queue.pop().await, Some(0));
+        assert_eq!(queue.pop().await, None);
+    }
+}
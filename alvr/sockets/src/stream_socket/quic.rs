@@ -0,0 +1,207 @@
+// QUIC stream socket. Small latency-sensitive packets (tracking, haptics, statistics) are sent as
+// unreliable datagrams, everything else gets its own unidirectional stream per stream ID. This way
+// a lost video packet can only stall the video stream and congestion control is handled by QUIC.
+
+use super::PacketQueues;
+use crate::{Ldc, CERTIFICATE_SERVER_NAME, HAPTICS, LOCAL_IP, STATISTICS, TRACKING};
+use alvr_common::prelude::*;
+use bytes::{Buf, Bytes, BytesMut};
+use futures::{
+    stream::{SelectAll, StreamExt},
+    SinkExt,
+};
+use quinn::{
+    ClientConfig, Connection, Datagrams, Endpoint, Incoming, IncomingUniStreams, NewConnection,
+    RecvStream, SendStream, ServerConfig, TransportConfig,
+};
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use tokio::sync::Mutex;
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(1);
+
+type QuicStreamWriter = Arc<Mutex<FramedWrite<SendStream, Ldc>>>;
+
+#[derive(Clone)]
+pub struct QuicStreamSendSocket {
+    connection: Connection,
+    // streams are opened lazily the first time a packet for a stream ID is sent
+    streams: Arc<Mutex<HashMap<u16, QuicStreamWriter>>>,
+}
+
+impl QuicStreamSendSocket {
+    pub async fn send(&self, buffer: Bytes) -> StrResult {
+        let stream_id = u16::from_be_bytes([buffer[0], buffer[1]]);
+
+        if matches!(stream_id, TRACKING | HAPTICS | STATISTICS)
+            && matches!(self.connection.max_datagram_size(), Some(size) if buffer.len() <= size)
+        {
+            return self.connection.send_datagram(buffer).map_err(err!());
+        }
+
+        let writer = {
+            let mut streams = self.streams.lock().await;
+            if let Some(writer) = streams.get(&stream_id) {
+                Arc::clone(writer)
+            } else {
+                let stream = self.connection.open_uni().await.map_err(err!())?;
+                let writer = Arc::new(Mutex::new(FramedWrite::new(stream, Ldc::new())));
+                streams.insert(stream_id, Arc::clone(&writer));
+
+                writer
+            }
+        };
+
+        let mut writer = writer.lock().await;
+        writer.send(buffer).await.map_err(err!())
+    }
+}
+
+pub struct QuicStreamReceiveSocket {
+    // keep the endpoint alive for the lifetime of the connection
+    _endpoint: Endpoint,
+    datagrams: Datagrams,
+    uni_streams: IncomingUniStreams,
+}
+
+// The headset generates a new self-signed certificate for each session, so there is nothing to
+// check it against yet. The peer IP is still checked against the control socket one.
+struct SkipServerVerification;
+
+impl rustls::client::ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(
+        &self,
+        _: &rustls::Certificate,
+        _: &[rustls::Certificate],
+        _: &rustls::ServerName,
+        _: &mut dyn Iterator<Item = &[u8]>,
+        _: &[u8],
+        _: SystemTime,
+    ) -> Result<rustls::client::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::ServerCertVerified::assertion())
+    }
+}
+
+fn transport_config() -> Arc<TransportConfig> {
+    let mut config = TransportConfig::default();
+    config.keep_alive_interval(Some(KEEP_ALIVE_INTERVAL));
+
+    Arc::new(config)
+}
+
+fn split_connection(
+    endpoint: Endpoint,
+    new_connection: NewConnection,
+) -> (QuicStreamSendSocket, QuicStreamReceiveSocket) {
+    let NewConnection {
+        connection,
+        uni_streams,
+        datagrams,
+        ..
+    } = new_connection;
+
+    (
+        QuicStreamSendSocket {
+            connection,
+            streams: Arc::new(Mutex::new(HashMap::new())),
+        },
+        QuicStreamReceiveSocket {
+            _endpoint: endpoint,
+            datagrams,
+            uni_streams,
+        },
+    )
+}
+
+pub async fn listen_for_server(port: u16) -> StrResult<(Endpoint, Incoming)> {
+    let certificate =
+        rcgen::generate_simple_self_signed(vec![CERTIFICATE_SERVER_NAME.into()]).map_err(err!())?;
+    let certificate_der = certificate.serialize_der().map_err(err!())?;
+    let private_key_der = certificate.serialize_private_key_der();
+
+    let mut config = ServerConfig::with_single_cert(
+        vec![rustls::Certificate(certificate_der)],
+        rustls::PrivateKey(private_key_der),
+    )
+    .map_err(err!())?;
+    config.transport = transport_config();
+
+    Endpoint::server(config, (LOCAL_IP, port).into()).map_err(err!())
+}
+
+pub async fn accept_from_server(
+    endpoint: Endpoint,
+    mut incoming: Incoming,
+    server_ip: IpAddr,
+) -> StrResult<(QuicStreamSendSocket, QuicStreamReceiveSocket)> {
+    let new_connection = incoming
+        .next()
+        .await
+        .ok_or_else(enone!())?
+        .await
+        .map_err(err!())?;
+
+    let server_address = new_connection.connection.remote_address();
+    if server_address.ip() != server_ip {
+        return fmt_e!("Connected to wrong server: {server_address} != {server_ip}");
+    }
+
+    Ok(split_connection(endpoint, new_connection))
+}
+
+pub async fn connect_to_client(
+    client_ip: IpAddr,
+    port: u16,
+) -> StrResult<(QuicStreamSendSocket, QuicStreamReceiveSocket)> {
+    let crypto = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_custom_certificate_verifier(Arc::new(SkipServerVerification))
+        .with_no_client_auth();
+    let mut config = ClientConfig::new(Arc::new(crypto));
+    config.transport = transport_config();
+
+    let endpoint = Endpoint::client((LOCAL_IP, 0).into()).map_err(err!())?;
+    let new_connection = endpoint
+        .connect_with(config, (client_ip, port).into(), CERTIFICATE_SERVER_NAME)
+        .map_err(err!())?
+        .await
+        .map_err(err!())?;
+
+    Ok(split_connection(endpoint, new_connection))
+}
+
+pub async fn receive_loop(
+    mut socket: QuicStreamReceiveSocket,
+    packet_enqueuers: PacketQueues,
+) -> StrResult {
+    let mut streams = SelectAll::<FramedRead<RecvStream, Ldc>>::new();
+
+    loop {
+        let mut packet = tokio::select! {
+            maybe_datagram = socket.datagrams.next() => match maybe_datagram {
+                Some(datagram) => BytesMut::from(&datagram.map_err(err!())?[..]),
+                None => return Ok(()),
+            },
+            maybe_stream = socket.uni_streams.next() => match maybe_stream {
+                Some(stream) => {
+                    streams.push(FramedRead::new(stream.map_err(err!())?, Ldc::new()));
+                    continue;
+                }
+                None => return Ok(()),
+            },
+            Some(maybe_packet) = streams.next(), if !streams.is_empty() => {
+                maybe_packet.map_err(err!())?
+            }
+        };
+
+        let stream_id = packet.get_u16();
+        if let Some(enqueuer) = packet_enqueuers.lock().await.get(&stream_id) {
+            enqueuer.push(packet)?;
+        }
+    }
+}
@@ -0,0 +1,333 @@
+// Recordings store the decrypted packets of the TRACKING, VIDEO, AUDIO and STATISTICS streams,
+// together with the session used for the connection. A recording can be played back through a
+// StreamSocket, so the stream consumers can be debugged without a headset.
+//
+// File layout: [RecordingHeader][RecordedPacket]... serialized with bincode. Packets are stored
+// without stream ID and encryption, starting with the packet kind. FEC parity packets are recorded
+// too, so the playback goes through the same recovery.
+
+use super::{PacketQueues, StreamReceiveSocket, StreamSendSocket, StreamSocket};
+use crate::{security::StreamCipher, StreamKey, AUDIO, STATISTICS, TRACKING, VIDEO};
+use alvr_common::{parking_lot, prelude::*};
+use alvr_session::SessionDesc;
+use bytes::{Buf, BufMut, BytesMut};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Write},
+    path::Path,
+    sync::{mpsc as smpsc, Arc},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{mpsc as tmpsc, Mutex, Notify},
+    time,
+};
+
+const RECORDING_FORMAT_VERSION: u32 = 3;
+// Packets read in advance during playback
+const PLAYBACK_READ_AHEAD: usize = 64;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RecordedDirection {
+    Sent,
+    Received,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordingHeader {
+    format_version: u32,
+    // JSON, like in ClientConfigPacket
+    session_desc: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordedPacket {
+    timestamp: Duration,
+    direction: RecordedDirection,
+    stream_id: u16,
+    bytes: Vec<u8>,
+}
+
+// Writing happens on a separate thread to avoid blocking the stream tasks. The recording is
+// complete once the recorder is dropped.
+pub struct StreamRecorder {
+    start: Instant,
+    // None once the recorder is dropped
+    packet_sender: parking_lot::Mutex<Option<smpsc::Sender<RecordedPacket>>>,
+    writer_thread: Option<JoinHandle<()>>,
+}
+
+impl StreamRecorder {
+    pub fn new(path: &Path, session_desc: &SessionDesc) -> StrResult<Self> {
+        let mut writer = BufWriter::new(File::create(path).map_err(err!())?);
+
+        let header = RecordingHeader {
+            format_version: RECORDING_FORMAT_VERSION,
+            session_desc: serde_json::to_string(session_desc).map_err(err!())?,
+        };
+        bincode::serialize_into(&mut writer, &header).map_err(err!())?;
+
+        let (packet_sender, packet_receiver) = smpsc::channel::<RecordedPacket>();
+        let writer_thread = thread::spawn(move || {
+            // the loop ends when the recorder is dropped
+            while let Ok(packet) = packet_receiver.recv() {
+                if let Err(e) = bincode::serialize_into(&mut writer, &packet) {
+                    error!("Failed to write recording: {e}");
+                    return;
+                }
+            }
+
+            if let Err(e) = writer.flush() {
+                error!("Failed to write recording: {e}");
+            }
+        });
+
+        Ok(Self {
+            start: Instant::now(),
+            packet_sender: parking_lot::Mutex::new(Some(packet_sender)),
+            writer_thread: Some(writer_thread),
+        })
+    }
+
+    pub(super) fn record(&self, direction: RecordedDirection, stream_id: u16, bytes: &[u8]) {
+        if !matches!(stream_id, TRACKING | VIDEO | AUDIO | STATISTICS) {
+            return;
+        }
+
+        let packet = RecordedPacket {
+            timestamp: self.start.elapsed(),
+            direction,
+            stream_id,
+            bytes: bytes.to_vec(),
+        };
+        if let Some(sender) = &*self.packet_sender.lock() {
+            sender.send(packet).ok();
+        }
+    }
+}
+
+impl Drop for StreamRecorder {
+    // Waits for the packets recorded so far to be written
+    fn drop(&mut self) {
+        self.packet_sender.get_mut().take();
+
+        if let Some(thread) = self.writer_thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+pub struct StreamPlayer {
+    reader: BufReader<File>,
+    direction: RecordedDirection,
+    // Replayed packets are encrypted again so they go through the normal StreamReceiver path.
+    // They never leave the process, so the key does not need to be secret
+    cipher: StreamCipher,
+}
+
+impl StreamPlayer {
+    // Returns the session of the recording and a socket whose receivers get the packets recorded
+    // with the given direction. Packets sent through the socket are discarded by
+    // StreamSocket::send_loop(). The playback starts with StreamSocket::receive_loop() and respects
+    // the recorded timing.
+    pub fn open(
+        path: &Path,
+        direction: RecordedDirection,
+    ) -> StrResult<(SessionDesc, StreamSocket)> {
+        let mut reader = BufReader::new(File::open(path).map_err(err!())?);
+
+        let header: RecordingHeader = bincode::deserialize_from(&mut reader).map_err(err!())?;
+        if header.format_version != RECORDING_FORMAT_VERSION {
+            return fmt_e!(
+                "Unsupported recording format version {}",
+                header.format_version
+            );
+        }
+        let session_desc = serde_json::from_str(&header.session_desc).map_err(err!())?;
+
+        let stream_key = StreamKey {
+            server_to_client: [0; 32],
+            client_to_server: [0; 32],
+        };
+
+        let player = StreamPlayer {
+            reader,
+            direction,
+            cipher: StreamCipher::new(&stream_key, true),
+        };
+
+        let socket = StreamSocket {
+            send_socket: StreamSendSocket::Replay,
+            receive_socket: Arc::new(Mutex::new(Some(StreamReceiveSocket::Replay(player)))),
+            packet_queues: Arc::new(Mutex::new(HashMap::new())),
+            send_queues: Arc::new(parking_lot::Mutex::new(vec![])),
+            send_notifier: Arc::new(Notify::new()),
+            queue_configs: HashMap::new(),
+            reorder_windows: HashMap::new(),
+            cipher: Arc::new(StreamCipher::new(&stream_key, false)),
+            recorder: None,
+            // packets sent during playback are discarded, they are not fragmented
+            max_packet_size: usize::MAX,
+        };
+
+        Ok((session_desc, socket))
+    }
+}
+
+fn read_packet(reader: &mut BufReader<File>) -> StrResult<Option<RecordedPacket>> {
+    match bincode::deserialize_from(reader) {
+        Ok(packet) => Ok(Some(packet)),
+        Err(e) => match *e {
+            bincode::ErrorKind::Io(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+            e => fmt_e!("{e}"),
+        },
+    }
+}
+
+pub async fn receive_loop(player: StreamPlayer, packet_enqueuers: PacketQueues) -> StrResult {
+    let StreamPlayer {
+        mut reader,
+        direction,
+        cipher,
+    } = player;
+
+    // Like for the recorder, reading happens on a separate thread to avoid blocking the runtime
+    let (packet_sender, mut packet_receiver) = tmpsc::channel(PLAYBACK_READ_AHEAD);
+    thread::spawn(move || {
+        // the loop ends at the end of the recording, on error or when the playback is dropped
+        while let Some(res) = read_packet(&mut reader).transpose() {
+            let is_err = res.is_err();
+            if packet_sender.blocking_send(res).is_err() || is_err {
+                return;
+            }
+        }
+    });
+
+    let start = time::Instant::now();
+
+    while let Some(packet) = packet_receiver.recv().await {
+        let packet = packet?;
+        if packet.direction != direction {
+            continue;
+        }
+
+        time::sleep_until(start + packet.timestamp).await;
+
+        let mut bytes = BytesMut::with_capacity(
+            2 + StreamCipher::HEADER_SIZE + packet.bytes.len() + StreamCipher::TRAILER_SIZE,
+        );
+        bytes.put_u16(packet.stream_id);
+        bytes.put_bytes(0, StreamCipher::HEADER_SIZE);
+        bytes.put_slice(&packet.bytes);
+        cipher.encrypt(&mut bytes)?;
+
+        // the receive loops of the other sockets strip the stream ID too
+        bytes.advance(2);
+
+        if let Some(enqueuer) = packet_enqueuers.lock().await.get(&packet.stream_id) {
+            enqueuer.push(bytes)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StreamSocketBuilder;
+    use alvr_session::SocketProtocol;
+    use std::{
+        env, fs,
+        net::{IpAddr, TcpListener},
+        process,
+    };
+
+    const MESSAGES_COUNT: u32 = 3;
+    // Bigger than the packet size, so the messages are fragmented
+    const MESSAGE_SIZE: usize = 5000;
+
+    fn recorded_packets_count(path: &Path) -> Option<usize> {
+        let mut reader = BufReader::new(File::open(path).ok()?);
+        bincode::deserialize_from::<_, RecordingHeader>(&mut reader).ok()?;
+
+        let mut count = 0;
+        while read_packet(&mut reader).ok()?.is_some() {
+            count += 1;
+        }
+
+        Some(count)
+    }
+
+    // Chosen by the OS, so parallel test runs do not collide
+    fn free_port() -> u16 {
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    #[tokio::test]
+    async fn recorded_packets_are_played_back() {
+        let port = free_port();
+        let path = env::temp_dir().join(format!("alvr_recording_test_{}.bin", process::id()));
+        let localhost = IpAddr::from([127, 0, 0, 1]);
+        let stream_key = StreamKey {
+            server_to_client: [1; 32],
+            client_to_server: [2; 32],
+        };
+
+        let builder = StreamSocketBuilder::listen_for_server(port, SocketProtocol::Tcp)
+            .await
+            .unwrap();
+        let client = tokio::spawn({
+            let stream_key = stream_key.clone();
+            async move {
+                builder
+                    .accept_from_server(localhost, port, stream_key, 1400)
+                    .await
+            }
+        });
+        let mut server = StreamSocketBuilder::connect_to_client(
+            localhost,
+            port,
+            SocketProtocol::Tcp,
+            1_000_000,
+            stream_key,
+            1400,
+        )
+        .await
+        .unwrap();
+        let client = client.await.unwrap().unwrap();
+
+        server.set_recorder(StreamRecorder::new(&path, &SessionDesc::default()).unwrap());
+        let mut sender = server.request_stream::<u32>(VIDEO, None).await.unwrap();
+        for index in 0..MESSAGES_COUNT {
+            let mut buffer = sender.new_buffer(&index, MESSAGE_SIZE).unwrap();
+            buffer.get_mut().put_bytes(index as u8, MESSAGE_SIZE);
+            sender.send_buffer(buffer).await.unwrap();
+        }
+        // The recording is complete once the recorder is dropped with the socket
+        drop(sender);
+        drop(server);
+        drop(client);
+        assert!(recorded_packets_count(&path).unwrap() > MESSAGES_COUNT as usize);
+
+        let (_, player) = StreamPlayer::open(&path, RecordedDirection::Sent).unwrap();
+        let mut receiver = player.subscribe_to_stream::<u32>(VIDEO).await.unwrap();
+        player.receive_loop().await.unwrap();
+
+        for index in 0..MESSAGES_COUNT {
+            let packet = receiver.recv().await.unwrap();
+            assert_eq!(packet.header, index);
+            assert!(!packet.had_packet_loss);
+            assert_eq!(&packet.buffer[..], &vec![index as u8; MESSAGE_SIZE][..]);
+        }
+
+        fs::remove_file(&path).ok();
+    }
+}
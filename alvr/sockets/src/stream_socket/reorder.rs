@@ -0,0 +1,261 @@
+// With UDP, the packets of a stream can arrive out of order or more than once. The ReorderBuffer
+// delivers the packets in order of index. A packet that arrives before the ones preceding it is held
+// until they arrive or until the reorder window elapses, then the missing packets are skipped.
+// Packets that arrive after being skipped and duplicates are dropped.
+
+use crate::{AUDIO, VIDEO};
+use bytes::BytesMut;
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// Packets behind the next index that are checked for duplicates
+const HISTORY_SIZE: u32 = 64;
+const MAX_HELD_PACKETS: usize = 1024;
+
+// Used for the streams that have not been configured with StreamSocket::set_reorder_window()
+pub fn default_reorder_window(stream_id: u16) -> Duration {
+    match stream_id {
+        AUDIO => Duration::from_millis(10),
+        VIDEO => Duration::from_millis(5),
+        // Late tracking packets are useless, newer ones are delivered immediately
+        _ => Duration::ZERO,
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ReorderStatistics {
+    // Packets that arrived after a packet with a higher index and have been delivered in order
+    pub packets_reordered: u64,
+    // Packets that arrived after being skipped. They are also counted as lost
+    pub packets_late: u64,
+    pub packets_duplicated: u64,
+}
+
+struct HeldPacket {
+    index: u32,
+    arrival: Instant,
+    bytes: BytesMut,
+}
+
+pub struct ReorderBuffer {
+    window: Duration,
+    next_index: Option<u32>,
+    // Bit n is set if the packet with index next_index - 1 - n has been delivered
+    history: u64,
+    // Sorted by index
+    held_packets: VecDeque<HeldPacket>,
+    ready_packets: VecDeque<BytesMut>,
+    pub statistics: ReorderStatistics,
+}
+
+impl ReorderBuffer {
+    // With a zero window, packets are never held
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            next_index: None,
+            history: 0,
+            held_packets: VecDeque::new(),
+            ready_packets: VecDeque::new(),
+            statistics: ReorderStatistics::default(),
+        }
+    }
+
+    pub fn push_packet(&mut self, index: u32, bytes: BytesMut) {
+        let next_index = *self.next_index.get_or_insert(index);
+        let distance = index.wrapping_sub(next_index) as i32;
+
+        if distance < 0 {
+            let age = index.wrapping_sub(next_index).wrapping_neg() - 1;
+            if age < HISTORY_SIZE && self.history & (1 << age) != 0 {
+                self.statistics.packets_duplicated += 1;
+            } else {
+                self.statistics.packets_late += 1;
+            }
+
+            return;
+        }
+
+        if distance == 0 {
+            if !self.held_packets.is_empty() {
+                self.statistics.packets_reordered += 1;
+            }
+            self.deliver(index, bytes);
+            self.deliver_held();
+
+            return;
+        }
+
+        if self.window.is_zero() {
+            self.deliver(index, bytes);
+
+            return;
+        }
+
+        let position = self
+            .held_packets
+            .iter()
+            .position(|packet| packet.index.wrapping_sub(next_index) as i32 >= distance)
+            .unwrap_or(self.held_packets.len());
+        if matches!(self.held_packets.get(position), Some(packet) if packet.index == index) {
+            self.statistics.packets_duplicated += 1;
+
+            return;
+        }
+        if position < self.held_packets.len() {
+            self.statistics.packets_reordered += 1;
+        }
+        self.held_packets.insert(
+            position,
+            HeldPacket {
+                index,
+                arrival: Instant::now(),
+                bytes,
+            },
+        );
+
+        if self.held_packets.len() > MAX_HELD_PACKETS {
+            self.release_held();
+        }
+    }
+
+    pub fn pop_packet(&mut self) -> Option<BytesMut> {
+        self.ready_packets.pop_front()
+    }
+
+    // When to give up waiting for the missing packets
+    pub fn hold_deadline(&self) -> Option<Instant> {
+        self.held_packets
+            .iter()
+            .map(|packet| packet.arrival)
+            .min()
+            .map(|arrival| arrival + self.window)
+    }
+
+    // Skips the missing packets before the first held packet
+    pub fn release_held(&mut self) {
+        if let Some(packet) = self.held_packets.pop_front() {
+            self.deliver(packet.index, packet.bytes);
+            self.deliver_held();
+        }
+    }
+
+    // Skips the packets before `index`, if any
+    fn deliver(&mut self, index: u32, bytes: BytesMut) {
+        if let Some(next_index) = self.next_index {
+            let skipped = index.wrapping_sub(next_index);
+            self.history = self.history.checked_shl(skipped).unwrap_or(0);
+        }
+        self.history = (self.history << 1) | 1;
+        self.next_index = Some(index.wrapping_add(1));
+
+        self.ready_packets.push_back(bytes);
+    }
+
+    fn deliver_held(&mut self) {
+        while matches!(self.held_packets.front(), Some(packet) if Some(packet.index) == self.next_index)
+        {
+            let packet = self.held_packets.pop_front().unwrap();
+            self.deliver(packet.index, packet.bytes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push(reorder_buffer: &mut ReorderBuffer, index: u32) {
+        reorder_buffer.push_packet(index, BytesMut::from(&index.to_be_bytes()[..]));
+    }
+
+    fn pop_all(reorder_buffer: &mut ReorderBuffer) -> Vec<u32> {
+        std::iter::from_fn(|| reorder_buffer.pop_packet())
+            .map(|bytes| u32::from_be_bytes(bytes[..].try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn reordered_packets_are_delivered_in_order() {
+        let mut reorder_buffer = ReorderBuffer::new(Duration::from_millis(10));
+
+        for index in [0, 2, 3, 1, 5, 4] {
+            push(&mut reorder_buffer, index);
+        }
+
+        assert_eq!(pop_all(&mut reorder_buffer), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(reorder_buffer.statistics.packets_reordered, 2);
+        assert_eq!(reorder_buffer.statistics.packets_late, 0);
+        assert!(reorder_buffer.hold_deadline().is_none());
+    }
+
+    #[test]
+    fn held_packets_are_released() {
+        let mut reorder_buffer = ReorderBuffer::new(Duration::from_millis(10));
+
+        for index in [0, 2, 4, 3] {
+            push(&mut reorder_buffer, index);
+        }
+        assert_eq!(pop_all(&mut reorder_buffer), vec![0]);
+        assert!(reorder_buffer.hold_deadline().is_some());
+
+        // Packet 1 is skipped
+        reorder_buffer.release_held();
+        assert_eq!(pop_all(&mut reorder_buffer), vec![2, 3, 4]);
+        assert!(reorder_buffer.hold_deadline().is_none());
+
+        push(&mut reorder_buffer, 1);
+        assert!(pop_all(&mut reorder_buffer).is_empty());
+        assert_eq!(reorder_buffer.statistics.packets_late, 1);
+
+        // Nothing to release
+        reorder_buffer.release_held();
+        push(&mut reorder_buffer, 5);
+        assert_eq!(pop_all(&mut reorder_buffer), vec![5]);
+    }
+
+    #[test]
+    fn duplicate_packets_are_dropped() {
+        let mut reorder_buffer = ReorderBuffer::new(Duration::from_millis(10));
+
+        // Duplicates of delivered and held packets
+        for index in [0, 1, 0, 3, 3, 2, 1] {
+            push(&mut reorder_buffer, index);
+        }
+
+        assert_eq!(pop_all(&mut reorder_buffer), vec![0, 1, 2, 3]);
+        assert_eq!(reorder_buffer.statistics.packets_duplicated, 3);
+        assert_eq!(reorder_buffer.statistics.packets_late, 0);
+    }
+
+    #[test]
+    fn packets_are_not_held_without_window() {
+        let mut reorder_buffer = ReorderBuffer::new(Duration::ZERO);
+
+        for index in [0, 2, 1, 3, 3] {
+            push(&mut reorder_buffer, index);
+        }
+
+        assert_eq!(pop_all(&mut reorder_buffer), vec![0, 2, 3]);
+        assert_eq!(reorder_buffer.statistics.packets_late, 1);
+        assert_eq!(reorder_buffer.statistics.packets_duplicated, 1);
+    }
+
+    #[test]
+    fn packets_are_delivered_across_index_wraparound() {
+        let mut reorder_buffer = ReorderBuffer::new(Duration::from_millis(10));
+
+        for index in [u32::MAX - 1, 0, u32::MAX, 1, u32::MAX] {
+            push(&mut reorder_buffer, index);
+        }
+
+        assert_eq!(
+            pop_all(&mut reorder_buffer),
+            vec![u32::MAX - 1, u32::MAX, 0, 1]
+        );
+        assert_eq!(reorder_buffer.statistics.packets_reordered, 1);
+        assert_eq!(reorder_buffer.statistics.packets_duplicated, 1);
+    }
+}
@@ -1,14 +1,15 @@
+use super::PacketQueues;
 use crate::{Ldc, LOCAL_IP};
 use alvr_common::prelude::*;
-use bytes::{Buf, Bytes, BytesMut};
+use bytes::{Buf, Bytes};
 use futures::{
     stream::{SplitSink, SplitStream},
     StreamExt,
 };
-use std::{collections::HashMap, net::IpAddr, sync::Arc};
+use std::{net::IpAddr, sync::Arc};
 use tokio::{
     net::{TcpListener, TcpStream},
-    sync::{mpsc, Mutex},
+    sync::Mutex,
 };
 use tokio_util::codec::Framed;
 
@@ -52,14 +53,14 @@ pub async fn connect_to_client(
 
 pub async fn receive_loop(
     mut socket: TcpStreamReceiveSocket,
-    packet_enqueuers: Arc<Mutex<HashMap<u16, mpsc::UnboundedSender<BytesMut>>>>,
+    packet_enqueuers: PacketQueues,
 ) -> StrResult {
     while let Some(maybe_packet) = socket.next().await {
         let mut packet = maybe_packet.map_err(err!())?;
 
         let stream_id = packet.get_u16();
-        if let Some(enqueuer) = packet_enqueuers.lock().await.get_mut(&stream_id) {
-            enqueuer.send(packet).map_err(err!())?;
+        if let Some(enqueuer) = packet_enqueuers.lock().await.get(&stream_id) {
+            enqueuer.push(packet)?;
         }
     }
 
@@ -1,3 +1,4 @@
+use super::PacketQueues;
 use crate::LOCAL_IP;
 use alvr_common::prelude::*;
 use bytes::{Buf, BufMut, Bytes, BytesMut};
@@ -9,7 +10,6 @@ use governor::{
 };
 use nonzero_ext::NonZero;
 use std::{
-    collections::HashMap,
     io,
     mem::MaybeUninit,
     net::{IpAddr, SocketAddr},
@@ -17,11 +17,7 @@ use std::{
     sync::Arc,
     task::{Context, Poll},
 };
-use tokio::{
-    io::ReadBuf,
-    net::UdpSocket,
-    sync::{mpsc, Mutex},
-};
+use tokio::{io::ReadBuf, net::UdpSocket};
 
 const INITIAL_RD_CAPACITY: usize = 64 * 1024;
 
@@ -154,14 +150,14 @@ pub async fn accept_from_server(
 
 pub async fn receive_loop(
     mut socket: ThrottledUdpStreamReceiveSocket,
-    packet_enqueuers: Arc<Mutex<HashMap<u16, mpsc::UnboundedSender<BytesMut>>>>,
+    packet_enqueuers: PacketQueues,
 ) -> StrResult {
     while let Some(maybe_packet) = socket.next().await {
         let (mut packet_bytes, _) = maybe_packet.map_err(err!())?;
 
         let stream_id = packet_bytes.get_u16();
-        if let Some(enqueuer) = packet_enqueuers.lock().await.get_mut(&stream_id) {
-            enqueuer.send(packet_bytes).map_err(err!())?;
+        if let Some(enqueuer) = packet_enqueuers.lock().await.get(&stream_id) {
+            enqueuer.push(packet_bytes)?;
         }
     }
 
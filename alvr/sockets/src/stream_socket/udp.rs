@@ -1,19 +1,16 @@
+use super::PacketQueues;
 use crate::{Ldc, LOCAL_IP};
 use alvr_common::prelude::*;
-use bytes::{Buf, Bytes, BytesMut};
+use bytes::{Buf, Bytes};
 use futures::{
     stream::{SplitSink, SplitStream},
     StreamExt,
 };
 use std::{
-    collections::HashMap,
     net::{IpAddr, SocketAddr},
     sync::Arc,
 };
-use tokio::{
-    net::UdpSocket,
-    sync::{mpsc, Mutex},
-};
+use tokio::{net::UdpSocket, sync::Mutex};
 use tokio_util::udp::UdpFramed;
 
 #[allow(clippy::type_complexity)]
@@ -57,7 +54,7 @@ pub async fn connect(
 
 pub async fn receive_loop(
     mut socket: UdpStreamReceiveSocket,
-    packet_enqueuers: Arc<Mutex<HashMap<u16, mpsc::UnboundedSender<BytesMut>>>>,
+    packet_enqueuers: PacketQueues,
 ) -> StrResult {
     while let Some(maybe_packet) = socket.inner.next().await {
         let (mut packet_bytes, address) = maybe_packet.map_err(err!())?;
@@ -67,8 +64,8 @@ pub async fn receive_loop(
         }
 
         let stream_id = packet_bytes.get_u16();
-        if let Some(enqueuer) = packet_enqueuers.lock().await.get_mut(&stream_id) {
-            enqueuer.send(packet_bytes).map_err(err!())?;
+        if let Some(enqueuer) = packet_enqueuers.lock().await.get(&stream_id) {
+            enqueuer.push(packet_bytes)?;
         }
     }
 
@@ -0,0 +1,461 @@
+// Headless client that emulates a headset. It follows the same connection sequence as
+// alvr_client_core (announce -> ServerAccepted -> StartStream -> StreamReady -> streaming) but
+// instead of decoding and playing back the streams it only records what it receives.
+
+use alvr_common::{
+    glam::{Quat, Vec3},
+    parking_lot::Mutex,
+    prelude::*,
+    ALVR_NAME, ALVR_VERSION, HEAD_ID,
+};
+use alvr_session::SessionDesc;
+use alvr_sockets::{
+    spawn_cancelable, ClientConfigPacket, ClientConnectionResult, ClientControlPacket,
+    ClientHandshakePacket, ClientIdentity, ClientStatistics, DeviceMotion, HandshakePacket,
+    Haptics, HeadsetInfoPacket, PeerType, ProbePacketHeader, ProtoControlSocket, ReceivedPacket,
+    ServerControlPacket, ServerHandshakePacket, StreamReceiver, StreamSocketBuilder, Tracking,
+    VideoFrameHeaderPacket, AUDIO, CONTROL_PORT, HAPTICS, LOCAL_IP,
+    MAX_HANDSHAKE_PACKET_SIZE_BYTES, PROBE, STATISTICS, TRACKING, VIDEO,
+};
+use rand::Rng;
+use serde::de::DeserializeOwned;
+use serde_json as json;
+use std::{
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{net::UdpSocket, sync::mpsc, time};
+
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+const CONTROL_CONNECT_RETRY_PAUSE: Duration = Duration::from_millis(500);
+const NETWORK_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(1);
+const STREAM_SETUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct VirtualClientConfig {
+    pub server_ip: IpAddr,
+    pub hostname: String,
+    pub device_name: String,
+    pub eye_width: u32,
+    pub eye_height: u32,
+    pub refresh_rate: f32,
+    // how long to stay connected after the stream has started
+    pub stream_duration: Duration,
+    // a new certificate is generated if missing. The server must trust the client again in that
+    // case since the pinned fingerprint will not match
+    pub identity: Option<ClientIdentity>,
+    // submitted on the dashboard to trust the client
+    pub pairing_code: String,
+}
+
+impl Default for VirtualClientConfig {
+    fn default() -> Self {
+        Self {
+            server_ip: [127, 0, 0, 1].into(),
+            hostname: "virtual.client.alvr".into(),
+            device_name: "Virtual Headset".into(),
+            eye_width: 1832,
+            eye_height: 1920,
+            refresh_rate: 72.,
+            stream_duration: Duration::from_secs(10),
+            identity: None,
+            pairing_code: format!("{:06}", rand::thread_rng().gen_range(0..1_000_000)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConnectionStage {
+    ControlConnected,
+    ConfigReceived,
+    StartStream,
+    StreamReady,
+    Streaming,
+    ServerRestarting,
+    ServerDisconnected,
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct StreamRecord {
+    pub packets_count: usize,
+    pub bytes_count: usize,
+    pub packet_losses_count: usize,
+    // packets recovered with FEC, they are included in packets_count
+    pub packets_recovered_count: usize,
+    // packets dropped because the receive queue was full
+    pub packets_dropped_count: usize,
+    // packets that arrived out of order, the late ones are not delivered
+    pub packets_reordered_count: usize,
+    pub packets_late_count: usize,
+    pub packets_duplicated_count: usize,
+    // time since the client started
+    pub first_packet_time: Option<Duration>,
+}
+
+impl StreamRecord {
+    fn report<T>(&mut self, packet: &ReceivedPacket<T>, start: Instant) {
+        self.packets_count += 1;
+        self.bytes_count += packet.buffer.len();
+        if packet.had_packet_loss {
+            self.packet_losses_count += 1;
+        }
+        self.first_packet_time
+            .get_or_insert_with(|| start.elapsed());
+    }
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct VirtualClientReport {
+    // stages are recorded in order with the time since the client started
+    pub stages: Vec<(ConnectionStage, Duration)>,
+    pub video: StreamRecord,
+    pub audio: StreamRecord,
+    pub haptics: StreamRecord,
+    pub tracking_packets_sent: usize,
+    // one per probe train received from the server
+    pub probe_reports_sent: usize,
+}
+
+impl VirtualClientReport {
+    // The full handshake has been completed and at least one video packet has been received
+    pub fn is_streaming(&self) -> bool {
+        let stages = self.stages.iter().map(|(s, _)| *s).collect::<Vec<_>>();
+
+        stages.starts_with(&[
+            ConnectionStage::ControlConnected,
+            ConnectionStage::ConfigReceived,
+            ConnectionStage::StartStream,
+            ConnectionStage::StreamReady,
+            ConnectionStage::Streaming,
+        ]) && self.video.packets_count > 0
+    }
+}
+
+struct ReportRecorder {
+    start: Instant,
+    report: Mutex<VirtualClientReport>,
+}
+
+impl ReportRecorder {
+    fn stage(&self, stage: ConnectionStage) {
+        info!("Virtual client stage: {stage:?}");
+        self.report
+            .lock()
+            .stages
+            .push((stage, self.start.elapsed()));
+    }
+}
+
+// Unlike the real client, send the announce packet directly to the server from an ephemeral port.
+// When running on the same machine, CONTROL_PORT is already bound by the server discovery socket.
+// While the client is not trusted, keep announcing so the server connects as soon as it is paired.
+async fn announce_loop(
+    handshake_packet: ClientHandshakePacket,
+    server_ip: IpAddr,
+) -> StrResult<ServerHandshakePacket> {
+    let pairing_code = handshake_packet.reserved2.clone();
+    let mut untrusted_logged = false;
+
+    let socket = UdpSocket::bind((LOCAL_IP, 0)).await.map_err(err!())?;
+
+    let packet_bytes =
+        bincode::serialize(&HandshakePacket::Client(handshake_packet)).map_err(err!())?;
+
+    let mut response_buffer = [0; MAX_HANDSHAKE_PACKET_SIZE_BYTES];
+    loop {
+        socket
+            .send_to(&packet_bytes, (server_ip, CONTROL_PORT))
+            .await
+            .map_err(err!())?;
+
+        tokio::select! {
+            Ok((packet_size, _)) = socket.recv_from(&mut response_buffer) => {
+                if let Ok(HandshakePacket::Server(response)) =
+                    bincode::deserialize(&response_buffer[..packet_size])
+                {
+                    if matches!(response, ServerHandshakePacket::ClientUntrusted) {
+                        if !untrusted_logged {
+                            info!("Client untrusted. Pair it with the code {pairing_code}");
+                            untrusted_logged = true;
+                        }
+                    } else {
+                        return Ok(response);
+                    }
+                }
+            }
+            _ = time::sleep(ANNOUNCE_INTERVAL) => (),
+        }
+    }
+}
+
+async fn record_loop<T: DeserializeOwned>(
+    mut receiver: StreamReceiver<T>,
+    recorder: Arc<ReportRecorder>,
+    select_record: fn(&mut VirtualClientReport) -> &mut StreamRecord,
+) -> StrResult {
+    loop {
+        let packet = receiver.recv().await?;
+
+        let mut report = recorder.report.lock();
+        let record = select_record(&mut report);
+        record.report(&packet, recorder.start);
+        let statistics = receiver.statistics();
+        record.packets_recovered_count = statistics.packets_recovered as _;
+        record.packets_dropped_count = statistics.packets_dropped as _;
+        record.packets_reordered_count = statistics.packets_reordered as _;
+        record.packets_late_count = statistics.packets_late as _;
+        record.packets_duplicated_count = statistics.packets_duplicated as _;
+    }
+}
+
+pub async fn run(config: VirtualClientConfig) -> StrResult<VirtualClientReport> {
+    let recorder = Arc::new(ReportRecorder {
+        start: Instant::now(),
+        report: Mutex::new(VirtualClientReport::default()),
+    });
+
+    let identity = match config.identity.clone() {
+        Some(identity) => identity,
+        None => ClientIdentity::generate()?,
+    };
+
+    let handshake_packet = ClientHandshakePacket {
+        alvr_name: ALVR_NAME.into(),
+        version: ALVR_VERSION.clone(),
+        device_name: config.device_name.clone(),
+        hostname: config.hostname.clone(),
+        reserved1: identity.fingerprint()?,
+        reserved2: config.pairing_code.clone(),
+    };
+
+    let (mut proto_socket, server_ip) = tokio::select! {
+        res = announce_loop(handshake_packet, config.server_ip) => {
+            return fmt_e!("Server refused the virtual client: {:?}", res?);
+        }
+        pair = async {
+            loop {
+                if let Ok(pair) =
+                    ProtoControlSocket::connect_to(PeerType::Server(identity.clone())).await
+                {
+                    break pair;
+                }
+
+                time::sleep(CONTROL_CONNECT_RETRY_PAUSE).await;
+            }
+        } => pair
+    };
+    recorder.stage(ConnectionStage::ControlConnected);
+
+    proto_socket
+        .send(&ClientConnectionResult::ServerAccepted {
+            headset_info: HeadsetInfoPacket {
+                recommended_eye_width: config.eye_width,
+                recommended_eye_height: config.eye_height,
+                available_refresh_rates: vec![config.refresh_rate],
+                preferred_refresh_rate: config.refresh_rate,
+                microphone_sample_rate: 44100,
+                reserved: ALVR_VERSION.to_string(),
+            },
+            server_ip,
+        })
+        .await?;
+    let config_packet = proto_socket.recv::<ClientConfigPacket>().await?;
+    recorder.stage(ConnectionStage::ConfigReceived);
+
+    let stream_key = proto_socket.stream_key();
+    let (mut control_sender, mut control_receiver) =
+        proto_socket.split::<ClientControlPacket, ServerControlPacket>();
+
+    match control_receiver.recv().await? {
+        ServerControlPacket::StartStream => recorder.stage(ConnectionStage::StartStream),
+        ServerControlPacket::Restarting => {
+            recorder.stage(ConnectionStage::ServerRestarting);
+            return Ok(recorder.report.lock().clone());
+        }
+        _ => return fmt_e!("Unexpected packet while waiting for StartStream"),
+    }
+
+    let settings = {
+        let mut session_desc = SessionDesc::default();
+        session_desc
+            .merge_from_json(&json::from_str(&config_packet.session_desc).map_err(err!())?)?;
+        session_desc.to_settings()
+    };
+
+    let stream_socket_builder = StreamSocketBuilder::listen_for_server(
+        settings.connection.stream_port,
+        settings.connection.stream_protocol,
+    )
+    .await?;
+
+    control_sender
+        .send(&ClientControlPacket::StreamReady)
+        .await?;
+    recorder.stage(ConnectionStage::StreamReady);
+
+    let stream_socket = tokio::select! {
+        res = stream_socket_builder.accept_from_server(
+            server_ip,
+            settings.connection.stream_port,
+            stream_key,
+            settings.connection.max_packet_size as _,
+        ) => res?,
+        _ = time::sleep(STREAM_SETUP_TIMEOUT) => {
+            return fmt_e!("Timeout while setting up streams");
+        }
+    };
+    let stream_socket = Arc::new(stream_socket);
+    recorder.stage(ConnectionStage::Streaming);
+
+    let video_receive_loop = record_loop(
+        stream_socket
+            .subscribe_to_stream::<VideoFrameHeaderPacket>(VIDEO)
+            .await?,
+        Arc::clone(&recorder),
+        |report| &mut report.video,
+    );
+    let audio_receive_loop = record_loop(
+        stream_socket.subscribe_to_stream::<()>(AUDIO).await?,
+        Arc::clone(&recorder),
+        |report| &mut report.audio,
+    );
+    let haptics_receive_loop = record_loop(
+        stream_socket
+            .subscribe_to_stream::<Haptics>(HAPTICS)
+            .await?,
+        Arc::clone(&recorder),
+        |report| &mut report.haptics,
+    );
+
+    // Synthesize one tracking and one statistics packet per frame. The statistics refer to the
+    // tracking packet of the previous frame, as if it had been displayed.
+    let tracking_send_loop = {
+        let mut tracking_sender = stream_socket.request_stream(TRACKING, None).await?;
+        let mut statistics_sender = stream_socket.request_stream(STATISTICS, None).await?;
+        let recorder = Arc::clone(&recorder);
+        let frame_interval = Duration::from_secs_f32(1. / config_packet.fps);
+        async move {
+            let mut interval = time::interval(frame_interval);
+            let mut last_target_timestamp = None;
+            loop {
+                interval.tick().await;
+
+                let target_timestamp = recorder.start.elapsed();
+                tracking_sender
+                    .send(&Tracking {
+                        target_timestamp,
+                        device_motions: vec![(
+                            *HEAD_ID,
+                            DeviceMotion {
+                                orientation: Quat::IDENTITY,
+                                position: Vec3::new(0., 1.6, 0.),
+                                ..Default::default()
+                            },
+                        )],
+                        left_hand_skeleton: None,
+                        right_hand_skeleton: None,
+                    })
+                    .await?;
+                recorder.report.lock().tracking_packets_sent += 1;
+
+                if let Some(last_target_timestamp) = last_target_timestamp {
+                    let total_pipeline_latency = target_timestamp - last_target_timestamp;
+                    let video = recorder.report.lock().video.clone();
+                    statistics_sender
+                        .send(&ClientStatistics {
+                            target_timestamp: last_target_timestamp,
+                            frame_interval,
+                            total_pipeline_latency,
+                            average_total_pipeline_latency: total_pipeline_latency,
+                            video_packets_reordered: video.packets_reordered_count as _,
+                            video_packets_late: video.packets_late_count as _,
+                            video_packets_duplicated: video.packets_duplicated_count as _,
+                            ..Default::default()
+                        })
+                        .await?;
+                }
+                last_target_timestamp = Some(target_timestamp);
+            }
+        }
+    };
+
+    let (probe_report_sender, mut probe_report_receiver) = mpsc::unbounded_channel();
+    let probe_receive_loop = alvr_sockets::probe_receive_loop(
+        stream_socket
+            .subscribe_to_stream::<ProbePacketHeader>(PROBE)
+            .await?,
+        move |report| {
+            probe_report_sender.send(report).ok();
+        },
+    );
+
+    let control_loop = {
+        let recorder = Arc::clone(&recorder);
+        async move {
+            let mut keepalive_deadline = Instant::now();
+            loop {
+                tokio::select! {
+                    res = control_receiver.recv() => match res {
+                        Ok(ServerControlPacket::Ping(timestamp)) => {
+                            control_sender.send(&ClientControlPacket::Pong(timestamp)).await.ok();
+                        }
+                        Ok(ServerControlPacket::Restarting) => {
+                            recorder.stage(ConnectionStage::ServerRestarting);
+                            break;
+                        }
+                        Ok(_) => (),
+                        Err(e) => {
+                            info!("Server disconnected. Cause: {e}");
+                            recorder.stage(ConnectionStage::ServerDisconnected);
+                            break;
+                        }
+                    },
+                    Some(report) = probe_report_receiver.recv() => {
+                        control_sender.send(&ClientControlPacket::ProbeReport(report)).await.ok();
+                        recorder.report.lock().probe_reports_sent += 1;
+                    }
+                    _ = time::sleep_until(keepalive_deadline.into()) => {
+                        if control_sender.send(&ClientControlPacket::KeepAlive).await.is_err() {
+                            recorder.stage(ConnectionStage::ServerDisconnected);
+                            break;
+                        }
+                        keepalive_deadline += NETWORK_KEEPALIVE_INTERVAL;
+                    }
+                }
+            }
+
+            StrResult::Ok(())
+        }
+    };
+
+    let send_loop = {
+        let stream_socket = Arc::clone(&stream_socket);
+        async move { stream_socket.send_loop().await }
+    };
+
+    let receive_loop = {
+        let stream_socket = Arc::clone(&stream_socket);
+        async move { stream_socket.receive_loop().await }
+    };
+
+    tokio::select! {
+        res = spawn_cancelable(receive_loop) => {
+            if let Err(e) = res {
+                info!("Server disconnected. Cause: {e}");
+            }
+            recorder.stage(ConnectionStage::ServerDisconnected);
+        },
+        res = spawn_cancelable(send_loop) => res?,
+        res = spawn_cancelable(video_receive_loop) => res?,
+        res = spawn_cancelable(audio_receive_loop) => res?,
+        res = spawn_cancelable(haptics_receive_loop) => res?,
+        res = spawn_cancelable(tracking_send_loop) => res?,
+        res = spawn_cancelable(probe_receive_loop) => res?,
+        res = control_loop => res?,
+        _ = time::sleep(config.stream_duration) => (),
+    }
+
+    let report = recorder.report.lock().clone();
+
+    Ok(report)
+}
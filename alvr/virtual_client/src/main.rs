@@ -0,0 +1,93 @@
+use alvr_common::prelude::*;
+use alvr_sockets::ClientIdentity;
+use alvr_virtual_client::VirtualClientConfig;
+use pico_args::Arguments;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+    time::Duration,
+};
+
+const HELP_STR: &str = r#"
+alvr_virtual_client
+Headless virtual headset for end-to-end tests of the ALVR server.
+
+USAGE:
+    alvr_virtual_client [ARGS]
+
+ARGS:
+    --server-ip <IP>        IP of the server. Default: 127.0.0.1
+    --hostname <NAME>       Hostname used to identify the client. Default: virtual.client.alvr
+    --duration <SECONDS>    Time to keep streaming after the stream started. Default: 10
+    --identity <PATH>       JSON file with the client certificate, created if missing. Without it
+                            the client must be paired again on every run
+    --pairing-code <CODE>   Code to submit on the dashboard to pair the client. Default: random
+"#;
+
+fn load_or_create_identity(path: &Path) -> StrResult<ClientIdentity> {
+    if let Ok(identity_string) = fs::read_to_string(path) {
+        return serde_json::from_str(&identity_string).map_err(err!());
+    }
+
+    let identity = ClientIdentity::generate()?;
+    fs::write(path, serde_json::to_string(&identity).map_err(err!())?).map_err(err!())?;
+
+    Ok(identity)
+}
+
+fn main() {
+    env_logger::init();
+
+    let mut args = Arguments::from_env();
+
+    if args.contains(["-h", "--help"]) {
+        println!("{HELP_STR}");
+        return;
+    }
+
+    let mut config = VirtualClientConfig::default();
+    if let Some(server_ip) = args.opt_value_from_str("--server-ip").unwrap() {
+        config.server_ip = server_ip;
+    }
+    if let Some(hostname) = args.opt_value_from_str("--hostname").unwrap() {
+        config.hostname = hostname;
+    }
+    if let Some(duration_s) = args.opt_value_from_str::<_, u64>("--duration").unwrap() {
+        config.stream_duration = Duration::from_secs(duration_s);
+    }
+    if let Some(pairing_code) = args.opt_value_from_str("--pairing-code").unwrap() {
+        config.pairing_code = pairing_code;
+    }
+    if let Some(identity_path) = args.opt_value_from_str::<_, PathBuf>("--identity").unwrap() {
+        match load_or_create_identity(&identity_path) {
+            Ok(identity) => config.identity = Some(identity),
+            Err(e) => {
+                error!("{e}");
+                process::exit(1);
+            }
+        }
+    }
+
+    if !args.finish().is_empty() {
+        println!("\nWrong arguments.");
+        println!("{HELP_STR}");
+        process::exit(1);
+    }
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    match runtime.block_on(alvr_virtual_client::run(config)) {
+        Ok(report) => {
+            println!("{report:#?}");
+
+            if !report.is_streaming() {
+                error!("The virtual client did not reach the streaming stage");
+                process::exit(1);
+            }
+        }
+        Err(e) => {
+            error!("{e}");
+            process::exit(1);
+        }
+    }
+}
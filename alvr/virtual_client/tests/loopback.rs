@@ -0,0 +1,145 @@
+// Plays the server side of the connection sequence on 127.0.0.1 and checks that the virtual client
+// goes through handshake -> StartStream -> StreamReady -> streaming and records the video frames.
+// It binds the control and stream ports, so it cannot run while a server is running.
+
+use alvr_session::{SessionDesc, SocketProtocolDefaultVariant};
+use alvr_sockets::{
+    ClientConfigPacket, ClientConnectionResult, ClientControlPacket, HandshakePacket, PeerType,
+    ProtoControlSocket, ServerControlPacket, StreamSocketBuilder, Tracking, VideoFrameHeaderPacket,
+    CONTROL_PORT, LOCAL_IP, MAX_HANDSHAKE_PACKET_SIZE_BYTES, TRACKING, VIDEO,
+};
+use alvr_virtual_client::{ConnectionStage, VirtualClientConfig};
+use std::{sync::Arc, time::Duration};
+use tokio::{net::UdpSocket, time};
+
+const FRAMES_COUNT: u64 = 10;
+// Bigger than the packet size, so the frames are fragmented
+const FRAME_SIZE: usize = 100_000;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn virtual_client_streams_from_loopback_server() {
+    let discovery_socket = UdpSocket::bind((LOCAL_IP, CONTROL_PORT)).await.unwrap();
+
+    let client = tokio::spawn(alvr_virtual_client::run(VirtualClientConfig {
+        stream_duration: Duration::from_secs(2),
+        ..Default::default()
+    }));
+
+    let mut buffer = [0; MAX_HANDSHAKE_PACKET_SIZE_BYTES];
+    let (packet_size, _) = discovery_socket.recv_from(&mut buffer).await.unwrap();
+    let fingerprint = match bincode::deserialize(&buffer[..packet_size]).unwrap() {
+        HandshakePacket::Client(handshake_packet) => handshake_packet.reserved1,
+        HandshakePacket::Server(_) => panic!("Expected the client announce"),
+    };
+
+    // The client starts listening after the first announce
+    let (mut proto_socket, client_ip) = loop {
+        if let Ok(pair) = ProtoControlSocket::connect_to(PeerType::AnyClient(vec![(
+            [127, 0, 0, 1].into(),
+            Some(fingerprint.clone()),
+        )]))
+        .await
+        {
+            break pair;
+        }
+
+        time::sleep(Duration::from_millis(100)).await;
+    };
+    assert_eq!(proto_socket.client_fingerprint(), fingerprint);
+
+    assert!(matches!(
+        proto_socket.recv().await.unwrap(),
+        ClientConnectionResult::ServerAccepted { .. }
+    ));
+
+    // Both ends run on the same machine, UDP would bind the same stream port twice
+    let mut session_desc = SessionDesc::default();
+    session_desc
+        .session_settings
+        .connection
+        .stream_protocol
+        .variant = SocketProtocolDefaultVariant::Tcp;
+    let settings = session_desc.to_settings();
+
+    proto_socket
+        .send(&ClientConfigPacket {
+            session_desc: serde_json::to_string(&session_desc).unwrap(),
+            dashboard_url: "".into(),
+            view_resolution_width: 1832,
+            view_resolution_height: 1920,
+            fps: 72.,
+            game_audio_sample_rate: 44100,
+            reserved: "".into(),
+            server_version: None,
+        })
+        .await
+        .unwrap();
+
+    let stream_key = proto_socket.stream_key();
+    let (mut control_sender, mut control_receiver) =
+        proto_socket.split::<ServerControlPacket, ClientControlPacket>();
+
+    control_sender
+        .send(&ServerControlPacket::StartStream)
+        .await
+        .unwrap();
+    while !matches!(
+        control_receiver.recv().await.unwrap(),
+        ClientControlPacket::StreamReady
+    ) {}
+
+    let stream_socket = StreamSocketBuilder::connect_to_client(
+        client_ip,
+        settings.connection.stream_port,
+        settings.connection.stream_protocol,
+        30_000_000,
+        stream_key,
+        settings.connection.max_packet_size as _,
+    )
+    .await
+    .unwrap();
+    let mut video_sender = stream_socket
+        .request_stream::<VideoFrameHeaderPacket>(VIDEO, None)
+        .await
+        .unwrap();
+    let mut tracking_receiver = stream_socket
+        .subscribe_to_stream::<Tracking>(TRACKING)
+        .await
+        .unwrap();
+
+    let stream_socket = Arc::new(stream_socket);
+    tokio::spawn({
+        let stream_socket = Arc::clone(&stream_socket);
+        async move { stream_socket.send_loop().await }
+    });
+    tokio::spawn({
+        let stream_socket = Arc::clone(&stream_socket);
+        async move { stream_socket.receive_loop().await }
+    });
+
+    for video_frame_index in 0..FRAMES_COUNT {
+        let header = VideoFrameHeaderPacket {
+            packet_counter: video_frame_index as _,
+            tracking_frame_index: video_frame_index,
+            video_frame_index,
+            sent_time: 0,
+            frame_byte_size: FRAME_SIZE as _,
+        };
+        let mut buffer = video_sender.new_buffer(&header, FRAME_SIZE).unwrap();
+        buffer.get_mut().extend(vec![0; FRAME_SIZE]);
+        video_sender.send_buffer(buffer).await.unwrap();
+    }
+
+    tracking_receiver.recv().await.unwrap();
+
+    let report = client.await.unwrap().unwrap();
+    assert!(report.is_streaming());
+    assert_eq!(report.video.packets_count, FRAMES_COUNT as usize);
+    assert_eq!(report.video.bytes_count, FRAMES_COUNT as usize * FRAME_SIZE);
+    assert_eq!(report.video.packet_losses_count, 0);
+    assert!(report.tracking_packets_sent > 0);
+    assert!(!report
+        .stages
+        .iter()
+        .any(|(stage, _)| *stage == ConnectionStage::ServerDisconnected));
+}
@@ -8,7 +8,7 @@ define([
         const self = this;
 
         $(document).on("click", ".registerAlvrDriver", () => {
-            $.get("api/driver/register", undefined, (res) => {
+            $.post("api/driver/register", undefined, (res) => {
                 if (res != -1) {
                     Lobibox.notify("success", {
                         size: "mini",
@@ -258,7 +258,7 @@ define([
             });
 
             $("#addFirewallRules").click(() => {
-                $.get("api/firewall-rules/add", undefined, (res) => {
+                $.post("api/firewall-rules/add", undefined, (res) => {
                     if (res == 0) {
                         Lobibox.notify("success", {
                             size: "mini",
@@ -272,7 +272,7 @@ define([
             });
 
             $("#removeFirewallRules").click(() => {
-                $.get("api/firewall-rules/remove", undefined, (res) => {
+                $.post("api/firewall-rules/remove", undefined, (res) => {
                     if (res == 0) {
                         Lobibox.notify("success", {
                             size: "mini",
@@ -315,6 +315,7 @@ define([
             driverList.fillDriverList("registeredDriversInst");
 
             uploadPreset.addUploadPreset("settingUploadPreset", settings.getWebClientId());
+            uploadPreset.addPresetList("settingUploadPreset");
 
             document.title = `ALVR dashboard (server v${version})`;
         });
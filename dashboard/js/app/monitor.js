@@ -26,6 +26,8 @@ define([
         let latencyGraph;
         let framerateGraph;
         let clientConnected = false;
+        // Used to skip the events replayed by the server after reconnecting
+        let lastEvent = { sequence: -1, timestamp: -1 };
 
         function logInit() {
             const url = window.location.href;
@@ -55,6 +57,26 @@ define([
             });
         }
 
+        function eventsInit() {
+            const url = window.location.href;
+            const arr = url.split("/");
+
+            const events_listener = new WebSocket("ws://" + arr[2] + "/api/events");
+
+            events_listener.onerror = (ev) => {
+                console.log("Events error", ev);
+            };
+
+            events_listener.onclose = (ev) => {
+                console.log("Events closed", ev);
+                eventsInit();
+            };
+
+            events_listener.addEventListener("message", function (e) {
+                addEvent(JSON.parse(e.data));
+            });
+        }
+
         function init() {
             let compiledTemplate = _.template(monitorTemplate);
             const template = compiledTemplate(i18n);
@@ -66,6 +88,7 @@ define([
 
             $(document).ready(() => {
                 logInit();
+                eventsInit();
                 initNotificationLevel();
                 initAddClientModal(templateAddClient);
                 initPerformanceGraphs();
@@ -216,7 +239,7 @@ define([
 
                         $.ajax({
                             type: "POST",
-                            url: "api/client/trust",
+                            url: "api/client/add-ip",
                             contentType: "application/json;charset=UTF-8",
                             data: JSON.stringify([_hostmane, ip]),
                         });
@@ -257,18 +280,29 @@ define([
 
             $("#newClientsDiv" + " table")
                 .append(`<tr><td type="${displayName}" hostname="${hostname}" id="newClient_${id}">${displayName} (${hostname}) </td>
-            <td><button type="button" id="btnAddTrustedClient_${id}" class="btn btn-primary">${i18n["addTrustedClient"]}</button>
-            </td></tr>`);
+            <td><div class="input-group">
+            <input type="text" id="pairingCode_${id}" class="form-control" placeholder="${i18n["pairingCode"]}">
+            <div class="input-group-append"><button type="button" id="btnAddTrustedClient_${id}" class="btn btn-primary">${i18n["addTrustedClient"]}</button></div>
+            </div></td></tr>`);
 
-            const _hostmane = hostname;
             // this call need const variable unless you want them overwriten by the next call.
             $(document).ready(() => {
                 $("#btnAddTrustedClient_" + id).click(() => {
                     $.ajax({
                         type: "POST",
-                        url: "api/client/trust",
+                        url: "api/client/pair",
                         contentType: "application/json;charset=UTF-8",
-                        data: JSON.stringify([_hostmane, null]),
+                        data: JSON.stringify($("#pairingCode_" + id).val().trim()),
+                        error: () => {
+                            Lobibox.notify("error", {
+                                size: "mini",
+                                rounded: true,
+                                delayIndicator: false,
+                                sound: false,
+                                position: "bottom right",
+                                msg: i18n["error_WrongPairingCode"],
+                            });
+                        },
                     });
                 });
             });
@@ -329,60 +363,67 @@ define([
         }
 
         function addLogLine(line) {
-            let idObject = undefined;
-
             console.log(line);
 
-            const json_start_idx = line.indexOf("#{");
-            const json_end_idx = line.indexOf("}#");
-            if (json_start_idx != -1 && json_end_idx != -1) {
-                idObject = line.substring(json_start_idx + 1, json_end_idx + 1);
-            }
-
             const split = line.split(" ");
             line = line.replace(split[0] + " " + split[1], "");
 
             const skipWithoutId = $("#_root_extra_excludeNotificationsWithoutId").prop("checked");
+            if (!skipWithoutId) {
+                notify(split[1], undefined, line);
+            }
 
-            let addToTable = true;
-            if (idObject !== undefined) {
-                idObject = JSON.parse(idObject);
-                handleJson(idObject);
-                switch (idObject.id) {
-                    case "Statistics":
-                        addToTable = false;
-                        break;
-                    case "GraphStatistics":
-                        addToTable = false;
-                        break;
-                    default:
-                        line = idObject.id;
-                        break;
-                }
+            addTableRow(split[0], split[1], line);
+        }
+
+        function addEvent(event) {
+            if (event.sequence <= lastEvent.sequence && event.timestamp <= lastEvent.timestamp) {
+                return;
             }
+            lastEvent = event;
 
-            if (notificationLevels.includes(split[1].trim())) {
-                if (!(skipWithoutId && idObject === undefined) && Lobibox.notify.list.length < 2) {
-                    Lobibox.notify(getNotificationType(split[1]), {
-                        size: "mini",
-                        rounded: true,
-                        delayIndicator: false,
-                        sound: false,
-                        position: "bottom left",
-                        title: getI18nNotification(idObject, line, split[1]).title,
-                        msg: getI18nNotification(idObject, line, split[1]).msg,
-                    });
-                }
+            // Log lines are received from the log websocket
+            if (event.id === "Log") {
+                return;
             }
 
-            if (addToTable) {
-                const row = `<tr><td>${split[0]}</td><td>${
-                    split[1]
-                }</td><td>${line.trim()}</td></tr>`;
-                $("#loggingTable").append(row);
-                if ($("#loggingTable").children().length > 500) {
-                    $("#loggingTable tr").first().remove();
-                }
+            console.log(event);
+
+            handleJson(event);
+            switch (event.id) {
+                case "Statistics":
+                case "GraphStatistics":
+                case "LatencyStatistics":
+                case "NetworkStatistics":
+                    return;
+                default:
+                    break;
+            }
+
+            const time = new Date(event.timestamp).toTimeString().split(" ")[0];
+            notify("[INFO]", event, event.id);
+            addTableRow(time, "[INFO]", event.id);
+        }
+
+        function notify(level, idObject, line) {
+            if (notificationLevels.includes(level.trim()) && Lobibox.notify.list.length < 2) {
+                Lobibox.notify(getNotificationType(level), {
+                    size: "mini",
+                    rounded: true,
+                    delayIndicator: false,
+                    sound: false,
+                    position: "bottom left",
+                    title: getI18nNotification(idObject, line, level).title,
+                    msg: getI18nNotification(idObject, line, level).msg,
+                });
+            }
+        }
+
+        function addTableRow(time, level, line) {
+            const row = `<tr><td>${time}</td><td>${level}</td><td>${line.trim()}</td></tr>`;
+            $("#loggingTable").append(row);
+            if ($("#loggingTable").children().length > 500) {
+                $("#loggingTable tr").first().remove();
             }
         }
 
@@ -422,6 +463,7 @@ define([
         function handleJson(json) {
             switch (json.id) {
                 case "Statistics":
+                case "NetworkStatistics":
                     updateStatistics(json.data);
                     break;
                 case "GraphStatistics":
@@ -894,7 +936,8 @@ define([
 
         function updateStatistics(statistics) {
             for (const stat in statistics) {
-                $("#statistic_" + stat).text(statistics[stat]);
+                // null if not measured yet
+                $("#statistic_" + stat).text(statistics[stat] !== null ? statistics[stat] : "-");
             }
         }
 
@@ -5,7 +5,8 @@ define({
         // Clients container
         clients: "Clients",
         newClients: "New Clients",
-        addTrustedClient: "Trust",
+        addTrustedClient: "Pair",
+        pairingCode: "Code shown in the headset",
         trustedClients: "Trusted Clients",
         removeTrustedClient: "Remove",
         troubleshooting:
@@ -41,6 +42,8 @@ define({
         encodeLatencyMax: "Encode latency max",
         transportLatency: "Transport latency",
         decodeLatency: "Decoder latency",
+        networkBandwidth: "Network bandwidth",
+        networkLoss: "Network loss",
         fecPercentage: "Fec percentage",
         fecFailureTotal: "Fec failure total",
         fecFailureInSecond: "Fec failure / s",
@@ -58,6 +61,7 @@ define({
         error_DuplicateHostname: "A device with this hostname is already registered",
         error_DuplicateIp: "This IP address is already registed on this device",
         error_InvalidIp: "Not a valid IPv4 formatted address",
+        error_WrongPairingCode: "No client is showing this pairing code",
         // Performance graphs tab
         performanceGraphs: "Performance graphs",
         performanceNetwork: "Network",
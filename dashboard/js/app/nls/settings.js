@@ -120,6 +120,9 @@ define({
             "Bitrate light load threshold", // adv
         "_root_video_adaptiveBitrate_content_bitrateLightLoadThreshold.description":
             "Limit increasing bitrate if sent rate is below threshold percentage of bitrate. Prevents stutters caused when switching from simple scenes to complex scenes", // adv
+        "_root_video_adaptiveBitrate_content_useNetworkEstimate.name": "Use network estimate", // adv
+        "_root_video_adaptiveBitrate_content_useNetworkEstimate.description":
+            "Keep the bitrate below the bandwidth measured by probing the network, and decrease it when the probe packets are lost", // adv
         // Audio tab
         "_root_audio_tab.name": "Audio",
         "_root_audio_linuxBackend-choice-.name": "Linux backend",
@@ -298,17 +301,22 @@ define({
         // Connection tab
         "_root_connection_tab.name": "Connection",
         "_root_connection_clientDiscovery.name": "Client discovery",
-        "_root_connection_clientDiscovery_content_autoTrustClients.name":
-            "Trust clients automatically (not recommended)", // adv
         "_root_connection_webServerPort.name": "Web server port",
+        "_root_connection_webServerLoopbackOnly.name": "Web server local only", // adv
+        "_root_connection_webServerLoopbackOnly.description":
+            "Accept dashboard connections only from this PC. Requires a restart of SteamVR.", // adv
         "_root_connection_streamProtocol-choice-.name": "Streaming protocol",
         "_root_connection_streamProtocol-choice-.description":
-            "Network protocol used to stream data between client and server. UDP works best at low bitrates (<30), Throttled UDP works best at medium bitrates (~100), TCP works at any bitrate.",
+            "Network protocol used to stream data between client and server. UDP works best at low bitrates (<30), Throttled UDP works best at medium bitrates (~100), TCP works at any bitrate. QUIC sends each stream separately, so video loss does not stall tracking.",
         "_root_connection_streamProtocol_udp-choice-.name": "UDP",
         "_root_connection_streamProtocol_throttledUdp-choice-.name": "Throttled UDP",
         "_root_connection_streamProtocol_tcp-choice-.name": "TCP",
+        "_root_connection_streamProtocol_quic-choice-.name": "QUIC",
         "_root_connection_streamPort.name": "Server streaming port", // adv
         "_root_connection_streamPort.description": "Port used by the server to receive packets.", // adv
+        "_root_connection_maxSpectators.name": "Max spectators",
+        "_root_connection_maxSpectators.description":
+            "Number of additional headsets that can connect while a client is streaming. Spectators receive the same video and audio but their tracking and inputs are ignored. They are disconnected when the first client disconnects.\nRequires the TCP or QUIC streaming protocol.",
         "_root_connection_aggressiveKeyframeResend.name": "Aggressive keyframe resend",
         "_root_connection_aggressiveKeyframeResend.description":
             "Decrease minimum interval between keyframes from 100 ms to 5 ms. \nUsed only when packet loss is detected. \nImproves experience on networks with packet loss.",
@@ -318,6 +326,9 @@ define({
         "_root_connection_onDisconnectScript.name": "On disconnect script",
         "_root_connection_onDisconnectScript.description":
             "This script/executable will be run asynchronously when headset disconnects and on SteamVR shutdown.\nEnvironment variable ACTION will be set to &#34;disconnect&#34; (without quotes).",
+        "_root_connection_maxPacketSize.name": "Max packet size", // adv
+        "_root_connection_maxPacketSize.description":
+            "Size in bytes of the biggest packet sent on the streaming socket. Bigger video frames and messages are split into multiple packets. Lower it if your network drops big packets, for example when using a VPN.", // adv
         // Extra tab
         "_root_extra_tab.name": "Extra",
         "_root_extra_theme-choice-.name": "Theme",
@@ -334,7 +345,23 @@ define({
         "_root_extra_updateChannel_noUpdates-choice-.name": "No updates",
         "_root_extra_updateChannel_stable-choice-.name": "Stable",
         "_root_extra_updateChannel_nightly-choice-.name": "Nightly",
-        "_root_extra_logToDisk.name": "Log to disk (session_log.txt)",
+        "_root_extra_logToDisk.name": "Log to disk (session_logs folder)",
+        "_root_extra_logFiles.name": "Log files", // adv
+        "_root_extra_logFiles_format-choice-.name": "Format", // adv
+        "_root_extra_logFiles_format-choice-.description":
+            "JSON lines contain the level, the module and the client of each message, for log analysis tools.", // adv
+        "_root_extra_logFiles_format_text-choice-.name": "Text", // adv
+        "_root_extra_logFiles_format_jsonLines-choice-.name": "JSON lines", // adv
+        "_root_extra_logFiles_maxFileSizeMb.name": "Maximum file size (MB)", // adv
+        "_root_extra_logFiles_maxFileAgeHours.name": "Maximum file age (hours)", // adv
+        "_root_extra_logFiles_maxFileAgeHours.description":
+            "A new log file is started when the current one reaches the maximum size or age.", // adv
+        "_root_extra_logFiles_keptSessions.name": "Kept sessions", // adv
+        "_root_extra_logFiles_keptSessions.description":
+            "Number of previous server sessions whose logs are kept.", // adv
+        "_root_extra_recordStreams.name": "Record streams", // adv
+        "_root_extra_recordStreams.description":
+            "Save tracking, video, audio and statistics of each connection to the recordings folder next to the log, for offline debugging. Recordings grow quickly.", // adv
         "_root_extra_notificationLevel-choice-.name": "Notification level", // adv
         "_root_extra_notificationLevel-choice-.description":
             "At which level notification will be generated. From less details to all details: \n- Error \n- Warning \n- Informations \n- Debug", // adv
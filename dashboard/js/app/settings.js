@@ -202,11 +202,27 @@ define([
                     }
                 },
                 error: function (res) {
+                    // the server lists the invalid settings in the error details
+                    const details = (res.responseJSON && res.responseJSON.details) || [];
+                    Lobibox.notify("error", {
+                        size: "mini",
+                        rounded: true,
+                        delayIndicator: false,
+                        sound: false,
+                        title: getI18n("settingsStoreError").name,
+                        msg:
+                            details.map((d) => d.path + ": " + d.message).join("\n") ||
+                            getI18n("settingsStoreError").description,
+                    });
+
                     console.log("FAILED");
-                    updating = true;
-                    session = res;
-                    setProperties(res.sessionSettings, "_root");
-                    updating = false;
+                    // go back to the settings stored by the server
+                    $.get("/api/session/load", (storedSession) => {
+                        updating = true;
+                        session = storedSession;
+                        setProperties(storedSession.sessionSettings, "_root");
+                        updating = false;
+                    });
                 },
             });
         };
@@ -325,7 +341,7 @@ define([
 
         function restartSteamVR() {
             const triggerRestart = () => {
-                $.get("restart-steamvr", undefined, (res) => {
+                $.post("restart-steamvr", undefined, (res) => {
                     if (res == 0) {
                         Lobibox.notify("success", {
                             size: "mini",
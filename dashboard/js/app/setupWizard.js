@@ -52,7 +52,7 @@ define([
                 $("#GPUSupportText").text(getAndCheckGPUSupport());
 
                 $("#addFirewall").click(() => {
-                    $.get("api/firewall-rules/add", undefined, (res) => {
+                    $.post("api/firewall-rules/add", undefined, (res) => {
                         if (res == -1) {
                             Lobibox.notify("error", {
                                 size: "mini",
@@ -105,5 +105,50 @@ define(["jquery", "lib/lodash", "text!app/templates/uploadPreset.html"], functio
 
             $("#" + elementId).prepend(template);
         };
+
+        // Presets shipped with the server are applied with one click
+        $(document).on("click", ".applyPresetButton", (ev) => {
+            const presetId = $(ev.currentTarget).data("preset-id");
+            $.ajax({
+                type: "POST",
+                url: "/api/presets/apply",
+                contentType: "application/json;charset=UTF-8",
+                data: JSON.stringify(presetId),
+                processData: false,
+                success: function () {
+                    // the settings page is built from the session, reload it
+                    window.location.reload();
+                },
+                error: function (res) {
+                    Lobibox.notify("error", {
+                        size: "mini",
+                        rounded: true,
+                        delayIndicator: false,
+                        sound: false,
+                        title: "Error while applying the preset",
+                        msg: res.responseText,
+                    });
+                },
+            });
+        });
+
+        this.addPresetList = function (elementId) {
+            $.get("/api/presets", (presets) => {
+                presets
+                    .slice()
+                    .reverse()
+                    .forEach((preset) => {
+                        const button = $("<a>", {
+                            class: "applyPresetButton",
+                            title: preset.description,
+                        })
+                            .data("preset-id", preset.id)
+                            .append($("<i>", { class: "fa fa-magic fa-lg" }))
+                            .append(document.createTextNode(" " + preset.name));
+
+                        $("#" + elementId).after($("<li>").append(button));
+                    });
+            });
+        };
     })();
 });